@@ -0,0 +1,390 @@
+use std::collections::HashMap;
+
+use ets_sched::{Metrics, Pid, Simulator, Slice, Time, Workload};
+use ratatui::{
+    Frame,
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, Cell, Paragraph, Row, Table},
+};
+
+use crate::gantt::{GanttChart, POLICIES, Window, colors, legend_line, simulate};
+
+/// Heading, value and whether lower is better.
+type Column = (&'static str, fn(&Metrics) -> f64, bool);
+
+/// Policies compared when the tab opens, by name.
+const DEFAULT_POLICIES: [&str; 2] = ["FCFS", "CFS"];
+
+struct Run {
+    policy: usize,
+    sim: Simulator,
+    /// First tick at which it departs from the first run.
+    divergence: Option<Time>,
+}
+
+/// Comparison tab: the same workload under several policies, one Gantt chart
+/// above the other on a shared time axis, with their metrics in one table.
+pub struct ComparisonView {
+    workload: Workload,
+    ncpus: usize,
+    runs: Vec<Run>,
+    /// Run whose policy `p` changes.
+    selected: usize,
+    /// Top CPU row of every chart.
+    first_cpu: usize,
+    colors: HashMap<Pid, Color>,
+    window: Window,
+}
+
+impl ComparisonView {
+    pub fn new(workload: Workload, ncpus: usize) -> ComparisonView {
+        let mut view = ComparisonView {
+            colors: colors(&workload.tasks),
+            workload,
+            ncpus: ncpus.clamp(1, 64),
+            runs: Vec::new(),
+            selected: 0,
+            first_cpu: 0,
+            window: Window::default(),
+        };
+        for name in DEFAULT_POLICIES {
+            let policy = POLICIES.iter().position(|&(n, _)| n == name).unwrap();
+            view.runs.push(view.run(policy));
+        }
+        view.update_divergence();
+        view
+    }
+
+    pub fn set_workload(&mut self, workload: Workload) {
+        self.colors = colors(&workload.tasks);
+        self.workload = workload;
+        for i in 0..self.runs.len() {
+            self.runs[i] = self.run(self.runs[i].policy);
+        }
+        self.update_divergence();
+    }
+
+    fn run(&self, policy: usize) -> Run {
+        Run {
+            policy,
+            sim: simulate(policy, &self.workload, self.ncpus),
+            divergence: None,
+        }
+    }
+
+    fn update_divergence(&mut self) {
+        let (first, rest) = self.runs.split_first_mut().unwrap();
+        for run in rest {
+            run.divergence = first_divergence(first.sim.trace(), run.sim.trace());
+        }
+        let end = self.runs.iter().map(|r| r.sim.now()).max().unwrap_or(0);
+        self.window.scroll(0, end);
+    }
+
+    /// Adds a run with the policy after the last one.
+    pub fn add_policy(&mut self) {
+        let last = self.runs.last().map_or(0, |r| r.policy);
+        self.runs.push(self.run((last + 1) % POLICIES.len()));
+        self.selected = self.runs.len() - 1;
+        self.update_divergence();
+    }
+
+    /// Drops the selected run, keeping at least two.
+    pub fn remove_policy(&mut self) {
+        if self.runs.len() > 2 {
+            self.runs.remove(self.selected);
+            self.selected = self.selected.min(self.runs.len() - 1);
+            self.update_divergence();
+        }
+    }
+
+    pub fn next_policy(&mut self) {
+        let policy = (self.runs[self.selected].policy + 1) % POLICIES.len();
+        self.runs[self.selected] = self.run(policy);
+        self.update_divergence();
+    }
+
+    pub fn select(&mut self, step: isize) {
+        self.selected = self
+            .selected
+            .saturating_add_signed(step)
+            .min(self.runs.len() - 1);
+    }
+
+    pub fn scroll(&mut self, columns: i64) {
+        let end = self.runs.iter().map(|r| r.sim.now()).max().unwrap_or(0);
+        self.window.scroll(columns, end);
+    }
+
+    pub fn scroll_home(&mut self) {
+        self.window.offset = 0;
+    }
+
+    pub fn scroll_cpus(&mut self, rows: isize) {
+        self.first_cpu = self
+            .first_cpu
+            .saturating_add_signed(rows)
+            .min(self.ncpus - 1);
+    }
+
+    pub fn zoom_in(&mut self) {
+        self.window.zoom_in();
+    }
+
+    pub fn zoom_out(&mut self) {
+        self.window.zoom_out();
+    }
+
+    pub fn render(&self, frame: &mut Frame, area: Rect) {
+        let mut constraints = vec![Constraint::Length(1)];
+        constraints.extend(self.runs.iter().map(|_| Constraint::Fill(1)));
+        constraints.push(Constraint::Length(1));
+        constraints.push(Constraint::Length(self.runs.len() as u16 + 3));
+        let areas = Layout::vertical(constraints).split(area);
+
+        frame.render_widget(
+            Paragraph::new(format!(
+                " CPUs: {} | tiempo {}..{} | zoom {} ms/col | ░ difiere de la primera | \
+                 ←/→ desplazar, +/- zoom, RePág/AvPág CPUs, ↑/↓ elegir, p política, \
+                 a añadir, x quitar",
+                self.ncpus,
+                self.window.offset,
+                self.window.end(area.width),
+                self.window.zoom,
+            )),
+            areas[0],
+        );
+
+        let reference = self.runs[0].sim.trace();
+        for (i, run) in self.runs.iter().enumerate() {
+            let (name, _) = POLICIES[run.policy];
+            let divergence = match (i, run.divergence) {
+                (0, _) => "referencia".to_string(),
+                (_, Some(t)) => format!("diverge en t={t}"),
+                (_, None) => "igual que la primera".to_string(),
+            };
+            let border = if i == self.selected {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default()
+            };
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .border_style(border)
+                .title(format!(" {}. {name}: {divergence} ", i + 1));
+            let chart = areas[1 + i];
+            frame.render_widget(
+                GanttChart {
+                    trace: run.sim.trace(),
+                    reference: (i > 0).then_some(reference),
+                    ncpus: self.ncpus,
+                    first_cpu: self.first_cpu,
+                    window: self.window,
+                    colors: &self.colors,
+                },
+                block.inner(chart),
+            );
+            frame.render_widget(block, chart);
+        }
+
+        let legend = areas[1 + self.runs.len()];
+        frame.render_widget(
+            Paragraph::new(legend_line(&self.workload.tasks, &self.colors)),
+            legend,
+        );
+        self.render_metrics(frame, areas[2 + self.runs.len()]);
+    }
+
+    fn render_metrics(&self, frame: &mut Frame, area: Rect) {
+        let metrics: Vec<Metrics> = self.runs.iter().map(|r| r.sim.metrics()).collect();
+        let columns: [Column; 7] = [
+            ("Retorno", Metrics::avg_turnaround, true),
+            ("Espera", Metrics::avg_waiting, true),
+            ("Respuesta", Metrics::avg_response, true),
+            ("Tareas/s", Metrics::throughput, false),
+            ("Uso CPU", |m| m.utilization() * 100.0, false),
+            ("Cambios", |m| m.context_switches as f64, true),
+            ("Equidad", Metrics::fairness, false),
+        ];
+        let best: Vec<f64> = columns
+            .iter()
+            .map(|&(_, value, lower)| {
+                let values = metrics.iter().map(value);
+                if lower {
+                    values.fold(f64::INFINITY, f64::min)
+                } else {
+                    values.fold(f64::NEG_INFINITY, f64::max)
+                }
+            })
+            .collect();
+
+        let header = ["Política"]
+            .into_iter()
+            .chain(columns.iter().map(|&(name, _, _)| name))
+            .map(Cell::from)
+            .collect::<Row>()
+            .style(
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            );
+        let rows = self.runs.iter().zip(&metrics).map(|(run, m)| {
+            let name = format!("{} ({})", POLICIES[run.policy].0, m.finished());
+            let cells = columns.iter().zip(&best).map(|(&(_, value, _), &best)| {
+                let value = value(m);
+                let style = if value == best {
+                    Style::default()
+                        .fg(Color::Green)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                Cell::from(format!("{value:.2}")).style(style)
+            });
+            Row::new([Cell::from(name)].into_iter().chain(cells))
+        });
+        let mut widths = vec![Constraint::Min(18)];
+        widths.extend(columns.iter().map(|_| Constraint::Length(10)));
+        frame.render_widget(
+            Table::new(rows, widths).header(header).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(" Métricas (mejor en verde) "),
+            ),
+            area,
+        );
+    }
+}
+
+/// First tick at which some CPU runs a different task, or idles when the
+/// other does not.
+pub fn first_divergence(a: &[Slice], b: &[Slice]) -> Option<Time> {
+    let ncpus = a.iter().chain(b).map(|s| s.cpu + 1).max().unwrap_or(0);
+    (0..ncpus)
+        .filter_map(|cpu| {
+            let a: Vec<&Slice> = a.iter().filter(|s| s.cpu == cpu).collect();
+            let b: Vec<&Slice> = b.iter().filter(|s| s.cpu == cpu).collect();
+            cpu_divergence(&a, &b)
+        })
+        .min()
+}
+
+fn cpu_divergence(a: &[&Slice], b: &[&Slice]) -> Option<Time> {
+    let (mut i, mut j, mut t) = (0, 0, 0);
+    loop {
+        while a.get(i).is_some_and(|s| s.end <= t) {
+            i += 1;
+        }
+        while b.get(j).is_some_and(|s| s.end <= t) {
+            j += 1;
+        }
+        let running = |s: Option<&&Slice>| s.filter(|s| s.start <= t).and_then(|s| s.pid);
+        if running(a.get(i)) != running(b.get(j)) {
+            return Some(t);
+        }
+        // Next time either side changes.
+        t = [a.get(i), b.get(j)]
+            .into_iter()
+            .flatten()
+            .map(|s| if s.start > t { s.start } else { s.end })
+            .min()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::{Terminal, backend::TestBackend};
+
+    use ets_sched::Task;
+
+    use super::*;
+
+    fn slice(cpu: usize, pid: Option<Pid>, start: Time, end: Time) -> Slice {
+        Slice {
+            cpu,
+            pid,
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn finds_the_first_different_decision() {
+        let a = [
+            slice(0, Some(1), 0, 4),
+            slice(1, Some(2), 0, 3),
+            slice(0, Some(3), 4, 6),
+        ];
+        let mut b = a;
+        assert_eq!(first_divergence(&a, &b), None);
+        // Same tasks, merged differently, on CPU 1.
+        b[1] = slice(1, Some(2), 0, 2);
+        assert_eq!(first_divergence(&a, &b), Some(2));
+        b[1] = slice(1, Some(2), 0, 3);
+        b[2] = slice(0, Some(1), 4, 6);
+        assert_eq!(first_divergence(&a, &b), Some(4));
+        // A run that goes on after the other ended.
+        let longer = [a[0], a[1], a[2], slice(0, Some(4), 6, 7)];
+        assert_eq!(first_divergence(&a, &longer), Some(6));
+    }
+
+    #[test]
+    fn compares_policies_on_one_workload() {
+        // The convoy: FCFS runs the long task first, SRTF does not.
+        let tasks: Vec<Task> = [(1, 0, 24), (2, 1, 3), (3, 2, 3)]
+            .into_iter()
+            .map(|(pid, arrival, burst)| {
+                Task::builder()
+                    .pid(pid)
+                    .arrival(arrival)
+                    .burst(burst)
+                    .build()
+            })
+            .collect();
+        let mut view = ComparisonView::new(tasks.into(), 1);
+        assert_eq!(view.runs.len(), 2);
+        view.select(1);
+        while POLICIES[view.runs[1].policy].0 != "SRTF" {
+            view.next_policy();
+        }
+        assert_eq!(view.runs[1].divergence, Some(1));
+
+        view.add_policy();
+        assert_eq!(view.runs.len(), 3);
+        view.select(-1);
+        view.remove_policy();
+        view.remove_policy();
+        assert_eq!(view.runs.len(), 2);
+        assert_eq!(POLICIES[view.runs[0].policy].0, "FCFS");
+
+        let mut terminal = Terminal::new(TestBackend::new(120, 20)).unwrap();
+        terminal
+            .draw(|frame| view.render(frame, frame.area()))
+            .unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content
+            .iter()
+            .map(|c| c.symbol())
+            .collect();
+        assert!(screen.contains("1. FCFS: referencia"));
+        // HRRN also runs the long task first here.
+        assert!(screen.contains("2. HRRN: igual que la primera"));
+    }
+
+    #[test]
+    fn charts_scroll_through_the_cpus() {
+        let tasks: Vec<Task> = (1..=8)
+            .map(|pid| Task::builder().pid(pid).burst(10).build())
+            .collect();
+        let mut view = ComparisonView::new(tasks.into(), 4);
+        view.scroll_cpus(2);
+        assert_eq!(view.first_cpu, 2);
+        view.scroll_cpus(10);
+        assert_eq!(view.first_cpu, 3);
+        view.scroll_cpus(-10);
+        assert_eq!(view.first_cpu, 0);
+    }
+}
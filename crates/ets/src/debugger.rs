@@ -0,0 +1,364 @@
+use std::collections::HashMap;
+
+use ets_sched::{CpuState, Debugger, Event, EventKind, Pid, Simulator, Time, Workload};
+use ratatui::{
+    Frame,
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, Cell, Paragraph, Row, Table, Wrap},
+};
+
+use crate::gantt::{GanttChart, LABEL_WIDTH, POLICIES, Window, colors, simulator};
+
+/// CPUs shown side by side.
+const VISIBLE_CPUS: usize = 4;
+
+/// Debugger tab: runs a simulation one event, or one tick in fixed-tick
+/// mode, at a time, forwards and backwards, showing every runqueue as the
+/// policy sees it.
+pub struct DebuggerView {
+    workload: Workload,
+    ncpus: usize,
+    policy: usize,
+    fixed_tick: bool,
+    debugger: Debugger,
+    colors: HashMap<Pid, Color>,
+    /// Time typed so far for "go to".
+    target: String,
+    first_cpu: usize,
+}
+
+impl DebuggerView {
+    pub fn new(workload: Workload, ncpus: usize) -> DebuggerView {
+        let ncpus = ncpus.clamp(1, 64);
+        DebuggerView {
+            debugger: Debugger::new(simulator(0, &workload, ncpus)),
+            colors: colors(&workload.tasks),
+            workload,
+            ncpus,
+            policy: 0,
+            fixed_tick: false,
+            target: String::new(),
+            first_cpu: 0,
+        }
+    }
+
+    pub fn set_workload(&mut self, workload: Workload) {
+        self.colors = colors(&workload.tasks);
+        self.workload = workload;
+        self.restart();
+    }
+
+    pub fn next_policy(&mut self) {
+        self.policy = (self.policy + 1) % POLICIES.len();
+        self.restart();
+    }
+
+    pub fn toggle_fixed_tick(&mut self) {
+        self.fixed_tick = !self.fixed_tick;
+        self.restart();
+    }
+
+    pub fn restart(&mut self) {
+        let mut sim = simulator(self.policy, &self.workload, self.ncpus);
+        sim.fixed_tick = self.fixed_tick;
+        self.debugger = Debugger::new(sim);
+    }
+
+    pub fn step(&mut self) {
+        self.debugger.step();
+    }
+
+    pub fn step_event(&mut self) {
+        self.debugger.step_event();
+    }
+
+    pub fn step_back(&mut self) {
+        self.debugger.step_back();
+    }
+
+    pub fn type_digit(&mut self, digit: char) {
+        if self.target.len() < 9 {
+            self.target.push(digit);
+        }
+    }
+
+    pub fn erase_digit(&mut self) {
+        self.target.pop();
+    }
+
+    /// Goes to the typed time, backwards or forwards.
+    pub fn go_to_target(&mut self) {
+        if let Ok(time) = self.target.parse::<Time>() {
+            self.debugger.seek(time);
+        }
+        self.target.clear();
+    }
+
+    pub fn scroll_cpus(&mut self, step: isize) {
+        self.first_cpu = self
+            .first_cpu
+            .saturating_add_signed(step)
+            .min(self.ncpus - 1);
+    }
+
+    pub fn render(&self, frame: &mut Frame, area: Rect) {
+        let sim = self.debugger.sim();
+        let shown = (self.ncpus - self.first_cpu).min(VISIBLE_CPUS);
+        let [info, chart, cpus, events] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(shown as u16 + 3),
+            Constraint::Fill(2),
+            Constraint::Fill(1),
+        ])
+        .areas(area);
+
+        let (name, _) = POLICIES[self.policy];
+        let state = if sim.is_done() { " (terminada)" } else { "" };
+        let step = if self.fixed_tick { "tick" } else { "evento" };
+        frame.render_widget(
+            Paragraph::new(format!(
+                " Política: {name} | t = {} ms{state} | paso: {step} | ir a: {}_ | \
+                 → paso, ← atrás, e siguiente evento, 0-9 + Enter ir a t, \
+                 Inicio reiniciar, t ticks/eventos, ↑/↓ CPUs, p política",
+                sim.now(),
+                self.target,
+            )),
+            info,
+        );
+
+        // The last stretch of the run, ending now.
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(" Hasta ahora ");
+        let inner = block.inner(chart);
+        frame.render_widget(block, chart);
+        let columns = inner.width.saturating_sub(LABEL_WIDTH) as Time;
+        frame.render_widget(
+            GanttChart {
+                trace: sim.trace(),
+                reference: None,
+                ncpus: self.ncpus,
+                first_cpu: self.first_cpu,
+                window: Window {
+                    offset: sim.now().saturating_sub(columns),
+                    zoom: 1,
+                },
+                colors: &self.colors,
+            },
+            inner,
+        );
+
+        let areas = Layout::horizontal(vec![Constraint::Fill(1); shown]).split(cpus);
+        for (cpu, &area) in (self.first_cpu..).zip(areas.iter()) {
+            self.render_cpu(frame, area, &self.debugger.cpu_state(cpu));
+        }
+
+        let (events, devices) = if self.workload.devices.is_empty() {
+            (events, None)
+        } else {
+            let [events, devices] =
+                Layout::horizontal([Constraint::Fill(2), Constraint::Fill(1)]).areas(events);
+            (events, Some(devices))
+        };
+        if let Some(area) = devices {
+            self.render_devices(frame, area);
+        }
+
+        let lines: Vec<Line> = sim
+            .events()
+            .iter()
+            .rev()
+            .take(events.height.saturating_sub(2) as usize)
+            .map(|event| Line::from(describe(sim, event)))
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(" Eventos (el último arriba) "),
+            ),
+            events,
+        );
+    }
+
+    /// Each device with the task it serves first and then those waiting.
+    fn render_devices(&self, frame: &mut Frame, area: Rect) {
+        let sim = self.debugger.sim();
+        let lines: Vec<Line> = sim
+            .devices()
+            .enumerate()
+            .map(|(id, device)| {
+                let queue = sim.device_queue(id);
+                let state = match queue.split_first() {
+                    None => "libre".to_string(),
+                    Some((first, [])) => format!("sirve a {first}"),
+                    Some((first, rest)) => {
+                        let rest: Vec<String> = rest.iter().map(Pid::to_string).collect();
+                        format!("sirve a {first}, esperan {}", rest.join(", "))
+                    }
+                };
+                Line::from(format!(" {}: {state}", device.name))
+            })
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(" Dispositivos "),
+            ),
+            area,
+        );
+    }
+
+    fn render_cpu(&self, frame: &mut Frame, area: Rect, state: &CpuState) {
+        let sim = self.debugger.sim();
+        let running = match state.current.and_then(|pid| sim.task(pid)) {
+            Some(task) => format!(
+                "ejecuta {}, le quedan {} ms, vruntime {}",
+                task.pid, task.remaining, task.se.vruntime
+            ),
+            None => "ninguna tarea".to_string(),
+        };
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(format!(" CPU {}: {running} ", state.cpu));
+        let [reason, queue] =
+            Layout::vertical([Constraint::Length(2), Constraint::Min(0)]).areas(block.inner(area));
+        frame.render_widget(block, area);
+
+        let reason_text = match (&state.current, &state.reason) {
+            (Some(_), Some(reason)) => format!("Motivo: {reason}"),
+            (Some(_), None) => "Motivo: la política no lo indica".to_string(),
+            (None, _) => String::new(),
+        };
+        frame.render_widget(
+            Paragraph::new(reason_text)
+                .style(Style::default().fg(Color::Green))
+                .wrap(Wrap { trim: true }),
+            reason,
+        );
+
+        let header = ["PID", "Cola de ejecución"]
+            .into_iter()
+            .map(Cell::from)
+            .collect::<Row>()
+            .style(
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            );
+        let rows = state.runqueue.iter().map(|entry| {
+            let keys: Vec<String> = entry
+                .keys
+                .iter()
+                .map(|(name, value)| format!("{name} {value}"))
+                .collect();
+            let color = self.colors.get(&entry.pid).copied().unwrap_or_default();
+            Row::new([
+                Cell::from(entry.pid.to_string()).style(Style::default().fg(color)),
+                Cell::from(keys.join(", ")),
+            ])
+        });
+        frame.render_widget(
+            Table::new(rows, [Constraint::Length(6), Constraint::Min(10)]).header(header),
+            queue,
+        );
+    }
+}
+
+fn describe(sim: &Simulator, event: &Event) -> String {
+    let what = match &event.kind {
+        EventKind::Arrival => "llega".to_string(),
+        EventKind::Pick(Some(reason)) => format!("elegida: {reason}"),
+        EventKind::Pick(None) => "elegida".to_string(),
+        EventKind::Preempt => "expropiada".to_string(),
+        EventKind::Yield => "cede la CPU".to_string(),
+        EventKind::Block(until) => format!("se bloquea hasta t={until}"),
+        EventKind::Request { device, done } => {
+            let name = sim.devices().nth(*device).map_or("?", |d| d.name.as_str());
+            format!("pide E/S a {name}, lista en t={done}")
+        }
+        EventKind::Wakeup => "despierta".to_string(),
+        EventKind::Migrate { from } => format!("migra desde la CPU {from}"),
+        EventKind::Exit => "termina".to_string(),
+    };
+    format!(
+        " t={:<6} CPU {:<2} tarea {:<6} {what}",
+        event.time, event.cpu, event.pid
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use ets_sched::{Device, ServiceTime, Task};
+    use ratatui::{Terminal, backend::TestBackend};
+
+    use super::*;
+
+    fn screen(view: &DebuggerView) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(120, 30)).unwrap();
+        terminal
+            .draw(|frame| view.render(frame, frame.area()))
+            .unwrap();
+        terminal
+            .backend()
+            .buffer()
+            .content
+            .iter()
+            .map(|c| c.symbol())
+            .collect()
+    }
+
+    #[test]
+    fn steps_back_and_goes_to_a_time() {
+        let tasks = vec![
+            Task::builder().pid(1).burst(20).build(),
+            Task::builder().pid(2).arrival(3).bursts([2, 5, 2]).build(),
+        ];
+        let mut view = DebuggerView::new(tasks.into(), 1);
+        // Straight to the arrival of task 2.
+        view.step();
+        assert_eq!(view.debugger.now(), 3);
+        view.step_event();
+        view.step_back();
+        assert_eq!(view.debugger.now(), 3);
+        view.toggle_fixed_tick();
+        view.step();
+        assert_eq!(view.debugger.now(), 1);
+        for digit in "152".chars() {
+            view.type_digit(digit);
+        }
+        view.erase_digit();
+        view.go_to_target();
+        assert_eq!(view.debugger.now(), 15);
+        assert!(view.target.is_empty());
+
+        let screen = screen(&view);
+        assert!(screen.contains("t = 15 ms"));
+        assert!(screen.contains("Motivo: leftmost"));
+        assert!(screen.contains("se bloquea hasta"));
+    }
+
+    #[test]
+    fn shows_device_queues() {
+        // Each task runs a tick, then waits 10 on the disk, one after the other.
+        let on_disk = |pid| {
+            Task::builder()
+                .pid(pid)
+                .bursts([1, 1, 1])
+                .devices([Some(0)])
+                .build()
+        };
+        let workload = Workload {
+            tasks: vec![on_disk(1), on_disk(2)],
+            devices: vec![Device::new("disk", ServiceTime::Fixed(10))],
+        };
+        let mut view = DebuggerView::new(workload, 1);
+        view.debugger.seek(5);
+        let screen = screen(&view);
+        assert!(screen.contains("disk: sirve a 1, esperan 2"));
+        assert!(screen.contains("pide E/S a disk, lista en t=21"));
+    }
+}
@@ -0,0 +1,438 @@
+use std::{cmp::Reverse, collections::HashMap};
+
+use ets_sched::{
+    Cfs, Deadline, DlMode, Eevdf, Fcfs, Hrrn, Lottery, Mlfq, Pid, Priority, Rt, Scheduler,
+    Simulator, Sjf, Slice, Srtf, Stride, Task, Time, Workload,
+};
+use ratatui::{
+    Frame,
+    buffer::Buffer,
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph, Widget},
+};
+
+/// Builds one CPU's runqueue.
+pub type Build = fn() -> Box<dyn Scheduler>;
+
+/// Policies the views cycle through.
+pub const POLICIES: &[(&str, Build)] = &[
+    ("CFS", || Box::new(Cfs::new())),
+    ("EEVDF", || Box::new(Eevdf::new())),
+    ("RT + CFS", || Box::new(Rt::new(Box::new(Cfs::new())))),
+    ("DL + RT + CFS", || {
+        Box::new(Deadline::new(
+            DlMode::Cbs,
+            Box::new(Rt::new(Box::new(Cfs::new()))),
+        ))
+    }),
+    ("FCFS", || Box::new(Fcfs::new())),
+    ("SJF", || Box::new(Sjf::new())),
+    ("SRTF", || Box::new(Srtf::new())),
+    ("HRRN", || Box::new(Hrrn::new())),
+    ("Prioridad", || Box::new(Priority::new())),
+    ("MLFQ", || Box::new(Mlfq::default())),
+    ("Lotería", || Box::new(Lottery::new(1))),
+    ("Stride", || Box::new(Stride::new())),
+];
+
+const PALETTE: [Color; 12] = [
+    Color::Cyan,
+    Color::Green,
+    Color::Yellow,
+    Color::Magenta,
+    Color::Blue,
+    Color::Red,
+    Color::LightCyan,
+    Color::LightGreen,
+    Color::LightYellow,
+    Color::LightMagenta,
+    Color::LightBlue,
+    Color::LightRed,
+];
+
+/// Stops runs whose tasks never finish.
+const MAX_TICKS: Time = 1_000_000;
+
+pub const LABEL_WIDTH: u16 = 7;
+
+/// Stretch of time on screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Window {
+    /// First tick on screen.
+    pub offset: Time,
+    /// Ticks per column.
+    pub zoom: Time,
+}
+
+impl Default for Window {
+    fn default() -> Self {
+        Window { offset: 0, zoom: 1 }
+    }
+}
+
+impl Window {
+    /// Moves by `columns` columns of the current zoom, staying before `end`.
+    pub fn scroll(&mut self, columns: i64, end: Time) {
+        let ticks = columns * self.zoom as i64;
+        self.offset = (self.offset as i64 + ticks).clamp(0, end as i64) as Time;
+    }
+
+    pub fn zoom_in(&mut self) {
+        self.zoom = (self.zoom / 2).max(1);
+    }
+
+    pub fn zoom_out(&mut self) {
+        self.zoom = (self.zoom * 2).min(1 << 12);
+    }
+
+    /// Last tick shown by a chart `width` cells wide, borders included.
+    pub fn end(&self, width: u16) -> Time {
+        self.offset + width.saturating_sub(LABEL_WIDTH + 2) as Time * self.zoom
+    }
+}
+
+/// Gantt chart tab: simulates a set of tasks under one policy and shows
+/// which task ran on each CPU over time.
+pub struct GanttView {
+    workload: Workload,
+    ncpus: usize,
+    policy: usize,
+    sim: Simulator,
+    colors: HashMap<Pid, Color>,
+    window: Window,
+    first_cpu: usize,
+}
+
+impl GanttView {
+    pub fn new(workload: Workload, ncpus: usize) -> GanttView {
+        let ncpus = ncpus.clamp(1, 64);
+        let colors = colors(&workload.tasks);
+        GanttView {
+            sim: simulate(0, &workload, ncpus),
+            workload,
+            ncpus,
+            policy: 0,
+            colors,
+            window: Window::default(),
+            first_cpu: 0,
+        }
+    }
+
+    pub fn set_workload(&mut self, workload: Workload) {
+        self.colors = colors(&workload.tasks);
+        self.workload = workload;
+        self.rerun();
+    }
+
+    pub fn next_policy(&mut self) {
+        self.policy = (self.policy + 1) % POLICIES.len();
+        self.rerun();
+    }
+
+    fn rerun(&mut self) {
+        self.sim = simulate(self.policy, &self.workload, self.ncpus);
+        self.window.scroll(0, self.sim.now());
+    }
+
+    pub fn scroll(&mut self, columns: i64) {
+        self.window.scroll(columns, self.sim.now());
+    }
+
+    pub fn scroll_home(&mut self) {
+        self.window.offset = 0;
+    }
+
+    pub fn zoom_in(&mut self) {
+        self.window.zoom_in();
+    }
+
+    pub fn zoom_out(&mut self) {
+        self.window.zoom_out();
+    }
+
+    pub fn scroll_cpus(&mut self, rows: isize) {
+        self.first_cpu = self
+            .first_cpu
+            .saturating_add_signed(rows)
+            .min(self.ncpus - 1);
+    }
+
+    pub fn render(&self, frame: &mut Frame, area: Rect) {
+        let [info, chart, legend, summary] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(3),
+            Constraint::Length(1),
+            Constraint::Length(2),
+        ])
+        .areas(area);
+
+        let (name, _) = POLICIES[self.policy];
+        frame.render_widget(
+            Paragraph::new(format!(
+                " Política: {name} | CPUs: {} | tiempo {}..{} de {} ms | zoom {} ms/col | \
+                 ←/→ desplazar, +/- zoom, ↑/↓ CPUs, p política",
+                self.ncpus,
+                self.window.offset,
+                self.window.end(chart.width),
+                self.sim.now(),
+                self.window.zoom,
+            )),
+            info,
+        );
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(" Diagrama de Gantt ");
+        let inner = block.inner(chart);
+        frame.render_widget(block, chart);
+        frame.render_widget(
+            GanttChart {
+                trace: self.sim.trace(),
+                reference: None,
+                ncpus: self.ncpus,
+                first_cpu: self.first_cpu,
+                window: self.window,
+                colors: &self.colors,
+            },
+            inner,
+        );
+
+        frame.render_widget(
+            Paragraph::new(legend_line(&self.workload.tasks, &self.colors)),
+            legend,
+        );
+
+        let metrics = self.sim.metrics();
+        frame.render_widget(
+            Paragraph::new(vec![
+                Line::from(format!(
+                    " Retorno medio {:.1} ms, espera media {:.1} ms, respuesta media {:.1} ms",
+                    metrics.avg_turnaround(),
+                    metrics.avg_waiting(),
+                    metrics.avg_response(),
+                )),
+                Line::from(format!(
+                    " Uso de CPU {:.1}%, cambios de contexto {}, migraciones {}, equidad {:.3}",
+                    metrics.utilization() * 100.0,
+                    metrics.context_switches,
+                    metrics.migrations,
+                    metrics.fairness(),
+                )),
+            ]),
+            summary,
+        );
+    }
+}
+
+/// A simulation of `workload` under a policy, not yet started. `main` has
+/// checked the workload against the CPU count.
+pub fn simulator(policy: usize, workload: &Workload, ncpus: usize) -> Simulator {
+    let (_, build) = POLICIES[policy];
+    workload
+        .simulator((0..ncpus).map(|_| build()).collect())
+        .unwrap()
+}
+
+pub fn simulate(policy: usize, workload: &Workload, ncpus: usize) -> Simulator {
+    let mut sim = simulator(policy, workload, ncpus);
+    sim.run_until(MAX_TICKS);
+    sim
+}
+
+/// Colour and name of every task.
+pub fn legend_line<'a>(tasks: &'a [Task], colors: &HashMap<Pid, Color>) -> Line<'a> {
+    tasks
+        .iter()
+        .flat_map(|task| {
+            [
+                Span::styled("  ", Style::default().bg(colors[&task.pid])),
+                Span::raw(format!(" {} {}  ", task.pid, task.name)),
+            ]
+        })
+        .collect()
+}
+
+pub fn colors(tasks: &[Task]) -> HashMap<Pid, Color> {
+    tasks
+        .iter()
+        .zip(PALETTE.iter().cycle())
+        .map(|(task, &color)| (task.pid, color))
+        .collect()
+}
+
+/// One row per CPU, from `first_cpu` down, above a time axis. Columns that
+/// differ from `reference`, another run of the same workload, are hatched.
+pub struct GanttChart<'a> {
+    pub trace: &'a [Slice],
+    pub reference: Option<&'a [Slice]>,
+    pub ncpus: usize,
+    pub first_cpu: usize,
+    pub window: Window,
+    pub colors: &'a HashMap<Pid, Color>,
+}
+
+impl GanttChart<'_> {
+    /// What `cpu` did during most of each column, `None` when it idled.
+    fn columns(&self, trace: &[Slice], cpu: usize, width: u16) -> Vec<Option<Pid>> {
+        let slices: Vec<&Slice> = trace.iter().filter(|s| s.cpu == cpu).collect();
+        (0..width as Time)
+            .map(|col| {
+                let start = self.window.offset + col * self.window.zoom;
+                let end = start + self.window.zoom;
+                let first = slices.partition_point(|s| s.end <= start);
+                slices[first..]
+                    .iter()
+                    .take_while(|s| s.start < end)
+                    .min_by_key(|s| Reverse(s.end.min(end) - s.start.max(start)))
+                    .and_then(|s| s.pid)
+            })
+            .collect()
+    }
+}
+
+impl Widget for GanttChart<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.width <= LABEL_WIDTH || area.height < 2 {
+            return;
+        }
+        let width = area.width - LABEL_WIDTH;
+        let x0 = area.x + LABEL_WIDTH;
+        let rows = (area.height - 1) as usize;
+
+        for (row, cpu) in (self.first_cpu..self.ncpus).take(rows).enumerate() {
+            let y = area.y + row as u16;
+            buf.set_string(area.x, y, format!("CPU {cpu}"), Style::default());
+            let columns = self.columns(self.trace, cpu, width);
+            let reference = self.reference.map(|r| self.columns(r, cpu, width));
+            let cells: Vec<(Option<Pid>, bool)> = columns
+                .iter()
+                .enumerate()
+                .map(|(col, &pid)| (pid, reference.as_ref().is_some_and(|r| r[col] != pid)))
+                .collect();
+            let mut col = 0;
+            while col < cells.len() {
+                let (pid, differs) = cells[col];
+                let run = cells[col..]
+                    .iter()
+                    .take_while(|&&c| c == (pid, differs))
+                    .count();
+                let fill = if differs { "░" } else { " " }.repeat(run);
+                let x = x0 + col as u16;
+                match pid {
+                    Some(pid) => {
+                        let style = Style::default().bg(self.colors[&pid]).fg(Color::Black);
+                        buf.set_string(x, y, fill, style);
+                        let label = pid.to_string();
+                        if label.len() <= run {
+                            buf.set_string(x, y, label, style.add_modifier(Modifier::BOLD));
+                        }
+                    }
+                    None if differs => buf.set_string(x, y, fill, Style::default().fg(Color::Red)),
+                    None => {}
+                }
+                col += run;
+            }
+        }
+
+        // Time axis: a mark and the tick every ten columns.
+        let y = area.y + area.height - 1;
+        buf.set_string(area.x, y, "ms", Style::default().fg(Color::DarkGray));
+        for col in (0..width).step_by(10) {
+            let time = self.window.offset + col as Time * self.window.zoom;
+            let label = format!("|{time}");
+            let room = (width - col) as usize;
+            buf.set_stringn(
+                x0 + col,
+                y,
+                label,
+                room,
+                Style::default().fg(Color::DarkGray),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::{Terminal, backend::TestBackend};
+
+    use super::*;
+
+    fn slice(cpu: usize, pid: Option<Pid>, start: Time, end: Time) -> Slice {
+        Slice {
+            cpu,
+            pid,
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn columns_show_what_ran_most() {
+        let trace = [
+            slice(0, Some(1), 0, 3),
+            slice(1, Some(3), 0, 8),
+            slice(0, Some(2), 3, 4),
+            slice(0, None, 4, 8),
+        ];
+        let colors = HashMap::new();
+        let mut chart = GanttChart {
+            trace: &trace,
+            reference: None,
+            ncpus: 2,
+            first_cpu: 0,
+            window: Window::default(),
+            colors: &colors,
+        };
+        assert_eq!(
+            chart.columns(&trace, 0, 6),
+            [Some(1), Some(1), Some(1), Some(2), None, None]
+        );
+        chart.window.zoom = 4;
+        assert_eq!(chart.columns(&trace, 0, 3), [Some(1), None, None]);
+        assert_eq!(chart.columns(&trace, 1, 3), [Some(3), Some(3), None]);
+        chart.window = Window { offset: 2, zoom: 2 };
+        // Ties go to the earlier slice.
+        assert_eq!(chart.columns(&trace, 0, 2), [Some(1), None]);
+    }
+
+    #[test]
+    fn view_scrolls_within_the_run() {
+        let tasks: Vec<Task> = (1..=3)
+            .map(|pid| Task::builder().pid(pid).burst(20).build())
+            .collect();
+        let mut view = GanttView::new(tasks.into(), 2);
+        assert!(view.sim.is_done());
+        view.scroll(-5);
+        assert_eq!(view.window.offset, 0);
+        view.zoom_out();
+        view.scroll(4);
+        assert_eq!(view.window.offset, 8);
+        view.scroll(1000);
+        assert_eq!(view.window.offset, view.sim.now());
+        view.scroll_cpus(5);
+        assert_eq!(view.first_cpu, 1);
+        for _ in 0..POLICIES.len() {
+            view.next_policy();
+            assert!(view.sim.is_done(), "{}", POLICIES[view.policy].0);
+        }
+
+        let mut terminal = Terminal::new(TestBackend::new(100, 12)).unwrap();
+        view.scroll_home();
+        view.scroll_cpus(-5);
+        terminal
+            .draw(|frame| view.render(frame, frame.area()))
+            .unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content
+            .iter()
+            .map(|c| c.symbol())
+            .collect();
+        assert!(screen.contains("CPU 0"));
+        assert!(screen.contains("|0"));
+    }
+}
@@ -1,38 +1,80 @@
+mod compare;
+mod debugger;
+mod gantt;
+
 use std::{
-    io,
+    env, io, process,
     time::{Duration, Instant},
 };
 
-use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ets_sched::{Task, Workload};
 use ratatui::{
     DefaultTerminal, Frame,
     layout::{Constraint, Layout, Rect},
     style::{Color, Modifier, Style},
-    widgets::{Block, Borders, Cell, Row, Table, TableState},
+    widgets::{Block, Borders, Cell, Row, Table, TableState, Tabs},
 };
 
+use compare::ComparisonView;
+use debugger::DebuggerView;
+use gantt::GanttView;
 use sys_probe::{Process, SysProbe};
 
+/// Live processes simulated when no workload file is given.
+const LIVE_TASKS: usize = 8;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Tab {
+    Processes,
+    Gantt,
+    Compare,
+    Debug,
+}
+
 pub struct App {
     pub sys: SysProbe,
     pub items: Vec<Process>,
     pub filtered: Vec<Process>,
     pub filter: String,
     pub table_state: TableState,
+    pub tab: Tab,
+    pub gantt: GanttView,
+    pub compare: ComparisonView,
+    pub debugger: DebuggerView,
+    /// Workload file to simulate; otherwise the simulation tabs use the
+    /// first processes of the table.
+    pub workload: Option<Workload>,
     pub exit: bool,
 }
 
+impl Default for App {
+    fn default() -> Self {
+        App::new(None)
+    }
+}
+
 impl App {
-    pub fn new() -> App {
+    pub fn new(workload: Option<Workload>) -> App {
+        let mut sys = SysProbe::new();
+        sys.init();
+        let simulated = workload.clone().unwrap_or_default();
+        let gantt = GanttView::new(simulated.clone(), sys.cpus);
+        let compare = ComparisonView::new(simulated.clone(), sys.cpus);
+        let debugger = DebuggerView::new(simulated, sys.cpus);
         let mut app = App {
-            sys: SysProbe::new(),
+            sys,
             items: Vec::new(),
             filtered: Vec::new(),
             filter: String::new(),
             table_state: TableState::default(),
+            tab: Tab::Processes,
+            gantt,
+            compare,
+            debugger,
+            workload,
             exit: false,
         };
-        app.sys.init();
         app.update_processes();
         app
     }
@@ -55,9 +97,33 @@ impl App {
         self.filtered = self
             .items
             .iter()
-            .cloned()
             .filter(|p| p.name.to_lowercase().contains(&text) || p.pid.to_string().contains(&text))
+            .cloned()
+            .collect();
+    }
+
+    fn switch_tab(&mut self) {
+        self.tab = match self.tab {
+            Tab::Processes => Tab::Gantt,
+            Tab::Gantt => Tab::Compare,
+            Tab::Compare => Tab::Debug,
+            Tab::Debug => Tab::Processes,
+        };
+        if self.workload.is_some() {
+            return;
+        }
+        let tasks: Vec<Task> = self
+            .filtered
+            .iter()
+            .take(LIVE_TASKS)
+            .map(Task::from)
             .collect();
+        match self.tab {
+            Tab::Gantt => self.gantt.set_workload(tasks.into()),
+            Tab::Compare => self.compare.set_workload(tasks.into()),
+            Tab::Debug => self.debugger.set_workload(tasks.into()),
+            Tab::Processes => {}
+        }
     }
 
     pub fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
@@ -71,25 +137,11 @@ impl App {
                 .checked_sub(last_tick.elapsed())
                 .unwrap_or_else(|| Duration::from_secs(0));
 
-            if crossterm::event::poll(timeout)? {
-                if let Event::Key(key) = event::read()? {
-                    if key.kind == KeyEventKind::Press {
-                        match key.code {
-                            KeyCode::Char('q') => self.exit = true,
-                            KeyCode::Down => self.next_row(),
-                            KeyCode::Up => self.previous_row(),
-                            KeyCode::Char(c) => {
-                                self.filter.push(c);
-                                self.apply_filter();
-                            }
-                            KeyCode::Backspace => {
-                                self.filter.pop();
-                                self.apply_filter();
-                            }
-                            _ => {}
-                        }
-                    }
-                }
+            if crossterm::event::poll(timeout)?
+                && let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                self.handle_key(key);
             }
 
             if last_tick.elapsed() >= tick_rate {
@@ -100,12 +152,87 @@ impl App {
         Ok(())
     }
 
+    fn handle_key(&mut self, key: KeyEvent) {
+        match (self.tab, key.code) {
+            (_, KeyCode::Char('q')) => self.exit = true,
+            (_, KeyCode::Tab) => self.switch_tab(),
+            (Tab::Processes, KeyCode::Down) => self.next_row(),
+            (Tab::Processes, KeyCode::Up) => self.previous_row(),
+            (Tab::Processes, KeyCode::Char(c)) => {
+                self.filter.push(c);
+                self.apply_filter();
+            }
+            (Tab::Processes, KeyCode::Backspace) => {
+                self.filter.pop();
+                self.apply_filter();
+            }
+            (Tab::Gantt, KeyCode::Left) => self.gantt.scroll(-10),
+            (Tab::Gantt, KeyCode::Right) => self.gantt.scroll(10),
+            (Tab::Gantt, KeyCode::Home) => self.gantt.scroll_home(),
+            (Tab::Gantt, KeyCode::Up) => self.gantt.scroll_cpus(-1),
+            (Tab::Gantt, KeyCode::Down) => self.gantt.scroll_cpus(1),
+            (Tab::Gantt, KeyCode::Char('+')) => self.gantt.zoom_in(),
+            (Tab::Gantt, KeyCode::Char('-')) => self.gantt.zoom_out(),
+            (Tab::Gantt, KeyCode::Char('p')) => self.gantt.next_policy(),
+            (Tab::Compare, KeyCode::Left) => self.compare.scroll(-10),
+            (Tab::Compare, KeyCode::Right) => self.compare.scroll(10),
+            (Tab::Compare, KeyCode::Home) => self.compare.scroll_home(),
+            (Tab::Compare, KeyCode::Up) => self.compare.select(-1),
+            (Tab::Compare, KeyCode::Down) => self.compare.select(1),
+            (Tab::Compare, KeyCode::PageUp) => self.compare.scroll_cpus(-1),
+            (Tab::Compare, KeyCode::PageDown) => self.compare.scroll_cpus(1),
+            (Tab::Compare, KeyCode::Char('+')) => self.compare.zoom_in(),
+            (Tab::Compare, KeyCode::Char('-')) => self.compare.zoom_out(),
+            (Tab::Compare, KeyCode::Char('p')) => self.compare.next_policy(),
+            (Tab::Compare, KeyCode::Char('a')) => self.compare.add_policy(),
+            (Tab::Compare, KeyCode::Char('x')) => self.compare.remove_policy(),
+            (Tab::Debug, KeyCode::Right) => self.debugger.step(),
+            (Tab::Debug, KeyCode::Left) => self.debugger.step_back(),
+            (Tab::Debug, KeyCode::Char('e')) => self.debugger.step_event(),
+            (Tab::Debug, KeyCode::Char(c)) if c.is_ascii_digit() => self.debugger.type_digit(c),
+            (Tab::Debug, KeyCode::Backspace) => self.debugger.erase_digit(),
+            (Tab::Debug, KeyCode::Enter) => self.debugger.go_to_target(),
+            (Tab::Debug, KeyCode::Home) => self.debugger.restart(),
+            (Tab::Debug, KeyCode::Up) => self.debugger.scroll_cpus(-1),
+            (Tab::Debug, KeyCode::Down) => self.debugger.scroll_cpus(1),
+            (Tab::Debug, KeyCode::Char('p')) => self.debugger.next_policy(),
+            (Tab::Debug, KeyCode::Char('t')) => self.debugger.toggle_fixed_tick(),
+            _ => {}
+        }
+    }
+
     fn draw(&mut self, frame: &mut Frame) {
-        let rects = Layout::default()
-            .constraints([Constraint::Percentage(100)])
-            .split(frame.area());
+        let [tabs, body] =
+            Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(frame.area());
+
+        let selected = match self.tab {
+            Tab::Processes => 0,
+            Tab::Gantt => 1,
+            Tab::Compare => 2,
+            Tab::Debug => 3,
+        };
+        frame.render_widget(
+            Tabs::new([
+                " Procesos ",
+                " Diagrama de Gantt ",
+                " Comparar políticas ",
+                " Depurador ",
+            ])
+            .select(selected)
+            .highlight_style(
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            tabs,
+        );
 
-        self.render_table(frame, rects[0]);
+        match self.tab {
+            Tab::Processes => self.render_table(frame, body),
+            Tab::Gantt => self.gantt.render(frame, body),
+            Tab::Compare => self.compare.render(frame, body),
+            Tab::Debug => self.debugger.render(frame, body),
+        }
     }
 
     fn render_table(&mut self, frame: &mut Frame, area: Rect) {
@@ -195,8 +322,22 @@ impl App {
 }
 
 fn main() -> io::Result<()> {
+    // An optional workload file to simulate instead of live processes.
+    let workload = env::args().nth(1).map(|path| {
+        let mut probe = SysProbe::new();
+        probe.set_cpus();
+        let checked = Workload::load(&path)
+            .and_then(|workload| workload.check_cpus(probe.cpus).map(|()| workload));
+        match checked {
+            Ok(workload) => workload,
+            Err(err) => {
+                eprintln!("{path}: {err}");
+                process::exit(1);
+            }
+        }
+    });
     let mut terminal = ratatui::init();
-    let app_result = App::new().run(&mut terminal);
+    let app_result = App::new(workload).run(&mut terminal);
     ratatui::restore();
     app_result
 }
@@ -0,0 +1,160 @@
+//! Schedulability tests for periodic task sets on one CPU.
+
+use std::fmt;
+
+use crate::task::{DlParams, Time};
+
+/// Fraction of the CPU the set asks for.
+pub fn utilization(set: &[DlParams]) -> f64 {
+    set.iter().map(DlParams::utilization).sum()
+}
+
+/// Like [`utilization`] but dividing by the deadline when it is shorter than
+/// the period.
+pub fn density(set: &[DlParams]) -> f64 {
+    set.iter()
+        .map(|dl| dl.runtime as f64 / dl.deadline.min(dl.period) as f64)
+        .sum()
+}
+
+/// Liu & Layland: `n` tasks with implicit deadlines are schedulable under
+/// rate monotonic if their utilization is at most `n(2^(1/n) - 1)`.
+pub fn liu_layland_bound(n: usize) -> f64 {
+    if n == 0 {
+        return 1.0;
+    }
+    let n = n as f64;
+    n * (2f64.powf(1.0 / n) - 1.0)
+}
+
+/// Worst-case response time of every task under rate monotonic priorities,
+/// in input order, or `None` where it exceeds the deadline.
+pub fn response_times(set: &[DlParams]) -> Vec<Option<Time>> {
+    set.iter()
+        .enumerate()
+        .map(|(i, task)| {
+            // Shorter period first; ties go to the earlier task.
+            let higher: Vec<&DlParams> = set
+                .iter()
+                .enumerate()
+                .filter(|&(j, other)| (other.period, j) < (task.period, i))
+                .map(|(_, other)| other)
+                .collect();
+            let mut response = task.runtime;
+            loop {
+                let next = task.runtime
+                    + higher
+                        .iter()
+                        .map(|hp| response.div_ceil(hp.period) * hp.runtime)
+                        .sum::<Time>();
+                if next > task.deadline {
+                    return None;
+                }
+                if next == response {
+                    return Some(response);
+                }
+                response = next;
+            }
+        })
+        .collect()
+}
+
+/// Results of every test for one task set.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schedulability {
+    pub utilization: f64,
+    pub density: f64,
+    pub liu_layland_bound: f64,
+    /// Whether every deadline equals its period, which the bound needs.
+    pub implicit_deadlines: bool,
+    pub response_times: Vec<Option<Time>>,
+}
+
+impl Schedulability {
+    pub fn analyze(set: &[DlParams]) -> Schedulability {
+        Schedulability {
+            utilization: utilization(set),
+            density: density(set),
+            liu_layland_bound: liu_layland_bound(set.len()),
+            implicit_deadlines: set.iter().all(|dl| dl.deadline == dl.period),
+            response_times: response_times(set),
+        }
+    }
+
+    /// Sufficient test for rate monotonic, or `None` when some deadline is
+    /// shorter than its period and the bound does not apply.
+    pub fn rm_by_bound(&self) -> Option<bool> {
+        self.implicit_deadlines
+            .then_some(self.utilization <= self.liu_layland_bound)
+    }
+
+    /// Exact test for rate monotonic.
+    pub fn rm_by_rta(&self) -> bool {
+        self.response_times.iter().all(Option::is_some)
+    }
+
+    /// Exact for implicit deadlines, sufficient otherwise.
+    pub fn edf(&self) -> bool {
+        self.density <= 1.0
+    }
+}
+
+impl fmt::Display for Schedulability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verdict = |ok: bool| if ok { "schedulable" } else { "not schedulable" };
+        writeln!(
+            f,
+            "U = {:.3}, Liu & Layland bound = {:.3}",
+            self.utilization, self.liu_layland_bound
+        )?;
+        match self.rm_by_bound() {
+            Some(ok) => writeln!(f, "RM (bound): {}", verdict(ok))?,
+            None => writeln!(f, "RM (bound): not applicable, deadlines before periods")?,
+        }
+        writeln!(f, "RM (RTA):   {}", verdict(self.rm_by_rta()))?;
+        for (i, response) in self.response_times.iter().enumerate() {
+            match response {
+                Some(r) => writeln!(f, "  task {i}: R = {r}")?,
+                None => writeln!(f, "  task {i}: misses its deadline")?,
+            }
+        }
+        writeln!(f, "EDF:        {}", verdict(self.edf()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rta_beats_the_bound() {
+        let set = [
+            DlParams::implicit(1, 4),
+            DlParams::implicit(2, 6),
+            DlParams::implicit(3, 13),
+        ];
+        let report = Schedulability::analyze(&set);
+        assert!((report.liu_layland_bound - 0.7798).abs() < 1e-4);
+        assert_eq!(report.rm_by_bound(), Some(false));
+        assert_eq!(report.response_times, [Some(1), Some(3), Some(10)]);
+        assert!(report.rm_by_rta() && report.edf());
+
+        let set = [DlParams::implicit(2, 5), DlParams::implicit(4, 7)];
+        let report = Schedulability::analyze(&set);
+        assert_eq!(report.response_times, [Some(2), None]);
+        assert!(report.edf());
+        assert!(report.to_string().contains("task 1: misses its deadline"));
+    }
+
+    #[test]
+    fn bound_needs_implicit_deadlines() {
+        // Well under the bound, yet the second task cannot meet a deadline
+        // of 2 behind the first one.
+        let set = [DlParams::implicit(1, 10), DlParams::new(2, 2, 20)];
+        let report = Schedulability::analyze(&set);
+        assert!(report.utilization <= report.liu_layland_bound);
+        assert_eq!(report.rm_by_bound(), None);
+        assert!(!report.rm_by_rta());
+        assert!(report.to_string().contains("RM (bound): not applicable"));
+    }
+}
@@ -0,0 +1,270 @@
+//! Stepping through a simulation, forwards and backwards.
+
+use crate::{
+    sched::RqEntry,
+    sim::{Event, EventKind, Simulator},
+    task::{Pid, Time},
+};
+
+/// Ticks between snapshots by default.
+const SNAPSHOT_INTERVAL: Time = 64;
+
+/// What one CPU is doing, for inspection between steps.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CpuState {
+    pub cpu: usize,
+    pub current: Option<Pid>,
+    /// Reason the policy gave for picking `current`.
+    pub reason: Option<String>,
+    pub runqueue: Vec<RqEntry>,
+}
+
+/// The simulation at some point, without its trace and event log; restoring
+/// it cuts the current ones back to these lengths.
+struct Snapshot {
+    sim: Simulator,
+    trace_len: usize,
+    events_len: usize,
+}
+
+impl Snapshot {
+    fn take(sim: &mut Simulator) -> Snapshot {
+        Snapshot {
+            trace_len: sim.trace().len(),
+            events_len: sim.events().len(),
+            sim: sim.without_history(),
+        }
+    }
+
+    fn restore(&self, sim: &mut Simulator) {
+        sim.rewind(&self.sim, self.trace_len, self.events_len);
+    }
+}
+
+/// Drives a [`Simulator`] one step at a time, from event to event or tick
+/// to tick in fixed-tick mode. Going back restores the latest snapshot
+/// before the target time and replays from there, which gives the same run
+/// since the simulation is deterministic.
+pub struct Debugger {
+    sim: Simulator,
+    /// Copies of the simulation at least `snapshot_interval` ticks apart,
+    /// oldest first.
+    snapshots: Vec<Snapshot>,
+    snapshot_interval: Time,
+    /// Time at which each step so far started.
+    starts: Vec<Time>,
+}
+
+impl Debugger {
+    pub fn new(sim: Simulator) -> Debugger {
+        Debugger::with_interval(sim, SNAPSHOT_INTERVAL)
+    }
+
+    /// Snapshots every `interval` ticks: fewer snapshots cost less memory
+    /// but longer replays.
+    pub fn with_interval(mut sim: Simulator, interval: Time) -> Debugger {
+        assert!(interval > 0, "snapshot interval must be at least one tick");
+        sim.log_events = true;
+        Debugger {
+            snapshots: vec![Snapshot::take(&mut sim)],
+            sim,
+            snapshot_interval: interval,
+            starts: Vec::new(),
+        }
+    }
+
+    pub fn sim(&self) -> &Simulator {
+        &self.sim
+    }
+
+    pub fn now(&self) -> Time {
+        self.sim.now()
+    }
+
+    pub fn cpu_state(&self, cpu: usize) -> CpuState {
+        let current = self.sim.current_on(cpu);
+        let reason = self
+            .sim
+            .events()
+            .iter()
+            .rev()
+            .find(|e| e.cpu == cpu && matches!(e.kind, EventKind::Pick(_)))
+            .filter(|e| Some(e.pid) == current)
+            .and_then(|e| match &e.kind {
+                EventKind::Pick(reason) => reason.clone(),
+                _ => None,
+            });
+        CpuState {
+            cpu,
+            current,
+            reason,
+            runqueue: self.sim.scheduler_on(cpu).runqueue(self.sim.now()),
+        }
+    }
+
+    /// Advances one step. Returns `false` once every task has finished.
+    pub fn step(&mut self) -> bool {
+        self.step_until(Time::MAX)
+    }
+
+    fn step_until(&mut self, limit: Time) -> bool {
+        if self.sim.is_done() {
+            return false;
+        }
+        self.starts.push(self.sim.now());
+        self.sim.step_until(limit);
+        let last = self.snapshots.last().map_or(0, |s| s.sim.now());
+        if self.sim.now() >= last + self.snapshot_interval {
+            self.snapshots.push(Snapshot::take(&mut self.sim));
+        }
+        true
+    }
+
+    /// Advances until something happens and returns what did.
+    pub fn step_event(&mut self) -> &[Event] {
+        let seen = self.sim.events().len();
+        while self.sim.events().len() == seen && self.step() {}
+        &self.sim.events()[seen..]
+    }
+
+    /// Advances to `time`, or until every task has finished.
+    pub fn run_until(&mut self, time: Time) {
+        while self.sim.now() < time && self.step_until(time) {}
+    }
+
+    /// Goes back to where the last step started. Returns `false` when already
+    /// at the start.
+    pub fn step_back(&mut self) -> bool {
+        let Some(&start) = self.starts.last() else {
+            return false;
+        };
+        self.seek(start);
+        true
+    }
+
+    /// Goes to `time`, backwards or forwards.
+    pub fn seek(&mut self, time: Time) {
+        if time < self.sim.now() {
+            let snapshot = self
+                .snapshots
+                .iter()
+                .rposition(|s| s.sim.now() <= time)
+                .unwrap_or(0);
+            self.snapshots[snapshot].restore(&mut self.sim);
+            let now = self.sim.now();
+            self.starts.retain(|&start| start < now);
+        }
+        self.run_until(time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        policy::{Cfs, Mlfq, MlfqConfig},
+        sched::Scheduler,
+        task::Task,
+    };
+
+    fn tasks() -> Vec<Task> {
+        vec![
+            Task::builder().pid(1).burst(30).build(),
+            Task::builder().pid(2).arrival(2).burst(10).build(),
+            Task::builder().pid(3).arrival(5).bursts([3, 4, 3]).build(),
+        ]
+    }
+
+    fn debugger(scheduler: Box<dyn Scheduler>) -> Debugger {
+        Debugger::with_interval(Simulator::new(scheduler, tasks()), 8)
+    }
+
+    #[test]
+    fn shows_runqueue_and_pick_reason() {
+        let mut dbg = debugger(Box::new(Cfs::new()));
+        dbg.run_until(3);
+        // Task 1 used up its slice; both wait for the next pick.
+        let state = dbg.cpu_state(0);
+        assert_eq!(state.current, None);
+        let queued: Vec<_> = state.runqueue.iter().map(|e| e.pid).collect();
+        assert_eq!(queued, [1, 2]);
+        for entry in &state.runqueue {
+            let task = dbg.sim().task(entry.pid).unwrap();
+            assert_eq!(entry.key("vruntime"), Some(task.se.vruntime as i64));
+        }
+
+        let events = dbg.step_event().to_vec();
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].time, events[0].pid), (3, 1));
+        let state = dbg.cpu_state(0);
+        assert_eq!(state.current, Some(1));
+        assert!(state.reason.unwrap().contains("leftmost"));
+    }
+
+    #[test]
+    fn shows_queue_levels() {
+        let config = MlfqConfig {
+            quanta: vec![2, 4],
+            boost_interval: None,
+        };
+        let mut dbg = debugger(Box::new(Mlfq::new(config).unwrap()));
+        dbg.run_until(3);
+        let levels: Vec<_> = dbg
+            .cpu_state(0)
+            .runqueue
+            .iter()
+            .map(|e| (e.pid, e.key("level")))
+            .collect();
+        // Task 1 sank to level 1 and the newcomer preempted it.
+        assert_eq!(levels, [(1, Some(1))]);
+        assert_eq!(dbg.cpu_state(0).current, Some(2));
+    }
+
+    #[test]
+    fn stepping_back_replays_the_same_run() {
+        let mut reference = Simulator::new(Box::new(Cfs::new()), tasks());
+        reference.log_events = true;
+        reference.run_until(20);
+
+        let mut dbg = debugger(Box::new(Cfs::new()));
+        dbg.run_until(20);
+        let mut times = vec![dbg.now()];
+        for _ in 0..5 {
+            dbg.step();
+            times.push(dbg.now());
+        }
+        for &time in times.iter().rev().skip(1) {
+            assert!(dbg.step_back());
+            assert_eq!(dbg.now(), time);
+        }
+        assert_eq!(dbg.now(), 20);
+        assert_eq!(dbg.sim().trace(), reference.trace());
+        assert_eq!(dbg.sim().events(), reference.events());
+
+        dbg.seek(0);
+        assert!(!dbg.step_back());
+        dbg.seek(1000);
+        assert!(dbg.sim().is_done());
+        assert!(!dbg.step());
+    }
+
+    #[test]
+    fn snapshots_leave_the_history_out() {
+        let tasks = || (1..=8).map(|pid| Task::builder().pid(pid).burst(100_000).build());
+        let mut dbg = Debugger::new(Simulator::new(Box::new(Cfs::new()), tasks().collect()));
+        dbg.seek(20_000);
+        assert!(dbg.snapshots.len() > 300);
+        // Copying the trace and events into each one made memory grow with
+        // the square of the run length.
+        for snapshot in &dbg.snapshots {
+            assert!(snapshot.sim.trace().is_empty() && snapshot.sim.events().is_empty());
+        }
+
+        let mut reference = Simulator::new(Box::new(Cfs::new()), tasks().collect());
+        reference.log_events = true;
+        reference.run_until(12_345);
+        dbg.seek(12_345);
+        assert_eq!(dbg.sim().trace(), reference.trace());
+        assert_eq!(dbg.sim().events(), reference.events());
+    }
+}
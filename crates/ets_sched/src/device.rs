@@ -0,0 +1,126 @@
+//! Devices that tasks block on between CPU bursts. Each one serves a
+//! request at a time, in arrival order, so tasks that use the same device
+//! wait for each other.
+
+use serde::Deserialize;
+
+use crate::{rng::Rng, task::Time};
+
+/// Index of a device in the simulation, in the order they were added.
+pub type DeviceId = usize;
+
+/// How long a device takes to serve one request, in ticks.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServiceTime {
+    Fixed(Time),
+    /// Uniform within `min..=max`.
+    Uniform(Time, Time),
+    /// Exponential with the given mean, memoryless like requests that
+    /// depend on a user or a remote host.
+    Exponential(Time),
+}
+
+impl ServiceTime {
+    /// At least one tick.
+    pub fn sample(&self, rng: &mut Rng) -> Time {
+        let ticks = match *self {
+            ServiceTime::Fixed(ticks) => ticks,
+            ServiceTime::Uniform(min, max) => min + rng.below(max - min + 1),
+            ServiceTime::Exponential(mean) => {
+                // 1 - u is in (0, 1], so the logarithm is finite.
+                let u = 1.0 - rng.next_f64();
+                (-(mean as f64) * u.ln()).round() as Time
+            }
+        };
+        ticks.max(1)
+    }
+
+    pub fn mean(&self) -> f64 {
+        match *self {
+            ServiceTime::Fixed(ticks) | ServiceTime::Exponential(ticks) => ticks as f64,
+            ServiceTime::Uniform(min, max) => (min + max) as f64 / 2.0,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            ServiceTime::Uniform(min, max) if min > max => {
+                Err(format!("uniform service time {min}..={max} is empty"))
+            }
+            ServiceTime::Fixed(0) | ServiceTime::Exponential(0) => {
+                Err("service time must be at least one tick".into())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Device {
+    pub name: String,
+    pub service: ServiceTime,
+}
+
+impl Device {
+    pub fn new(name: impl Into<String>, service: ServiceTime) -> Device {
+        service.validate().unwrap_or_else(|err| panic!("{err}"));
+        Device {
+            name: name.into(),
+            service,
+        }
+    }
+
+    /// A disk: a few milliseconds per request, with a long tail.
+    pub fn disk() -> Device {
+        Device::new("disk", ServiceTime::Exponential(8))
+    }
+
+    /// Round trips to a remote host.
+    pub fn network() -> Device {
+        Device::new("network", ServiceTime::Uniform(20, 80))
+    }
+
+    /// Someone typing: a key every fifth of a second on average.
+    pub fn keyboard() -> Device {
+        Device::new("keyboard", ServiceTime::Exponential(200))
+    }
+
+    /// The preset with this name, if there is one.
+    pub fn preset(name: &str) -> Option<Device> {
+        match name {
+            "disk" => Some(Device::disk()),
+            "network" => Some(Device::network()),
+            "keyboard" => Some(Device::keyboard()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(service: ServiceTime) -> Vec<Time> {
+        let mut rng = Rng::new(3);
+        (0..10_000).map(|_| service.sample(&mut rng)).collect()
+    }
+
+    #[test]
+    fn samples_follow_the_distribution() {
+        assert!(samples(ServiceTime::Fixed(5)).iter().all(|&t| t == 5));
+
+        let uniform = samples(ServiceTime::Uniform(2, 6));
+        assert!(uniform.iter().all(|t| (2..=6).contains(t)));
+        assert!(uniform.contains(&2) && uniform.contains(&6));
+
+        for service in [ServiceTime::Uniform(20, 80), ServiceTime::Exponential(200)] {
+            let values = samples(service);
+            let mean = values.iter().sum::<Time>() as f64 / values.len() as f64;
+            let error = (mean - service.mean()).abs() / service.mean();
+            assert!(error < 0.05, "{service:?}: mean {mean}");
+        }
+        assert!(ServiceTime::Uniform(3, 2).validate().is_err());
+        assert!(ServiceTime::Exponential(0).validate().is_err());
+    }
+}
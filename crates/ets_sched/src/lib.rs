@@ -1,14 +1,28 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+pub mod analysis;
+mod debug;
+mod device;
+mod metrics;
+mod policy;
+mod rng;
+mod sched;
+mod share;
+mod sim;
+mod task;
+mod workload;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+pub use debug::{CpuState, Debugger};
+pub use device::{Device, DeviceId, ServiceTime};
+pub use metrics::{Metrics, TaskMetrics, jain_index};
+pub use policy::{
+    AdmissionError, Cfs, Deadline, DlMode, Eevdf, Fcfs, Hrrn, Lottery, Mlfq, MlfqConfig,
+    MlfqConfigError, Priority, Rt, STRIDE1, Sjf, Srtf, Stride,
+};
+pub use rng::Rng;
+pub use sched::{Enqueue, RqEntry, Scheduler, SchedulerClone};
+pub use share::{fair_share, measured_share};
+pub use sim::{DeadlineMiss, Event, EventKind, Simulator, Slice};
+pub use task::{
+    DEFAULT_BURST, DlParams, Io, Job, MlfqLevel, NICE_0_LOAD, Pid, Policy, SCHED_PRIO_TO_WEIGHT,
+    SchedEntity, TICK_NS, Task, TaskBuilder, TaskState, Time, nice_to_weight,
+};
+pub use workload::{Workload, WorkloadError};
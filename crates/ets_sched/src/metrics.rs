@@ -0,0 +1,252 @@
+//! Figures of merit of a simulated run, to compare policies on the same
+//! workload.
+
+use std::fmt;
+
+use crate::{
+    sim::Simulator,
+    task::{NICE_0_LOAD, Pid, TaskState, Time},
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TaskMetrics {
+    pub pid: Pid,
+    pub arrival: Time,
+    pub completion: Option<Time>,
+    /// Completion minus arrival.
+    pub turnaround: Option<Time>,
+    /// Time spent runnable but not running.
+    pub waiting: Time,
+    /// First run minus arrival.
+    pub response: Option<Time>,
+    /// CPU time received.
+    pub cpu: Time,
+    pub weight: u64,
+}
+
+impl TaskMetrics {
+    /// CPU received per tick spent in the system up to `now`, scaled to a
+    /// nice-0 weight.
+    fn service_rate(&self, now: Time) -> f64 {
+        let end = self.completion.unwrap_or(now);
+        let present = end.saturating_sub(self.arrival).max(1);
+        self.cpu as f64 * NICE_0_LOAD as f64 / (self.weight as f64 * present as f64)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Metrics {
+    pub tasks: Vec<TaskMetrics>,
+    pub elapsed: Time,
+    pub ncpus: usize,
+    /// Ticks the CPUs spent running tasks, added over all CPUs.
+    pub busy: Time,
+    pub context_switches: usize,
+    pub migrations: usize,
+}
+
+impl Metrics {
+    pub fn new(sim: &Simulator) -> Metrics {
+        let tasks = sim
+            .tasks()
+            .iter()
+            .filter(|task| task.state != TaskState::New)
+            .map(|task| TaskMetrics {
+                pid: task.pid,
+                arrival: task.arrival,
+                completion: task.completion,
+                turnaround: task.completion.map(|end| end - task.arrival),
+                waiting: task.se.wait_sum,
+                response: task.first_run.map(|start| start - task.arrival),
+                cpu: task.se.sum_exec_runtime,
+                weight: task.se.weight,
+            })
+            .collect();
+        let busy = sim
+            .trace()
+            .iter()
+            .filter(|slice| slice.pid.is_some())
+            .map(|slice| slice.end - slice.start)
+            .sum();
+        Metrics {
+            tasks,
+            elapsed: sim.now(),
+            ncpus: sim.ncpus(),
+            busy,
+            context_switches: sim.context_switches(),
+            migrations: sim.migrations(),
+        }
+    }
+
+    pub fn finished(&self) -> usize {
+        self.tasks.iter().filter(|t| t.completion.is_some()).count()
+    }
+
+    /// Average over finished tasks.
+    pub fn avg_turnaround(&self) -> f64 {
+        mean(self.tasks.iter().filter_map(|t| t.turnaround))
+    }
+
+    pub fn avg_waiting(&self) -> f64 {
+        mean(self.tasks.iter().map(|t| t.waiting))
+    }
+
+    /// Average over tasks that have run.
+    pub fn avg_response(&self) -> f64 {
+        mean(self.tasks.iter().filter_map(|t| t.response))
+    }
+
+    /// Finished tasks per second.
+    pub fn throughput(&self) -> f64 {
+        self.finished() as f64 * 1000.0 / self.elapsed.max(1) as f64
+    }
+
+    /// Fraction of the available CPU time spent running tasks.
+    pub fn utilization(&self) -> f64 {
+        self.busy as f64 / (self.elapsed * self.ncpus as Time).max(1) as f64
+    }
+
+    /// Jain's index of the CPU each task received per tick in the system,
+    /// relative to its weight: 1 when every task was served at the same
+    /// weighted rate, down to 1/n when one task got everything.
+    pub fn fairness(&self) -> f64 {
+        let rates: Vec<f64> = self
+            .tasks
+            .iter()
+            .map(|t| t.service_rate(self.elapsed))
+            .collect();
+        jain_index(&rates)
+    }
+}
+
+/// `(Σx)² / (n·Σx²)`, 1 for an empty or all-zero set.
+pub fn jain_index(values: &[f64]) -> f64 {
+    let sum: f64 = values.iter().sum();
+    let squares: f64 = values.iter().map(|x| x * x).sum();
+    if squares == 0.0 {
+        return 1.0;
+    }
+    sum * sum / (values.len() as f64 * squares)
+}
+
+fn mean(values: impl Iterator<Item = Time>) -> f64 {
+    let (sum, n) = values.fold((0, 0), |(sum, n), v| (sum + v, n + 1));
+    if n == 0 { 0.0 } else { sum as f64 / n as f64 }
+}
+
+impl fmt::Display for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let or_dash = |time: Option<Time>| time.map_or("-".to_string(), |t| t.to_string());
+        writeln!(
+            f,
+            "{:>7} {:>7} {:>10} {:>10} {:>7} {:>8} {:>6}",
+            "pid", "arrival", "completion", "turnaround", "waiting", "response", "cpu"
+        )?;
+        for t in &self.tasks {
+            writeln!(
+                f,
+                "{:>7} {:>7} {:>10} {:>10} {:>7} {:>8} {:>6}",
+                t.pid,
+                t.arrival,
+                or_dash(t.completion),
+                or_dash(t.turnaround),
+                t.waiting,
+                or_dash(t.response),
+                t.cpu
+            )?;
+        }
+        writeln!(
+            f,
+            "avg turnaround {:.2}, waiting {:.2}, response {:.2}",
+            self.avg_turnaround(),
+            self.avg_waiting(),
+            self.avg_response()
+        )?;
+        writeln!(
+            f,
+            "throughput {:.2}/s, utilization {:.1}% of {} CPU(s)",
+            self.throughput(),
+            self.utilization() * 100.0,
+            self.ncpus
+        )?;
+        writeln!(
+            f,
+            "context switches {}, migrations {}, fairness {:.3}",
+            self.context_switches,
+            self.migrations,
+            self.fairness()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        policy::{Fcfs, Srtf},
+        sched::Scheduler,
+        task::Task,
+    };
+
+    fn run(scheduler: Box<dyn Scheduler>) -> Metrics {
+        // The convoy example: a long task ahead of two short ones.
+        let tasks = [(1, 0, 24), (2, 1, 3), (3, 2, 3)]
+            .into_iter()
+            .map(|(pid, arrival, burst)| {
+                Task::builder()
+                    .pid(pid)
+                    .arrival(arrival)
+                    .burst(burst)
+                    .build()
+            })
+            .collect();
+        let mut sim = Simulator::new(scheduler, tasks);
+        sim.run();
+        sim.metrics()
+    }
+
+    #[test]
+    fn fcfs_convoy() {
+        let metrics = run(Box::new(Fcfs::new()));
+        let turnaround: Vec<_> = metrics.tasks.iter().map(|t| t.turnaround).collect();
+        assert_eq!(turnaround, [Some(24), Some(26), Some(28)]);
+        let waiting: Vec<_> = metrics.tasks.iter().map(|t| t.waiting).collect();
+        assert_eq!(waiting, [0, 23, 25]);
+        assert_eq!(metrics.avg_waiting(), 16.0);
+        assert_eq!(metrics.avg_response(), 16.0);
+        assert_eq!(metrics.elapsed, 30);
+        assert_eq!(metrics.utilization(), 1.0);
+        assert_eq!(metrics.throughput(), 100.0);
+        assert_eq!(metrics.context_switches, 2);
+    }
+
+    #[test]
+    fn waiting_counts_only_ready_time() {
+        let tasks = vec![
+            Task::builder().pid(1).bursts([2, 5, 2]).build(),
+            Task::builder().pid(2).burst(3).build(),
+        ];
+        let mut sim = Simulator::new(Box::new(Fcfs::new()), tasks);
+        sim.run();
+        let metrics = sim.metrics();
+        // Task 2 runs while task 1 is in I/O, which is not waiting.
+        assert_eq!(metrics.tasks[0].waiting, 0);
+        assert_eq!(metrics.tasks[1].waiting, 2);
+        assert_eq!(metrics.busy, 7);
+        assert_eq!(metrics.elapsed, 9);
+    }
+
+    #[test]
+    fn fairness_tells_policies_apart() {
+        assert_eq!(jain_index(&[2.0, 2.0, 2.0]), 1.0);
+        assert!((jain_index(&[1.0, 0.0, 0.0, 0.0]) - 0.25).abs() < 1e-9);
+
+        let fcfs = run(Box::new(Fcfs::new()));
+        let srtf = run(Box::new(Srtf::new()));
+        // FCFS serves the long task at full rate and starves the short ones.
+        assert!(srtf.fairness() > fcfs.fairness());
+        assert!(srtf.avg_waiting() < fcfs.avg_waiting());
+        let report = fcfs.to_string();
+        assert!(report.contains("context switches 2"), "{report}");
+    }
+}
@@ -0,0 +1,340 @@
+use rbtree::CachedRbTree;
+
+use super::timeline::{self, Entity, Running};
+use crate::{
+    sched::{Enqueue, RqEntry, Scheduler},
+    task::{Pid, TICK_NS, Task, Time, calc_delta_fair},
+};
+
+/// Completely Fair Scheduler: tasks are ordered by vruntime on a red-black
+/// timeline and the leftmost one runs next. Tunables are in nanoseconds and
+/// default to the kernel's values for a single CPU.
+#[derive(Clone)]
+pub struct Cfs {
+    timeline: CachedRbTree<u64, Entity>,
+    /// Sum of the weights on the timeline.
+    load: u64,
+    min_vruntime: u64,
+    curr: Option<Running>,
+    pub sched_latency: u64,
+    pub min_granularity: u64,
+    pub wakeup_granularity: u64,
+}
+
+impl Default for Cfs {
+    fn default() -> Self {
+        Cfs::new()
+    }
+}
+
+impl Cfs {
+    pub fn new() -> Cfs {
+        Cfs {
+            timeline: CachedRbTree::new(),
+            load: 0,
+            min_vruntime: 0,
+            curr: None,
+            sched_latency: 6_000_000,
+            min_granularity: 750_000,
+            wakeup_granularity: 1_000_000,
+        }
+    }
+
+    pub fn min_vruntime(&self) -> u64 {
+        self.min_vruntime
+    }
+
+    /// Queued tasks in timeline order with their vruntime.
+    pub fn timeline(&self) -> impl Iterator<Item = (u64, Pid)> + '_ {
+        self.timeline
+            .iter()
+            .map(|(&vruntime, entity)| (vruntime, entity.pid))
+    }
+
+    /// Period over which every runnable task should run once.
+    fn sched_period(&self, nr_running: u64) -> u64 {
+        let nr_latency = self.sched_latency / self.min_granularity;
+        if nr_running > nr_latency {
+            nr_running * self.min_granularity
+        } else {
+            self.sched_latency
+        }
+    }
+
+    /// Wall-clock slice for an entity of `weight`, given everything runnable
+    /// including it.
+    fn sched_slice(&self, weight: u64, nr_running: u64, load: u64) -> u64 {
+        self.sched_period(nr_running) * weight / load
+    }
+
+    fn nr_running_with_curr(&self) -> (u64, u64) {
+        let (nr, load) = (self.timeline.len() as u64, self.load);
+        match self.curr {
+            Some(curr) => (nr + 1, load + curr.weight),
+            None => (nr, load),
+        }
+    }
+
+    fn update_min_vruntime(&mut self) {
+        let curr = self.curr.map(|c| c.vruntime);
+        timeline::update_min(&mut self.min_vruntime, curr, &self.timeline);
+    }
+
+    fn place_entity(&self, task: &mut Task, kind: Enqueue) {
+        let se = &mut task.se;
+        let vruntime = match kind {
+            // START_DEBIT: a new task owes one virtual slice before it runs,
+            // so forking cannot be used to grab the CPU.
+            Enqueue::Arrival => {
+                let (nr, load) = self.nr_running_with_curr();
+                let slice = self.sched_slice(se.weight, nr + 1, load + se.weight);
+                self.min_vruntime + calc_delta_fair(slice, se.weight)
+            }
+            // GENTLE_FAIR_SLEEPERS: sleepers get at most half a latency of
+            // credit.
+            Enqueue::Wakeup => self.min_vruntime.saturating_sub(self.sched_latency / 2),
+            // It slept on another runqueue's clock: carry over its distance
+            // to min_vruntime there first, as for a migration.
+            Enqueue::WakeupMigrated => {
+                se.vruntime = (self.min_vruntime as i64 - se.vlag).max(0) as u64;
+                self.min_vruntime.saturating_sub(self.sched_latency / 2)
+            }
+            // Keep the distance to min_vruntime it had on the old runqueue,
+            // whose clock is unrelated to this one.
+            Enqueue::Migrate => {
+                se.vruntime = (self.min_vruntime as i64 - se.vlag).max(0) as u64;
+                return;
+            }
+            Enqueue::Requeue => return,
+        };
+        se.vruntime = se.vruntime.max(vruntime);
+    }
+}
+
+impl Scheduler for Cfs {
+    fn name(&self) -> &str {
+        "cfs"
+    }
+
+    fn enqueue(&mut self, task: &mut Task, _now: Time, kind: Enqueue) {
+        if kind == Enqueue::Requeue {
+            self.curr = None;
+        }
+        self.place_entity(task, kind);
+        let entity = Entity {
+            pid: task.pid,
+            weight: task.se.weight,
+        };
+        task.se.node = Some(self.timeline.insert_multi(task.se.vruntime, entity));
+        self.load += entity.weight;
+        self.update_min_vruntime();
+    }
+
+    fn dequeue(&mut self, task: &mut Task, _now: Time) {
+        if let Some((_, entity)) = task
+            .se
+            .node
+            .take()
+            .and_then(|h| self.timeline.remove_handle(h))
+        {
+            task.se.vlag = self.min_vruntime as i64 - task.se.vruntime as i64;
+            self.load -= entity.weight;
+            self.update_min_vruntime();
+        }
+    }
+
+    fn pick_next(&mut self, _now: Time) -> Option<Pid> {
+        let (vruntime, entity) = self.timeline.pop_first()?;
+        self.load -= entity.weight;
+        self.curr = Some(Running {
+            weight: entity.weight,
+            vruntime,
+            slice_exec: 0,
+        });
+        self.update_min_vruntime();
+        Some(entity.pid)
+    }
+
+    fn tick(&mut self, curr: &mut Task, delta: Time, _now: Time) -> bool {
+        // Its handle went stale when it was picked off the timeline.
+        curr.se.node = None;
+        curr.se.vruntime += delta * calc_delta_fair(TICK_NS, curr.se.weight);
+        let running = self.curr.get_or_insert(Running {
+            weight: curr.se.weight,
+            vruntime: 0,
+            slice_exec: 0,
+        });
+        running.vruntime = curr.se.vruntime;
+        running.slice_exec += delta;
+        let (vruntime, slice_exec) = (running.vruntime, running.slice_exec);
+        self.update_min_vruntime();
+
+        // check_preempt_tick
+        let Some((&leftmost, _)) = self.timeline.first() else {
+            return false;
+        };
+        let (nr, load) = self.nr_running_with_curr();
+        let ideal = self.sched_slice(curr.se.weight, nr, load);
+        let exec = slice_exec * TICK_NS;
+        if exec >= ideal {
+            return true;
+        }
+        if exec < self.min_granularity {
+            return false;
+        }
+        vruntime > leftmost && vruntime - leftmost > ideal
+    }
+
+    /// First tick at which one of the `tick` checks can fire, the rest of
+    /// the run being linear in the ticks charged.
+    fn timer(&self, curr: Option<&Task>, _now: Time) -> Option<Time> {
+        let curr = curr?;
+        let (&leftmost, _) = self.timeline.first()?;
+        let exec = self.curr.map_or(0, |running| running.slice_exec);
+        let (nr, load) = self.nr_running_with_curr();
+        let ideal = self.sched_slice(curr.se.weight, nr, load);
+        let used_up = ideal.div_ceil(TICK_NS).saturating_sub(exec);
+        let past_granularity = self.min_granularity.div_ceil(TICK_NS).saturating_sub(exec);
+        let per_tick = calc_delta_fair(TICK_NS, curr.se.weight);
+        let ahead = (leftmost + ideal).saturating_sub(curr.se.vruntime) / per_tick + 1;
+        Some(used_up.min(past_granularity.max(ahead)).max(1))
+    }
+
+    fn task_dead(&mut self, _curr: &mut Task, _now: Time) {
+        self.curr = None;
+    }
+
+    /// Remembers the distance to min_vruntime in case the task wakes up on
+    /// another CPU.
+    fn block(&mut self, curr: &mut Task, _now: Time) {
+        curr.se.vlag = self.min_vruntime as i64 - curr.se.vruntime as i64;
+        self.curr = None;
+    }
+
+    /// Moves the task behind everything queued, like the old
+    /// `sched_compat_yield`.
+    fn yield_task(&mut self, curr: &mut Task, _now: Time) {
+        if let Some((&rightmost, _)) = self.timeline.last() {
+            curr.se.vruntime = curr.se.vruntime.max(rightmost + 1);
+        }
+    }
+
+    fn check_preempt(&self, curr: &Task, woken: &Task, _now: Time) -> bool {
+        let gran = calc_delta_fair(self.wakeup_granularity, woken.se.weight);
+        curr.se.vruntime > woken.se.vruntime + gran
+    }
+
+    fn nr_running(&self) -> usize {
+        self.timeline.len()
+    }
+
+    fn runqueue(&self, _now: Time) -> Vec<RqEntry> {
+        self.timeline
+            .iter()
+            .map(|(&vruntime, entity)| {
+                RqEntry::new(
+                    entity.pid,
+                    [
+                        ("vruntime", vruntime as i64),
+                        ("weight", entity.weight as i64),
+                    ],
+                )
+            })
+            .collect()
+    }
+
+    fn pick_reason(&self, _now: Time) -> Option<String> {
+        let (vruntime, _) = self.timeline.first()?;
+        Some(format!(
+            "leftmost on the timeline, vruntime {vruntime} (min_vruntime {})",
+            self.min_vruntime
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{share::fair_share, sim::Simulator};
+
+    fn task(pid: Pid, nice: i16, arrival: Time) -> Task {
+        Task::builder()
+            .pid(pid)
+            .nice(nice)
+            .arrival(arrival)
+            .burst(100_000)
+            .build()
+    }
+
+    #[test]
+    fn share_follows_weight() {
+        let tasks = vec![task(1, 0, 0), task(2, 0, 0), task(3, 5, 0)];
+        let mut sim = Simulator::new(Box::new(Cfs::new()), tasks);
+        sim.run_until(6_000);
+
+        let expected = fair_share(sim.tasks());
+        for ((pid, share), (_, fair)) in sim.cpu_share().into_iter().zip(expected) {
+            assert!((share - fair).abs() < 0.01, "pid {pid}: {share} vs {fair}");
+        }
+        // With three tasks each one runs for its slice of the 6 ms latency.
+        assert!(sim.trace().iter().all(|s| s.end - s.start <= 3));
+    }
+
+    #[test]
+    fn newcomer_starts_at_min_vruntime() {
+        let tasks = vec![task(1, 0, 0), task(2, 0, 1_000)];
+        let mut sim = Simulator::new(Box::new(Cfs::new()), tasks);
+        sim.run_until(1_000);
+        sim.step();
+
+        // Task 2 neither starves task 1 to catch up nor waits forever.
+        let first = sim.task(1).unwrap().se.vruntime;
+        let second = sim.task(2).unwrap().se.vruntime;
+        assert!(second.abs_diff(first) <= 6 * TICK_NS, "{first} vs {second}");
+        sim.run_until(1_100);
+        let runs = |pid| sim.task(pid).unwrap().se.sum_exec_runtime;
+        assert!(runs(2) >= 45 && runs(2) <= 55, "{}", runs(2));
+    }
+
+    #[test]
+    fn wakeup_on_another_cpu_keeps_its_place() {
+        // Two runqueues whose clocks are far apart.
+        let (mut old, mut new) = (Cfs::new(), Cfs::new());
+        let mut sleeper = task(1, 0, 0);
+        sleeper.se.vruntime = 1_000 * TICK_NS;
+        old.enqueue(&mut sleeper, 0, Enqueue::Requeue);
+        assert_eq!(old.pick_next(0), Some(1));
+        old.tick(&mut sleeper, 1, 1);
+        old.block(&mut sleeper, 1);
+        assert_eq!(old.min_vruntime(), 1_001 * TICK_NS);
+
+        let mut other = task(2, 0, 0);
+        other.se.vruntime = 10 * TICK_NS;
+        new.enqueue(&mut other, 0, Enqueue::Requeue);
+        assert_eq!(new.min_vruntime(), 10 * TICK_NS);
+        new.enqueue(&mut sleeper, 1, Enqueue::WakeupMigrated);
+        // It was level with min_vruntime, so it comes back level with it
+        // rather than 990 ms behind everything.
+        assert_eq!(sleeper.se.vruntime, 10 * TICK_NS);
+        let order: Vec<Pid> = new.timeline().map(|(_, pid)| pid).collect();
+        assert_eq!(order, [2, 1]);
+    }
+
+    #[test]
+    fn picks_leftmost() {
+        let mut cfs = Cfs::new();
+        let mut tasks = [task(1, 0, 0), task(2, 0, 0), task(3, 0, 0)];
+        for (task, vruntime) in tasks.iter_mut().zip([30, 10, 20]) {
+            task.se.vruntime = vruntime * TICK_NS;
+            cfs.enqueue(task, 0, Enqueue::Requeue);
+        }
+        let order: Vec<Pid> = cfs.timeline().map(|(_, pid)| pid).collect();
+        assert_eq!(order, [2, 3, 1]);
+
+        cfs.dequeue(&mut tasks[2], 0);
+        assert_eq!(cfs.pick_next(0), Some(2));
+        assert_eq!(cfs.pick_next(0), Some(1));
+        assert_eq!(cfs.pick_next(0), None);
+        assert_eq!(cfs.min_vruntime(), 30 * TICK_NS);
+    }
+}
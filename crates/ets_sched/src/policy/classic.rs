@@ -0,0 +1,468 @@
+//! Textbook batch policies. They know the length of each task's next CPU
+//! burst, which real systems can only estimate.
+
+use std::cmp::Ordering;
+
+use crate::{
+    sched::{Enqueue, RqEntry, Scheduler},
+    task::{Pid, Task, Time},
+};
+
+#[derive(Clone, Copy, Debug)]
+struct Ready {
+    pid: Pid,
+    /// When it last entered the ready list.
+    since: Time,
+    /// CPU left in its current burst.
+    burst: Time,
+    priority: i16,
+    seq: u64,
+}
+
+/// Unordered ready list; the policies scan it on every pick, and ties go to
+/// whoever has been queued the longest.
+#[derive(Clone, Default)]
+struct ReadyList {
+    entries: Vec<Ready>,
+    seq: u64,
+}
+
+impl ReadyList {
+    fn push(&mut self, task: &Task, now: Time) {
+        self.seq += 1;
+        self.entries.push(Ready {
+            pid: task.pid,
+            since: now,
+            burst: task.burst_left(),
+            priority: task.nice,
+            seq: self.seq,
+        });
+    }
+
+    fn remove(&mut self, pid: Pid) {
+        self.entries.retain(|entry| entry.pid != pid);
+    }
+
+    fn best<F>(&self, mut cmp: F) -> Option<&Ready>
+    where
+        F: FnMut(&Ready, &Ready) -> Ordering,
+    {
+        self.entries
+            .iter()
+            .min_by(|a, b| cmp(a, b).then(a.seq.cmp(&b.seq)))
+    }
+
+    fn take_best<F>(&mut self, cmp: F) -> Option<Pid>
+    where
+        F: FnMut(&Ready, &Ready) -> Ordering,
+    {
+        let pid = self.best(cmp)?.pid;
+        self.remove(pid);
+        Some(pid)
+    }
+
+    fn runqueue<const N: usize>(
+        &self,
+        keys: impl Fn(&Ready) -> [(&'static str, i64); N],
+    ) -> Vec<RqEntry> {
+        self.entries
+            .iter()
+            .map(|entry| RqEntry::new(entry.pid, keys(entry)))
+            .collect()
+    }
+}
+
+macro_rules! ready_list_hooks {
+    () => {
+        fn enqueue(&mut self, task: &mut Task, now: Time, _kind: Enqueue) {
+            self.ready.push(task, now);
+        }
+
+        fn dequeue(&mut self, task: &mut Task, _now: Time) {
+            self.ready.remove(task.pid);
+        }
+
+        fn nr_running(&self) -> usize {
+            self.ready.entries.len()
+        }
+    };
+}
+
+/// For the policies that only reschedule when a task arrives or leaves the
+/// CPU.
+macro_rules! no_tick_hooks {
+    () => {
+        fn tick(&mut self, _curr: &mut Task, _delta: Time, _now: Time) -> bool {
+            false
+        }
+
+        fn timer(&self, _curr: Option<&Task>, _now: Time) -> Option<Time> {
+            None
+        }
+    };
+}
+
+/// First-come, first-served.
+#[derive(Clone, Default)]
+pub struct Fcfs {
+    ready: ReadyList,
+}
+
+impl Fcfs {
+    pub fn new() -> Fcfs {
+        Fcfs::default()
+    }
+}
+
+impl Scheduler for Fcfs {
+    fn name(&self) -> &str {
+        "fcfs"
+    }
+
+    ready_list_hooks!();
+
+    fn pick_next(&mut self, _now: Time) -> Option<Pid> {
+        self.ready.take_best(|_, _| Ordering::Equal)
+    }
+
+    no_tick_hooks!();
+
+    fn runqueue(&self, now: Time) -> Vec<RqEntry> {
+        self.ready
+            .runqueue(|r| [("waited", (now - r.since) as i64)])
+    }
+
+    fn pick_reason(&self, now: Time) -> Option<String> {
+        let first = self.ready.best(|_, _| Ordering::Equal)?;
+        Some(format!(
+            "first in line, waiting for {} ticks",
+            now - first.since
+        ))
+    }
+}
+
+/// Non-preemptive shortest job first.
+#[derive(Clone, Default)]
+pub struct Sjf {
+    ready: ReadyList,
+}
+
+impl Sjf {
+    pub fn new() -> Sjf {
+        Sjf::default()
+    }
+}
+
+impl Scheduler for Sjf {
+    fn name(&self) -> &str {
+        "sjf"
+    }
+
+    ready_list_hooks!();
+
+    fn pick_next(&mut self, _now: Time) -> Option<Pid> {
+        self.ready.take_best(|a, b| a.burst.cmp(&b.burst))
+    }
+
+    no_tick_hooks!();
+
+    fn runqueue(&self, _now: Time) -> Vec<RqEntry> {
+        self.ready.runqueue(|r| [("burst", r.burst as i64)])
+    }
+
+    fn pick_reason(&self, _now: Time) -> Option<String> {
+        let best = self.ready.best(|a, b| a.burst.cmp(&b.burst))?;
+        Some(format!("shortest next burst, {} ticks", best.burst))
+    }
+}
+
+/// Shortest remaining time first, the preemptive SJF.
+#[derive(Clone, Default)]
+pub struct Srtf {
+    ready: ReadyList,
+}
+
+impl Srtf {
+    pub fn new() -> Srtf {
+        Srtf::default()
+    }
+}
+
+impl Scheduler for Srtf {
+    fn name(&self) -> &str {
+        "srtf"
+    }
+
+    ready_list_hooks!();
+
+    fn pick_next(&mut self, _now: Time) -> Option<Pid> {
+        self.ready.take_best(|a, b| a.burst.cmp(&b.burst))
+    }
+
+    no_tick_hooks!();
+
+    fn runqueue(&self, _now: Time) -> Vec<RqEntry> {
+        self.ready.runqueue(|r| [("burst", r.burst as i64)])
+    }
+
+    fn pick_reason(&self, _now: Time) -> Option<String> {
+        let best = self.ready.best(|a, b| a.burst.cmp(&b.burst))?;
+        Some(format!("shortest remaining burst, {} ticks", best.burst))
+    }
+
+    fn check_preempt(&self, curr: &Task, woken: &Task, _now: Time) -> bool {
+        woken.burst_left() < curr.burst_left()
+    }
+}
+
+/// Highest response ratio next: `(waiting + burst) / burst`, so short jobs
+/// go first but long ones cannot wait forever.
+#[derive(Clone, Default)]
+pub struct Hrrn {
+    ready: ReadyList,
+}
+
+impl Hrrn {
+    pub fn new() -> Hrrn {
+        Hrrn::default()
+    }
+}
+
+impl Scheduler for Hrrn {
+    fn name(&self) -> &str {
+        "hrrn"
+    }
+
+    ready_list_hooks!();
+
+    fn pick_next(&mut self, now: Time) -> Option<Pid> {
+        self.ready.take_best(|a, b| by_response_ratio(a, b, now))
+    }
+
+    no_tick_hooks!();
+
+    /// The ratio is in hundredths.
+    fn runqueue(&self, now: Time) -> Vec<RqEntry> {
+        self.ready.runqueue(|r| {
+            let waited = now - r.since;
+            [
+                ("waited", waited as i64),
+                ("burst", r.burst as i64),
+                ("ratio%", (100 * (waited + r.burst) / r.burst) as i64),
+            ]
+        })
+    }
+
+    fn pick_reason(&self, now: Time) -> Option<String> {
+        let best = self.ready.best(|a, b| by_response_ratio(a, b, now))?;
+        let ratio = (now - best.since + best.burst) as f64 / best.burst as f64;
+        Some(format!("highest response ratio, {ratio:.2}"))
+    }
+}
+
+/// Highest ratio first, compared cross-multiplied.
+fn by_response_ratio(a: &Ready, b: &Ready, now: Time) -> Ordering {
+    let ratio = |r: &Ready| (now - r.since + r.burst) as u128;
+    (ratio(b) * a.burst as u128).cmp(&(ratio(a) * b.burst as u128))
+}
+
+/// Static priority taken from the nice value, lower runs first. With
+/// `aging`, a waiting task gains one level every that many ticks.
+#[derive(Clone, Default)]
+pub struct Priority {
+    ready: ReadyList,
+    /// Effective priority the running task was picked with.
+    curr: Option<i64>,
+    pub preemptive: bool,
+    pub aging: Option<Time>,
+}
+
+impl Priority {
+    pub fn new() -> Priority {
+        Priority::default()
+    }
+
+    fn effective(&self, entry: &Ready, now: Time) -> i64 {
+        let boost = self
+            .aging
+            .map_or(0, |interval| (now - entry.since) / interval);
+        entry.priority as i64 - boost as i64
+    }
+
+    fn best(&self, now: Time) -> Option<&Ready> {
+        self.ready
+            .best(|a, b| self.effective(a, now).cmp(&self.effective(b, now)))
+    }
+
+    fn should_preempt(&self, curr: &Task, now: Time) -> bool {
+        let curr = self.curr.unwrap_or(curr.nice as i64);
+        self.preemptive
+            && self
+                .best(now)
+                .is_some_and(|entry| self.effective(entry, now) < curr)
+    }
+}
+
+impl Scheduler for Priority {
+    fn name(&self) -> &str {
+        "priority"
+    }
+
+    ready_list_hooks!();
+
+    fn pick_next(&mut self, now: Time) -> Option<Pid> {
+        let Some(entry) = self.best(now) else {
+            self.curr = None;
+            return None;
+        };
+        let pid = entry.pid;
+        self.curr = Some(self.effective(entry, now));
+        self.ready.remove(pid);
+        Some(pid)
+    }
+
+    fn tick(&mut self, curr: &mut Task, _delta: Time, now: Time) -> bool {
+        self.should_preempt(curr, now)
+    }
+
+    /// Aging changes priorities every tick.
+    fn timer(&self, _curr: Option<&Task>, _now: Time) -> Option<Time> {
+        (self.preemptive && self.aging.is_some()).then_some(1)
+    }
+
+    fn check_preempt(&self, curr: &Task, _woken: &Task, now: Time) -> bool {
+        self.should_preempt(curr, now)
+    }
+
+    fn runqueue(&self, now: Time) -> Vec<RqEntry> {
+        self.ready.runqueue(|r| {
+            [
+                ("nice", r.priority as i64),
+                ("effective", self.effective(r, now)),
+            ]
+        })
+    }
+
+    fn pick_reason(&self, now: Time) -> Option<String> {
+        let best = self.best(now)?;
+        Some(format!(
+            "highest priority, {} (nice {})",
+            self.effective(best, now),
+            best.priority
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::Simulator;
+
+    fn task(pid: Pid, arrival: Time, burst: Time, nice: i16) -> Task {
+        Task::builder()
+            .pid(pid)
+            .arrival(arrival)
+            .burst(burst)
+            .nice(nice)
+            .build()
+    }
+
+    fn run(scheduler: impl Scheduler + 'static, tasks: Vec<Task>) -> Vec<(Pid, Time, Time)> {
+        let mut sim = Simulator::new(Box::new(scheduler), tasks);
+        sim.run();
+        sim.trace()
+            .iter()
+            .map(|s| (s.pid.unwrap(), s.start, s.end))
+            .collect()
+    }
+
+    /// Arrivals 0..3 with bursts 8, 4, 9 and 5.
+    fn textbook() -> Vec<Task> {
+        [(1, 0, 8), (2, 1, 4), (3, 2, 9), (4, 3, 5)]
+            .into_iter()
+            .map(|(pid, arrival, burst)| task(pid, arrival, burst, 0))
+            .collect()
+    }
+
+    #[test]
+    fn batch_orders() {
+        assert_eq!(
+            run(Fcfs::new(), textbook()),
+            [(1, 0, 8), (2, 8, 12), (3, 12, 21), (4, 21, 26)]
+        );
+        assert_eq!(
+            run(Sjf::new(), textbook()),
+            [(1, 0, 8), (2, 8, 12), (4, 12, 17), (3, 17, 26)]
+        );
+        assert_eq!(
+            run(Srtf::new(), textbook()),
+            [(1, 0, 1), (2, 1, 5), (4, 5, 10), (1, 10, 17), (3, 17, 26)]
+        );
+        assert_eq!(
+            run(Hrrn::new(), textbook()),
+            [(1, 0, 8), (2, 8, 12), (4, 12, 17), (3, 17, 26)]
+        );
+    }
+
+    #[test]
+    fn hrrn_favours_long_waiters() {
+        // At t=6 task 2 has waited 5 for a burst of 5 (ratio 2) and task 3
+        // has waited 1 for a burst of 3 (ratio 1.33).
+        let tasks = || vec![task(1, 0, 6, 0), task(2, 1, 5, 0), task(3, 5, 3, 0)];
+        assert_eq!(
+            run(Hrrn::new(), tasks()),
+            [(1, 0, 6), (2, 6, 11), (3, 11, 14)]
+        );
+        assert_eq!(run(Sjf::new(), tasks()), [(1, 0, 6), (3, 6, 9), (2, 9, 14)]);
+    }
+
+    #[test]
+    fn shortest_next_burst_not_total_work() {
+        // Task 1 has more work overall but only one tick before its I/O.
+        let tasks = || {
+            vec![
+                Task::builder().pid(1).bursts([1, 10, 30]).build(),
+                task(2, 0, 10, 0),
+            ]
+        };
+        assert_eq!(
+            run(Sjf::new(), tasks()),
+            [(1, 0, 1), (2, 1, 11), (1, 11, 41)]
+        );
+        let mut srtf = Srtf::new();
+        let mut first = tasks().remove(0);
+        srtf.enqueue(&mut first, 0, Enqueue::Arrival);
+        assert_eq!(
+            srtf.pick_reason(0).unwrap(),
+            "shortest remaining burst, 1 ticks"
+        );
+    }
+
+    #[test]
+    fn priority_with_aging() {
+        let tasks = vec![
+            task(1, 0, 10, 3),
+            task(2, 0, 1, 1),
+            task(3, 0, 2, 4),
+            task(4, 0, 1, 5),
+            task(5, 0, 5, 2),
+        ];
+        assert_eq!(
+            run(Priority::new(), tasks),
+            [(2, 0, 1), (5, 1, 6), (1, 6, 16), (3, 16, 18), (4, 18, 19)]
+        );
+
+        let starving = || vec![task(1, 0, 30, 0), task(2, 0, 2, 5)];
+        let mut strict = Priority::new();
+        strict.preemptive = true;
+        assert_eq!(run(strict, starving()), [(1, 0, 30), (2, 30, 32)]);
+
+        let mut aging = Priority::new();
+        aging.preemptive = true;
+        aging.aging = Some(2);
+        assert_eq!(
+            run(aging, starving()),
+            [(1, 0, 12), (2, 12, 14), (1, 14, 32)]
+        );
+    }
+}
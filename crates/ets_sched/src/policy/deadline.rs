@@ -0,0 +1,503 @@
+use std::{collections::HashMap, fmt};
+
+use rbtree::{CachedRbTree, Handle};
+
+use crate::{
+    sched::{Enqueue, RqEntry, Scheduler},
+    task::{DlParams, Pid, Policy, Task, Time},
+};
+
+/// Fixed-point shift for bandwidths, as in the kernel's `to_ratio`.
+const BW_SHIFT: u32 = 20;
+
+fn to_ratio(period: Time, runtime: Time) -> u64 {
+    (runtime << BW_SHIFT) / period
+}
+
+/// How the deadline class orders its tasks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DlMode {
+    /// `SCHED_DEADLINE`: EDF over Constant Bandwidth Servers, which throttle
+    /// a task that overruns its runtime until its next period.
+    #[default]
+    Cbs,
+    /// Earliest job deadline first, without budget enforcement.
+    Edf,
+    /// Fixed priorities, shorter period first.
+    RateMonotonic,
+}
+
+impl DlMode {
+    fn name(self) -> &'static str {
+        match self {
+            DlMode::Cbs => "dl",
+            DlMode::Edf => "edf",
+            DlMode::RateMonotonic => "rm",
+        }
+    }
+}
+
+/// A deadline task that would push the reserved bandwidth over the cap.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdmissionError {
+    pub pid: Pid,
+    pub requested: f64,
+    pub available: f64,
+}
+
+impl fmt::Display for AdmissionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "task {} asks for {:.3} of the CPU but only {:.3} is left",
+            self.pid, self.requested, self.available
+        )
+    }
+}
+
+impl std::error::Error for AdmissionError {}
+
+/// Per-task server state, the kernel's `sched_dl_entity`.
+#[derive(Clone, Copy, Debug)]
+struct Server {
+    params: DlParams,
+    runtime: i64,
+    deadline: Time,
+    /// Key it is (or was last) queued with.
+    key: Time,
+    node: Option<Handle>,
+    /// Replenishment time while throttled.
+    throttled: Option<Time>,
+}
+
+/// The deadline class, above the real-time and normal ones in `next`.
+#[derive(Clone)]
+pub struct Deadline {
+    name: String,
+    mode: DlMode,
+    queue: CachedRbTree<Time, Pid>,
+    servers: HashMap<Pid, Server>,
+    total_bw: u64,
+    max_bw: u64,
+    rejected: Vec<AdmissionError>,
+    curr: Option<Pid>,
+    next: Box<dyn Scheduler>,
+}
+
+impl Deadline {
+    pub fn new(mode: DlMode, next: Box<dyn Scheduler>) -> Deadline {
+        Deadline {
+            name: format!("{}+{}", mode.name(), next.name()),
+            mode,
+            queue: CachedRbTree::new(),
+            servers: HashMap::new(),
+            total_bw: 0,
+            // sched_rt_runtime_us / sched_rt_period_us = 95%.
+            max_bw: to_ratio(100, 95),
+            rejected: Vec::new(),
+            curr: None,
+            next,
+        }
+    }
+
+    pub fn mode(&self) -> DlMode {
+        self.mode
+    }
+
+    /// Caps the bandwidth that can be reserved, 0.95 by default.
+    pub fn set_max_bandwidth(&mut self, max: f64) {
+        self.max_bw = (max * (1u64 << BW_SHIFT) as f64) as u64;
+    }
+
+    /// Fraction of the CPU reserved by the admitted tasks.
+    pub fn bandwidth(&self) -> f64 {
+        self.total_bw as f64 / (1u64 << BW_SHIFT) as f64
+    }
+
+    /// Tasks refused at admission; they run in the next class instead.
+    pub fn rejected(&self) -> &[AdmissionError] {
+        &self.rejected
+    }
+
+    /// Reserves bandwidth for a deadline task. Only the CBS mode enforces
+    /// the cap; the other modes admit everything, as schedulability is what
+    /// they are used to study.
+    pub fn admit(&mut self, task: &Task) -> Result<(), AdmissionError> {
+        let params = task.dl.expect("not a deadline task");
+        let bw = to_ratio(params.period, params.runtime);
+        if self.mode == DlMode::Cbs && self.total_bw + bw > self.max_bw {
+            let scale = (1u64 << BW_SHIFT) as f64;
+            return Err(AdmissionError {
+                pid: task.pid,
+                requested: bw as f64 / scale,
+                available: self.max_bw.saturating_sub(self.total_bw) as f64 / scale,
+            });
+        }
+        self.total_bw += bw;
+        self.servers.insert(
+            task.pid,
+            Server {
+                params,
+                runtime: 0,
+                deadline: 0,
+                key: 0,
+                node: None,
+                throttled: None,
+            },
+        );
+        Ok(())
+    }
+
+    fn insert(&mut self, pid: Pid) {
+        let server = self.servers.get_mut(&pid).unwrap();
+        server.node = Some(self.queue.insert_multi(server.key, pid));
+    }
+
+    /// Refills the servers whose throttling has run out. Returns whether any
+    /// task became runnable.
+    fn replenish(&mut self, now: Time) -> bool {
+        let ready: Vec<Pid> = self
+            .servers
+            .iter()
+            .filter(|(_, server)| server.throttled.is_some_and(|at| at <= now))
+            .map(|(&pid, _)| pid)
+            .collect();
+        for &pid in &ready {
+            let server = self.servers.get_mut(&pid).unwrap();
+            server.throttled = None;
+            while server.runtime <= 0 {
+                server.deadline += server.params.period;
+                server.runtime += server.params.runtime as i64;
+            }
+            server.key = server.deadline;
+            self.insert(pid);
+        }
+        !ready.is_empty()
+    }
+
+    fn is_dl(&self, task: &Task) -> bool {
+        task.policy.is_dl() && self.servers.contains_key(&task.pid)
+    }
+}
+
+impl Scheduler for Deadline {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn enqueue(&mut self, task: &mut Task, now: Time, kind: Enqueue) {
+        if kind == Enqueue::Arrival
+            && task.policy.is_dl()
+            && !self.servers.contains_key(&task.pid)
+            && let Err(err) = self.admit(task)
+        {
+            self.rejected.push(err);
+            task.policy = Policy::Normal;
+        }
+        if !self.is_dl(task) {
+            return self.next.enqueue(task, now, kind);
+        }
+        if kind == Enqueue::Requeue {
+            self.curr = None;
+        }
+        let mode = self.mode;
+        let job_deadline = task.job.map(|job| job.deadline);
+        let server = self.servers.get_mut(&task.pid).unwrap();
+        let params = server.params;
+        if kind != Enqueue::Requeue {
+            // CBS wake-up rule: keep the current reservation only if it can
+            // be used up by its deadline without exceeding the bandwidth.
+            let overflow = server.runtime as i128 * params.period as i128
+                > (server.deadline as i128 - now as i128) * params.runtime as i128;
+            if mode != DlMode::Cbs || server.deadline <= now || overflow {
+                server.deadline = job_deadline.unwrap_or(now + params.deadline);
+                server.runtime = params.runtime as i64;
+            }
+        }
+        if mode == DlMode::Cbs && server.runtime <= 0 {
+            server.throttled = Some(server.deadline);
+            return;
+        }
+        server.key = match mode {
+            DlMode::Cbs => server.deadline,
+            DlMode::Edf => job_deadline.unwrap_or(server.deadline),
+            DlMode::RateMonotonic => params.period,
+        };
+        self.insert(task.pid);
+    }
+
+    fn dequeue(&mut self, task: &mut Task, now: Time) {
+        if !self.is_dl(task) {
+            return self.next.dequeue(task, now);
+        }
+        let server = self.servers.get_mut(&task.pid).unwrap();
+        server.throttled = None;
+        if let Some(handle) = server.node.take() {
+            self.queue.remove_handle(handle);
+        }
+    }
+
+    fn pick_next(&mut self, now: Time) -> Option<Pid> {
+        self.replenish(now);
+        match self.queue.pop_first() {
+            Some((_, pid)) => {
+                self.servers.get_mut(&pid).unwrap().node = None;
+                self.curr = Some(pid);
+                Some(pid)
+            }
+            None => {
+                self.curr = None;
+                self.next.pick_next(now)
+            }
+        }
+    }
+
+    fn tick(&mut self, curr: &mut Task, delta: Time, now: Time) -> bool {
+        let woke = self.replenish(now);
+        if !self.is_dl(curr) {
+            return self.next.tick(curr, delta, now) || !self.queue.is_empty();
+        }
+        let server = self.servers.get_mut(&curr.pid).unwrap();
+        server.runtime -= delta as i64;
+        if self.mode == DlMode::Cbs && server.runtime <= 0 {
+            return true;
+        }
+        let key = server.key;
+        woke && self.queue.first().is_some_and(|(&first, _)| first < key)
+    }
+
+    /// When the running server runs out of runtime or a throttled one is
+    /// refilled.
+    fn timer(&self, curr: Option<&Task>, now: Time) -> Option<Time> {
+        let replenish = self
+            .servers
+            .values()
+            .filter_map(|server| server.throttled)
+            .min()
+            .map(|at| at.saturating_sub(now).max(1));
+        let own = match curr {
+            Some(task) if self.is_dl(task) => {
+                (self.mode == DlMode::Cbs).then(|| self.servers[&task.pid].runtime.max(1) as Time)
+            }
+            _ => self.next.timer(curr, now),
+        };
+        [replenish, own].into_iter().flatten().min()
+    }
+
+    fn task_dead(&mut self, curr: &mut Task, now: Time) {
+        if !self.is_dl(curr) {
+            return self.next.task_dead(curr, now);
+        }
+        let server = self.servers.remove(&curr.pid).unwrap();
+        self.total_bw -= to_ratio(server.params.period, server.params.runtime);
+        self.curr = None;
+    }
+
+    fn block(&mut self, curr: &mut Task, now: Time) {
+        if !self.is_dl(curr) {
+            return self.next.block(curr, now);
+        }
+        self.curr = None;
+    }
+
+    /// A deadline task yields to say its job is done; it gives up the rest
+    /// of its runtime.
+    fn yield_task(&mut self, curr: &mut Task, now: Time) {
+        if !self.is_dl(curr) {
+            return self.next.yield_task(curr, now);
+        }
+        if let Some(server) = self.servers.get_mut(&curr.pid) {
+            server.runtime = 0;
+        }
+    }
+
+    fn check_preempt(&self, curr: &Task, woken: &Task, now: Time) -> bool {
+        match (self.is_dl(curr), self.is_dl(woken)) {
+            (false, false) => self.next.check_preempt(curr, woken, now),
+            (false, true) => true,
+            (true, false) => false,
+            (true, true) => {
+                let key = |task: &Task| self.servers[&task.pid].key;
+                self.servers[&woken.pid].node.is_some() && key(woken) < key(curr)
+            }
+        }
+    }
+
+    fn nr_running(&self) -> usize {
+        let throttled = self
+            .servers
+            .values()
+            .filter(|server| server.throttled.is_some())
+            .count();
+        self.queue.len() + throttled + self.next.nr_running()
+    }
+
+    /// Queued deadline tasks by key, the throttled ones, then the next
+    /// class.
+    fn runqueue(&self, now: Time) -> Vec<RqEntry> {
+        let entry = |pid: Pid, server: &Server| {
+            let mut keys = vec![
+                ("deadline", server.deadline as i64),
+                ("runtime", server.runtime),
+            ];
+            if let Some(at) = server.throttled {
+                keys.push(("throttled", at as i64));
+            } else if self.mode != DlMode::Cbs {
+                keys.push(("key", server.key as i64));
+            }
+            RqEntry::new(pid, keys)
+        };
+        let mut entries: Vec<RqEntry> = self
+            .queue
+            .iter()
+            .map(|(_, &pid)| entry(pid, &self.servers[&pid]))
+            .collect();
+        let mut throttled: Vec<(&Pid, &Server)> = self
+            .servers
+            .iter()
+            .filter(|(_, server)| server.throttled.is_some())
+            .collect();
+        throttled.sort_by_key(|&(&pid, server)| (server.throttled, pid));
+        entries.extend(
+            throttled
+                .into_iter()
+                .map(|(&pid, server)| entry(pid, server)),
+        );
+        entries.extend(self.next.runqueue(now));
+        entries
+    }
+
+    fn pick_reason(&self, now: Time) -> Option<String> {
+        let replenished = self
+            .servers
+            .values()
+            .any(|server| server.throttled.is_some_and(|at| at <= now));
+        let Some((&key, _)) = self.queue.first() else {
+            if replenished {
+                return Some("earliest deadline after replenishing runtime".into());
+            }
+            return self
+                .next
+                .pick_reason(now)
+                .map(|reason| format!("no deadline task; {}: {reason}", self.next.name()));
+        };
+        let rule = match self.mode {
+            DlMode::Cbs => "earliest deadline",
+            DlMode::Edf => "earliest job deadline",
+            DlMode::RateMonotonic => "shortest period",
+        };
+        let note = if replenished {
+            ", unless a replenished task beats it"
+        } else {
+            ""
+        };
+        Some(format!("{rule}, {key}{note}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{analysis::Schedulability, policy::Cfs, sim::Simulator};
+
+    fn periodic(pid: Pid, runtime: Time, period: Time, burst: Time, jobs: u32) -> Task {
+        Task::builder()
+            .pid(pid)
+            .sched_deadline(DlParams::implicit(runtime, period))
+            .burst(burst)
+            .jobs(jobs)
+            .build()
+    }
+
+    fn run(mode: DlMode, tasks: Vec<Task>) -> Simulator {
+        let mut sim = Simulator::new(Box::new(Deadline::new(mode, Box::new(Cfs::new()))), tasks);
+        sim.run();
+        sim
+    }
+
+    fn misses(sim: &Simulator, pid: Pid) -> usize {
+        sim.deadline_misses()
+            .iter()
+            .filter(|miss| miss.pid == pid)
+            .count()
+    }
+
+    #[test]
+    fn edf_schedules_what_rm_cannot() {
+        let set = || vec![periodic(1, 2, 5, 2, 7), periodic(2, 4, 7, 4, 5)];
+        let params: Vec<DlParams> = set().iter().map(|t| t.dl.unwrap()).collect();
+        let report = Schedulability::analyze(&params);
+        assert!(!report.rm_by_rta() && report.edf());
+
+        let rm = run(DlMode::RateMonotonic, set());
+        assert_eq!(misses(&rm, 1), 0);
+        assert!(misses(&rm, 2) > 0);
+        let edf = run(DlMode::Edf, set());
+        assert!(edf.deadline_misses().is_empty());
+        assert!(edf.now() <= 35);
+    }
+
+    #[test]
+    fn cbs_isolates_overruns() {
+        // Task 1 reserves 4 ticks per period but needs 8 each job.
+        let set = || vec![periodic(1, 4, 10, 8, 4), periodic(2, 4, 10, 4, 4)];
+        let edf = run(DlMode::Edf, set());
+        assert!(misses(&edf, 2) > 0);
+
+        let cbs = run(DlMode::Cbs, set());
+        assert_eq!(misses(&cbs, 2), 0);
+        assert!(misses(&cbs, 1) > 0);
+    }
+
+    #[test]
+    fn late_jobs_stay_on_the_period_grid() {
+        // Each job needs twice the reservation, so every one of them ends
+        // late, yet jobs are still released every 10 ticks.
+        let cbs = run(DlMode::Cbs, vec![periodic(1, 4, 10, 8, 4)]);
+        let misses: Vec<(Time, Time, Time)> = cbs
+            .deadline_misses()
+            .iter()
+            .map(|miss| (miss.release, miss.deadline, miss.completion))
+            .collect();
+        assert_eq!(
+            misses,
+            [(0, 10, 14), (10, 20, 34), (20, 30, 54), (30, 40, 74)]
+        );
+    }
+
+    #[test]
+    fn admission_control() {
+        let mut dl = Deadline::new(DlMode::Cbs, Box::new(Cfs::new()));
+        assert!(dl.admit(&periodic(1, 5, 10, 5, 1)).is_ok());
+        assert!(dl.admit(&periodic(2, 4, 10, 4, 1)).is_ok());
+        let err = dl.admit(&periodic(3, 1, 10, 1, 1)).unwrap_err();
+        assert_eq!(err.pid, 3);
+        assert!((dl.bandwidth() - 0.9).abs() < 1e-3);
+
+        // A refused task still runs, in the normal class.
+        let tasks = vec![
+            periodic(1, 6, 10, 6, 2),
+            periodic(2, 6, 10, 6, 2),
+            Task::builder().pid(3).burst(5).build(),
+        ];
+        let sim = run(DlMode::Cbs, tasks);
+        assert_eq!(sim.task(2).unwrap().policy, Policy::Normal);
+        assert!(sim.is_done());
+        assert_eq!(sim.scheduler().name(), "dl+cfs");
+    }
+
+    #[test]
+    fn deadline_preempts_lower_classes() {
+        let tasks = vec![
+            Task::builder().pid(1).burst(20).build(),
+            periodic(2, 2, 10, 2, 2),
+        ];
+        let sim = run(DlMode::Cbs, tasks);
+        let slices: Vec<(Pid, Time, Time)> = sim
+            .trace()
+            .iter()
+            .map(|s| (s.pid.unwrap(), s.start, s.end))
+            .collect();
+        assert_eq!(slices, [(2, 0, 2), (1, 2, 10), (2, 10, 12), (1, 12, 24)]);
+    }
+}
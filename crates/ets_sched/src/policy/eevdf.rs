@@ -0,0 +1,421 @@
+use rbtree::{Augment, CachedRbTree, Handle, NodeRef};
+
+use super::timeline::{self, Entity, Running};
+use crate::{
+    sched::{Enqueue, RqEntry, Scheduler},
+    task::{Pid, TICK_NS, Task, Time, calc_delta_fair},
+};
+
+/// Queued task with the virtual deadline of its current request.
+#[derive(Clone, Copy, Debug)]
+struct Request {
+    entity: Entity,
+    deadline: u64,
+}
+
+/// Caches the earliest virtual deadline of every subtree.
+struct MinDeadline;
+
+impl Augment<u64, Request> for MinDeadline {
+    type Value = u64;
+
+    fn compute(_: &u64, request: &Request, left: Option<&u64>, right: Option<&u64>) -> u64 {
+        [left, right]
+            .into_iter()
+            .flatten()
+            .fold(request.deadline, |min, &d| min.min(d))
+    }
+}
+
+type Node<'a> = NodeRef<'a, u64, Request, MinDeadline>;
+
+/// Earliest Eligible Virtual Deadline First, the fair class of Linux 6.6 and
+/// later. A task is eligible while its vruntime is not ahead of the
+/// weighted average, i.e. while its lag is not negative; among eligible
+/// tasks the one with the earliest virtual deadline runs.
+#[derive(Clone)]
+pub struct Eevdf {
+    timeline: CachedRbTree<u64, Request, MinDeadline>,
+    /// Sum of `weight * vruntime` and of the weights on the timeline, from
+    /// which the average vruntime is derived.
+    sum_wv: u128,
+    load: u64,
+    min_vruntime: u64,
+    curr: Option<Running>,
+    /// Request size in nanoseconds. The kernel's 0.75 ms is below the tick,
+    /// so the default is 3 ms.
+    pub base_slice: u64,
+}
+
+impl Default for Eevdf {
+    fn default() -> Self {
+        Eevdf::new()
+    }
+}
+
+impl Eevdf {
+    pub fn new() -> Eevdf {
+        Eevdf {
+            timeline: CachedRbTree::new_augmented(),
+            sum_wv: 0,
+            load: 0,
+            min_vruntime: 0,
+            curr: None,
+            base_slice: 3_000_000,
+        }
+    }
+
+    /// Weighted average vruntime of the runnable tasks, the point of zero
+    /// lag.
+    pub fn avg_vruntime(&self) -> u64 {
+        let (sum, load) = self.totals();
+        if load == 0 {
+            self.min_vruntime
+        } else {
+            (sum / load as u128) as u64
+        }
+    }
+
+    /// Queued tasks in timeline order with their vruntime and deadline.
+    pub fn timeline(&self) -> impl Iterator<Item = (u64, u64, Pid)> + '_ {
+        self.timeline
+            .iter()
+            .map(|(&vruntime, request)| (vruntime, request.deadline, request.entity.pid))
+    }
+
+    fn totals(&self) -> (u128, u64) {
+        match self.curr {
+            Some(curr) => (
+                self.sum_wv + curr.weight as u128 * curr.vruntime as u128,
+                self.load + curr.weight,
+            ),
+            None => (self.sum_wv, self.load),
+        }
+    }
+
+    fn eligible(&self, vruntime: u64) -> bool {
+        let (sum, load) = self.totals();
+        sum >= vruntime as u128 * load as u128
+    }
+
+    fn vslice(&self, weight: u64) -> u64 {
+        calc_delta_fair(self.base_slice, weight)
+    }
+
+    fn update_min_vruntime(&mut self) {
+        let curr = self.curr.map(|c| c.vruntime);
+        timeline::update_min(&mut self.min_vruntime, curr, &self.timeline);
+    }
+
+    /// Places a task that is becoming runnable so that it keeps the lag it
+    /// left with, and gives it a fresh deadline.
+    fn place_entity(&self, task: &mut Task, kind: Enqueue) {
+        let se = &mut task.se;
+        let mut lag = se.vlag as i128;
+        let (_, load) = self.totals();
+        // Adding the task moves the average towards it; inflate the lag so it
+        // is still `vlag` after the insertion.
+        if load > 0 {
+            lag = lag * (load + se.weight) as i128 / load as i128;
+        }
+        let vruntime = self.avg_vruntime() as i128 - lag;
+        se.vruntime = vruntime.max(0) as u64;
+        let mut vslice = self.vslice(se.weight);
+        // PLACE_DEADLINE_INITIAL: new tasks get half a slice so they start
+        // soon without jumping the whole queue.
+        if kind == Enqueue::Arrival {
+            vslice /= 2;
+        }
+        se.deadline = se.vruntime + vslice;
+    }
+
+    /// Eligible task with the earliest deadline.
+    fn pick_eevdf(&self) -> Option<Node<'_>> {
+        let mut node = self.timeline.as_tree().root_node();
+        let mut best: Option<Node<'_>> = None;
+        let mut best_left: Option<Node<'_>> = None;
+        while let Some(n) = node {
+            // Everything to the right has a larger vruntime and is not
+            // eligible either.
+            if !self.eligible(*n.key()) {
+                node = n.left();
+                continue;
+            }
+            // Everything to the left is eligible; remember the subtree with
+            // the earliest deadline.
+            if let Some(left) = n.left()
+                && best_left.is_none_or(|b| left.aggregate() < b.aggregate())
+            {
+                best_left = Some(left);
+            }
+            if best.is_none_or(|b| n.value().deadline < b.value().deadline) {
+                best = Some(n);
+            }
+            node = n.right();
+        }
+        let Some(mut subtree) = best_left else {
+            return best;
+        };
+        // On equal deadlines prefer the subtree, whose vruntimes are smaller.
+        if best.is_some_and(|b| b.value().deadline < *subtree.aggregate()) {
+            return best;
+        }
+        let target = *subtree.aggregate();
+        loop {
+            if let Some(left) = subtree.left()
+                && *left.aggregate() == target
+            {
+                subtree = left;
+            } else if subtree.value().deadline == target {
+                return Some(subtree);
+            } else {
+                subtree = subtree.right()?;
+            }
+        }
+    }
+
+    /// Remembers how far the task is from the average as it leaves, so it
+    /// can be placed at the same lag when it comes back.
+    fn update_entity_lag(&self, task: &mut Task, avg: u64) {
+        let limit = calc_delta_fair(self.base_slice.max(TICK_NS) * 2, task.se.weight) as i64;
+        task.se.vlag = (avg as i64 - task.se.vruntime as i64).clamp(-limit, limit);
+    }
+
+    fn remove(&mut self, handle: Handle) -> Option<(u64, Entity)> {
+        let (vruntime, Request { entity, .. }) = self.timeline.remove_handle(handle)?;
+        self.sum_wv -= entity.weight as u128 * vruntime as u128;
+        self.load -= entity.weight;
+        Some((vruntime, entity))
+    }
+}
+
+impl Scheduler for Eevdf {
+    fn name(&self) -> &str {
+        "eevdf"
+    }
+
+    fn enqueue(&mut self, task: &mut Task, _now: Time, kind: Enqueue) {
+        if kind == Enqueue::Requeue {
+            self.curr = None;
+        } else {
+            self.place_entity(task, kind);
+        }
+        let entity = Entity {
+            pid: task.pid,
+            weight: task.se.weight,
+        };
+        let request = Request {
+            entity,
+            deadline: task.se.deadline,
+        };
+        task.se.node = Some(self.timeline.insert_multi(task.se.vruntime, request));
+        self.sum_wv += entity.weight as u128 * task.se.vruntime as u128;
+        self.load += entity.weight;
+        self.update_min_vruntime();
+    }
+
+    fn dequeue(&mut self, task: &mut Task, _now: Time) {
+        let avg = self.avg_vruntime();
+        if task.se.node.take().and_then(|h| self.remove(h)).is_some() {
+            self.update_entity_lag(task, avg);
+            self.update_min_vruntime();
+        }
+    }
+
+    fn pick_next(&mut self, _now: Time) -> Option<Pid> {
+        let handle = self.pick_eevdf()?.handle();
+        let (vruntime, entity) = self.remove(handle)?;
+        self.curr = Some(Running {
+            weight: entity.weight,
+            vruntime,
+            slice_exec: 0,
+        });
+        self.update_min_vruntime();
+        Some(entity.pid)
+    }
+
+    fn tick(&mut self, curr: &mut Task, delta: Time, _now: Time) -> bool {
+        curr.se.node = None;
+        curr.se.vruntime += delta * calc_delta_fair(TICK_NS, curr.se.weight);
+        let running = self.curr.get_or_insert(Running {
+            weight: curr.se.weight,
+            vruntime: 0,
+            slice_exec: 0,
+        });
+        running.vruntime = curr.se.vruntime;
+        running.slice_exec += delta;
+        self.update_min_vruntime();
+
+        // update_deadline: once the request is served, issue a new one and
+        // let the others compete for the CPU.
+        if curr.se.vruntime < curr.se.deadline {
+            return false;
+        }
+        curr.se.deadline = curr.se.vruntime + self.vslice(curr.se.weight);
+        !self.timeline.is_empty()
+    }
+
+    /// When the current request is served, even with nobody else queued,
+    /// as that is when its deadline moves.
+    fn timer(&self, curr: Option<&Task>, _now: Time) -> Option<Time> {
+        let se = &curr?.se;
+        let per_tick = calc_delta_fair(TICK_NS, se.weight);
+        Some(
+            se.deadline
+                .saturating_sub(se.vruntime)
+                .div_ceil(per_tick)
+                .max(1),
+        )
+    }
+
+    fn task_dead(&mut self, _curr: &mut Task, _now: Time) {
+        self.curr = None;
+    }
+
+    fn block(&mut self, curr: &mut Task, _now: Time) {
+        let avg = self.avg_vruntime();
+        self.update_entity_lag(curr, avg);
+        self.curr = None;
+    }
+
+    /// Gives up the rest of the current request.
+    fn yield_task(&mut self, curr: &mut Task, _now: Time) {
+        curr.se.deadline += self.vslice(curr.se.weight);
+    }
+
+    /// The woken task preempts when it is what a pick would choose now.
+    fn check_preempt(&self, curr: &Task, woken: &Task, _now: Time) -> bool {
+        let Some(best) = self.pick_eevdf() else {
+            return false;
+        };
+        if best.value().entity.pid != woken.pid {
+            return false;
+        }
+        !self.eligible(curr.se.vruntime) || woken.se.deadline < curr.se.deadline
+    }
+
+    fn nr_running(&self) -> usize {
+        self.timeline.len()
+    }
+
+    fn runqueue(&self, _now: Time) -> Vec<RqEntry> {
+        let avg = self.avg_vruntime() as i64;
+        self.timeline
+            .iter()
+            .map(|(&vruntime, request)| {
+                RqEntry::new(
+                    request.entity.pid,
+                    [
+                        ("vruntime", vruntime as i64),
+                        ("deadline", request.deadline as i64),
+                        ("lag", avg - vruntime as i64),
+                        ("eligible", self.eligible(vruntime) as i64),
+                    ],
+                )
+            })
+            .collect()
+    }
+
+    fn pick_reason(&self, _now: Time) -> Option<String> {
+        let best = self.pick_eevdf()?;
+        let eligible = self
+            .timeline
+            .iter()
+            .filter(|&(&vruntime, _)| self.eligible(vruntime))
+            .count();
+        Some(format!(
+            "earliest deadline {} among {eligible} eligible (avg vruntime {})",
+            best.value().deadline,
+            self.avg_vruntime()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{policy::Cfs, share::fair_share, sim::Simulator};
+
+    fn task(pid: Pid, nice: i16, burst: Time) -> Task {
+        Task::builder().pid(pid).nice(nice).burst(burst).build()
+    }
+
+    fn queued(pid: Pid, vruntime: u64, deadline: u64) -> Task {
+        let mut task = task(pid, 0, 1);
+        task.se.vruntime = vruntime * TICK_NS;
+        task.se.deadline = deadline * TICK_NS;
+        task
+    }
+
+    #[test]
+    fn picks_earliest_eligible_deadline() {
+        let mut eevdf = Eevdf::new();
+        let mut tasks = [queued(1, 0, 100), queued(2, 10, 15), queued(3, 20, 12)];
+        for task in &mut tasks {
+            eevdf.enqueue(task, 0, Enqueue::Requeue);
+        }
+        assert_eq!(eevdf.avg_vruntime(), 10 * TICK_NS);
+
+        // Task 3 has the earliest deadline but is ahead of the average.
+        assert_eq!(eevdf.pick_next(0), Some(2));
+        eevdf.task_dead(&mut tasks[1], 0);
+        assert_eq!(eevdf.pick_next(0), Some(1));
+        eevdf.task_dead(&mut tasks[0], 0);
+        assert_eq!(eevdf.pick_next(0), Some(3));
+    }
+
+    #[test]
+    fn wakeup_keeps_lag() {
+        let mut eevdf = Eevdf::new();
+        let mut tasks = [queued(1, 0, 10), queued(2, 10, 20)];
+        for task in &mut tasks {
+            eevdf.enqueue(task, 0, Enqueue::Requeue);
+        }
+        eevdf.dequeue(&mut tasks[0], 0);
+        assert_eq!(tasks[0].se.vlag, 5 * TICK_NS as i64);
+        assert_eq!(eevdf.avg_vruntime(), 10 * TICK_NS);
+
+        eevdf.enqueue(&mut tasks[0], 0, Enqueue::Wakeup);
+        assert_eq!(tasks[0].se.vruntime, 0);
+        assert_eq!(tasks[0].se.deadline, 3 * TICK_NS);
+        assert_eq!(eevdf.avg_vruntime(), 5 * TICK_NS);
+    }
+
+    #[test]
+    fn share_follows_weight() {
+        let tasks = vec![
+            task(1, 0, 100_000),
+            task(2, -3, 100_000),
+            task(3, 5, 100_000),
+        ];
+        let mut sim = Simulator::new(Box::new(Eevdf::new()), tasks);
+        sim.run_until(6_000);
+
+        let expected = fair_share(sim.tasks());
+        for ((pid, share), (_, fair)) in sim.cpu_share().into_iter().zip(expected) {
+            assert!((share - fair).abs() < 0.01, "pid {pid}: {share} vs {fair}");
+        }
+    }
+
+    #[test]
+    fn same_workload_as_cfs() {
+        let workload = || vec![task(1, 0, 40), task(2, 0, 40), task(3, 10, 20)];
+        let mut cfs = Simulator::new(Box::new(Cfs::new()), workload());
+        let mut eevdf = Simulator::new(Box::new(Eevdf::new()), workload());
+        cfs.run();
+        eevdf.run();
+
+        assert_eq!(cfs.now(), eevdf.now());
+        // A nice-0 request is 3 ms, so no slice runs longer than that.
+        let longest = |sim: &Simulator, pid| {
+            sim.trace()
+                .iter()
+                .filter(|s| s.pid == Some(pid))
+                .map(|s| s.end - s.start)
+                .max()
+                .unwrap()
+        };
+        assert!(longest(&eevdf, 1) <= 3);
+        assert!(eevdf.task(3).unwrap().completion > eevdf.task(1).unwrap().completion);
+    }
+}
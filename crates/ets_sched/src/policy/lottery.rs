@@ -0,0 +1,136 @@
+use crate::{
+    rng::Rng,
+    sched::{Enqueue, RqEntry, Scheduler},
+    task::{Pid, Task, Time},
+};
+
+/// Lottery scheduling: every quantum a ticket is drawn at random and its
+/// holder runs. Tickets are the task's load weight, so nice levels keep their
+/// meaning; shares are only right on average.
+#[derive(Clone)]
+pub struct Lottery {
+    /// Queued tasks and their tickets.
+    queue: Vec<(Pid, u64)>,
+    total: u64,
+    rng: Rng,
+    used: Time,
+    pub quantum: Time,
+}
+
+impl Lottery {
+    pub fn new(seed: u64) -> Lottery {
+        Lottery {
+            queue: Vec::new(),
+            total: 0,
+            rng: Rng::new(seed),
+            used: 0,
+            quantum: 10,
+        }
+    }
+
+    pub fn tickets(&self) -> u64 {
+        self.total
+    }
+}
+
+impl Scheduler for Lottery {
+    fn name(&self) -> &str {
+        "lottery"
+    }
+
+    fn enqueue(&mut self, task: &mut Task, _now: Time, _kind: Enqueue) {
+        self.queue.push((task.pid, task.se.weight));
+        self.total += task.se.weight;
+    }
+
+    fn dequeue(&mut self, task: &mut Task, _now: Time) {
+        if let Some(i) = self.queue.iter().position(|&(pid, _)| pid == task.pid) {
+            self.total -= self.queue.remove(i).1;
+        }
+    }
+
+    fn pick_next(&mut self, _now: Time) -> Option<Pid> {
+        if self.queue.is_empty() {
+            return None;
+        }
+        let mut winner = self.rng.below(self.total);
+        let i = self
+            .queue
+            .iter()
+            .position(|&(_, tickets)| {
+                if winner < tickets {
+                    return true;
+                }
+                winner -= tickets;
+                false
+            })
+            .unwrap();
+        let (pid, tickets) = self.queue.swap_remove(i);
+        self.total -= tickets;
+        self.used = 0;
+        Some(pid)
+    }
+
+    fn tick(&mut self, _curr: &mut Task, delta: Time, _now: Time) -> bool {
+        self.used += delta;
+        self.used >= self.quantum && !self.queue.is_empty()
+    }
+
+    fn timer(&self, curr: Option<&Task>, _now: Time) -> Option<Time> {
+        curr?;
+        (!self.queue.is_empty()).then(|| self.quantum.saturating_sub(self.used).max(1))
+    }
+
+    fn nr_running(&self) -> usize {
+        self.queue.len()
+    }
+
+    fn runqueue(&self, _now: Time) -> Vec<RqEntry> {
+        self.queue
+            .iter()
+            .map(|&(pid, tickets)| RqEntry::new(pid, [("tickets", tickets as i64)]))
+            .collect()
+    }
+
+    /// Draws on a copy of the generator, so it names the ticket the next
+    /// pick will draw.
+    fn pick_reason(&self, _now: Time) -> Option<String> {
+        if self.queue.is_empty() {
+            return None;
+        }
+        let winner = self.rng.clone().below(self.total);
+        Some(format!("drew ticket {winner} of {}", self.total))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        share::{contending_tasks, fair_share},
+        sim::Simulator,
+    };
+
+    fn run(seed: u64, quantum: Time, until: Time) -> Simulator {
+        let mut lottery = Lottery::new(seed);
+        lottery.quantum = quantum;
+        let mut sim = Simulator::new(Box::new(lottery), contending_tasks());
+        sim.run_until(until);
+        sim
+    }
+
+    #[test]
+    fn same_seed_same_run() {
+        assert_eq!(run(42, 10, 500).trace(), run(42, 10, 500).trace());
+        assert_ne!(run(42, 10, 500).trace(), run(43, 10, 500).trace());
+    }
+
+    #[test]
+    fn share_follows_tickets_on_average() {
+        let sim = run(7, 1, 20_000);
+        let expected = fair_share(sim.tasks());
+        for ((pid, share), (_, fair)) in sim.cpu_share().into_iter().zip(expected) {
+            assert!((share - fair).abs() < 0.02, "pid {pid}: {share} vs {fair}");
+        }
+    }
+}
@@ -0,0 +1,437 @@
+use std::{collections::HashMap, collections::VecDeque, fmt, fs, io, path::Path};
+
+use serde::Deserialize;
+
+use crate::{
+    sched::{Enqueue, RqEntry, Scheduler},
+    task::{MlfqLevel, Pid, Task, Time},
+};
+
+/// MLFQ tunables, loadable from TOML:
+///
+/// ```toml
+/// quanta = [10, 20, 40]
+/// boost_interval = 200
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MlfqConfig {
+    /// Quantum in ticks of each level, highest priority first. There are as
+    /// many levels as quanta.
+    pub quanta: Vec<Time>,
+    /// Ticks between moving every task back to the top level; `None` turns
+    /// the boost off and lets CPU-bound tasks starve.
+    pub boost_interval: Option<Time>,
+}
+
+impl Default for MlfqConfig {
+    fn default() -> Self {
+        MlfqConfig {
+            quanta: vec![10, 20, 40],
+            boost_interval: Some(200),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum MlfqConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Invalid(&'static str),
+}
+
+impl fmt::Display for MlfqConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MlfqConfigError::Io(err) => write!(f, "cannot read config: {err}"),
+            MlfqConfigError::Parse(err) => write!(f, "invalid config: {err}"),
+            MlfqConfigError::Invalid(reason) => write!(f, "invalid config: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for MlfqConfigError {}
+
+impl MlfqConfig {
+    pub fn from_toml(text: &str) -> Result<MlfqConfig, MlfqConfigError> {
+        let config: MlfqConfig = toml::from_str(text).map_err(MlfqConfigError::Parse)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<MlfqConfig, MlfqConfigError> {
+        MlfqConfig::from_toml(&fs::read_to_string(path).map_err(MlfqConfigError::Io)?)
+    }
+
+    pub fn validate(&self) -> Result<(), MlfqConfigError> {
+        if self.quanta.is_empty() {
+            return Err(MlfqConfigError::Invalid("at least one level is needed"));
+        }
+        if self.quanta.contains(&0) {
+            return Err(MlfqConfigError::Invalid("quanta must be at least one tick"));
+        }
+        if self.boost_interval == Some(0) {
+            return Err(MlfqConfigError::Invalid(
+                "boost interval must be at least one tick",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Multilevel feedback queue. New tasks start on the top level, using up a
+/// quantum demotes a task one level, and every `boost_interval` all tasks go
+/// back to the top.
+#[derive(Clone)]
+pub struct Mlfq {
+    config: MlfqConfig,
+    queues: Vec<VecDeque<Pid>>,
+    /// Levels of the tasks queued or running here. The task keeps its own
+    /// copy while it is blocked or on another CPU.
+    levels: HashMap<Pid, MlfqLevel>,
+    last_boost: Time,
+    /// Level of the running task.
+    curr: Option<usize>,
+}
+
+impl Default for Mlfq {
+    fn default() -> Self {
+        Mlfq::new(MlfqConfig::default()).expect("the default config is valid")
+    }
+}
+
+impl Mlfq {
+    pub fn new(config: MlfqConfig) -> Result<Mlfq, MlfqConfigError> {
+        config.validate()?;
+        Ok(Mlfq {
+            queues: vec![VecDeque::new(); config.quanta.len()],
+            config,
+            levels: HashMap::new(),
+            last_boost: 0,
+            curr: None,
+        })
+    }
+
+    pub fn config(&self) -> &MlfqConfig {
+        &self.config
+    }
+
+    /// Current level of a task queued or running here, 0 being the top.
+    pub fn level(&self, pid: Pid) -> Option<usize> {
+        self.levels.get(&pid).map(|level| level.level)
+    }
+
+    /// The level a task brings along, or the top one if a boost came since
+    /// it was saved.
+    fn restore(&self, task: &Task) -> MlfqLevel {
+        let state = task.se.mlfq;
+        if state.saved < self.last_boost {
+            MlfqLevel::default()
+        } else {
+            state
+        }
+    }
+
+    /// Hands a task's level back to the task as it leaves this CPU.
+    fn save(&mut self, task: &mut Task, now: Time) {
+        if let Some(state) = self.levels.remove(&task.pid) {
+            task.se.mlfq = MlfqLevel {
+                saved: now,
+                ..state
+            };
+        }
+    }
+
+    fn top_level(&self) -> Option<usize> {
+        self.queues.iter().position(|queue| !queue.is_empty())
+    }
+
+    /// Returns whether a boost happened.
+    fn maybe_boost(&mut self, now: Time) -> bool {
+        let Some(interval) = self.config.boost_interval else {
+            return false;
+        };
+        if now - self.last_boost < interval {
+            return false;
+        }
+        self.last_boost = now;
+        for level in self.levels.values_mut() {
+            *level = MlfqLevel::default();
+        }
+        let (top, rest) = self.queues.split_first_mut().unwrap();
+        for queue in rest {
+            top.extend(queue.drain(..));
+        }
+        if self.curr.is_some() {
+            self.curr = Some(0);
+        }
+        true
+    }
+}
+
+impl Scheduler for Mlfq {
+    fn name(&self) -> &str {
+        "mlfq"
+    }
+
+    fn enqueue(&mut self, task: &mut Task, _now: Time, kind: Enqueue) {
+        if kind == Enqueue::Requeue {
+            self.curr = None;
+        }
+        let restored = self.restore(task);
+        let level = self.levels.entry(task.pid).or_insert(restored).level;
+        self.queues[level].push_back(task.pid);
+    }
+
+    fn dequeue(&mut self, task: &mut Task, now: Time) {
+        if let Some(level) = self.level(task.pid) {
+            self.queues[level].retain(|&pid| pid != task.pid);
+        }
+        self.save(task, now);
+    }
+
+    fn pick_next(&mut self, now: Time) -> Option<Pid> {
+        self.maybe_boost(now);
+        let level = self.top_level()?;
+        self.curr = Some(level);
+        self.queues[level].pop_front()
+    }
+
+    fn tick(&mut self, curr: &mut Task, delta: Time, now: Time) -> bool {
+        let last = self.queues.len() - 1;
+        let quanta = &self.config.quanta;
+        let restored = self.restore(curr);
+        let state = self.levels.entry(curr.pid).or_insert(restored);
+        state.used += delta;
+        let mut resched = false;
+        if state.used >= quanta[state.level] {
+            state.level = (state.level + 1).min(last);
+            state.used = 0;
+            let level = state.level;
+            self.curr = Some(level);
+            // Round-robin with whoever else is on the level it lands on.
+            resched = self.top_level().is_some_and(|top| top <= level);
+        }
+        if self.maybe_boost(now) {
+            resched = self.top_level().is_some();
+        }
+        curr.se.mlfq = MlfqLevel {
+            saved: now,
+            ..self.levels[&curr.pid]
+        };
+        resched
+            || self
+                .top_level()
+                .zip(self.curr)
+                .is_some_and(|(top, curr)| top < curr)
+    }
+
+    /// When the running task uses up its quantum or the next boost, which
+    /// also happens on an idle CPU.
+    fn timer(&self, curr: Option<&Task>, now: Time) -> Option<Time> {
+        let boost = self
+            .config
+            .boost_interval
+            .map(|interval| (self.last_boost + interval).saturating_sub(now).max(1));
+        let quantum = curr.map(|task| {
+            let state = self
+                .levels
+                .get(&task.pid)
+                .copied()
+                .unwrap_or_else(|| self.restore(task));
+            self.config.quanta[state.level]
+                .saturating_sub(state.used)
+                .max(1)
+        });
+        [boost, quantum].into_iter().flatten().min()
+    }
+
+    fn task_dead(&mut self, curr: &mut Task, _now: Time) {
+        self.levels.remove(&curr.pid);
+        self.curr = None;
+    }
+
+    fn block(&mut self, curr: &mut Task, now: Time) {
+        self.save(curr, now);
+        self.curr = None;
+    }
+
+    fn check_preempt(&self, curr: &Task, woken: &Task, _now: Time) -> bool {
+        let level = |pid| self.level(pid).unwrap_or(0);
+        level(woken.pid) < level(curr.pid)
+    }
+
+    fn nr_running(&self) -> usize {
+        self.queues.iter().map(VecDeque::len).sum()
+    }
+
+    fn runqueue(&self, _now: Time) -> Vec<RqEntry> {
+        self.queues
+            .iter()
+            .enumerate()
+            .flat_map(|(level, queue)| {
+                queue.iter().map(move |&pid| {
+                    let used = self.levels.get(&pid).map_or(0, |l| l.used);
+                    RqEntry::new(pid, [("level", level as i64), ("used", used as i64)])
+                })
+            })
+            .collect()
+    }
+
+    fn pick_reason(&self, now: Time) -> Option<String> {
+        let boost = self
+            .config
+            .boost_interval
+            .is_some_and(|interval| now - self.last_boost >= interval);
+        if boost {
+            return Some("priority boost, then first on level 0".into());
+        }
+        let level = self.top_level()?;
+        Some(format!("first on level {level}, the highest non-empty"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::Simulator;
+
+    fn task(pid: Pid, arrival: Time, burst: Time) -> Task {
+        Task::builder()
+            .pid(pid)
+            .arrival(arrival)
+            .burst(burst)
+            .build()
+    }
+
+    fn mlfq(quanta: &[Time], boost_interval: Option<Time>) -> Mlfq {
+        Mlfq::new(MlfqConfig {
+            quanta: quanta.to_vec(),
+            boost_interval,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn short_jobs_jump_ahead() {
+        let tasks = vec![task(1, 0, 100), task(2, 30, 2), task(3, 60, 3)];
+        let mut sim = Simulator::new(Box::new(mlfq(&[2, 4, 8], None)), tasks);
+        sim.run();
+        // The CPU-bound task has sunk to the bottom by the time the short
+        // ones arrive, so they run right away and finish on the top levels.
+        for pid in [2, 3] {
+            let task = sim.task(pid).unwrap();
+            assert_eq!(task.first_run, Some(task.arrival));
+            assert_eq!(task.completion, Some(task.arrival + task.burst));
+        }
+    }
+
+    #[test]
+    fn yielding_does_not_reset_allotment() {
+        let mut tasks = [task(1, 0, 100), task(2, 0, 100)];
+        let mut mlfq = mlfq(&[2, 4], None);
+        for task in &mut tasks {
+            mlfq.enqueue(task, 0, Enqueue::Arrival);
+        }
+        for now in 0..2 {
+            assert_eq!(mlfq.pick_next(now), Some(1));
+            mlfq.tick(&mut tasks[0], 1, now + 1);
+            mlfq.yield_task(&mut tasks[0], now + 1);
+            mlfq.enqueue(&mut tasks[0], now + 1, Enqueue::Requeue);
+            mlfq.dequeue(&mut tasks[1], now + 1);
+            mlfq.enqueue(&mut tasks[1], now + 1, Enqueue::Requeue);
+        }
+        assert_eq!(mlfq.level(1), Some(1));
+        assert_eq!(mlfq.level(2), Some(0));
+    }
+
+    #[test]
+    fn level_moves_with_the_task() {
+        let mut task = task(1, 0, 100);
+        let (mut here, mut there) = (mlfq(&[2, 4, 8], None), mlfq(&[2, 4, 8], None));
+        here.enqueue(&mut task, 0, Enqueue::Arrival);
+        assert_eq!(here.pick_next(0), Some(1));
+        here.tick(&mut task, 2, 2);
+        here.block(&mut task, 2);
+        assert_eq!(here.level(1), None);
+
+        // Woken on another CPU, then pulled back.
+        there.enqueue(&mut task, 9, Enqueue::Wakeup);
+        assert_eq!(there.level(1), Some(1));
+        there.dequeue(&mut task, 10);
+        here.enqueue(&mut task, 10, Enqueue::Migrate);
+        assert_eq!(here.level(1), Some(1));
+
+        // A boost while the task was away still counts.
+        let mut boosting = mlfq(&[2, 4, 8], Some(5));
+        boosting.pick_next(20);
+        boosting.enqueue(&mut task, 20, Enqueue::Migrate);
+        assert_eq!(boosting.level(1), Some(0));
+    }
+
+    #[test]
+    fn demotes_and_boosts() {
+        let tasks = vec![task(1, 0, 100), task(2, 0, 100)];
+        let mut sim = Simulator::new(Box::new(mlfq(&[1, 2, 4], Some(20))), tasks);
+        sim.run_until(24);
+        let trace: Vec<(Pid, Time, Time)> = sim
+            .trace()
+            .iter()
+            .map(|s| (s.pid.unwrap(), s.start, s.end))
+            .collect();
+        assert_eq!(
+            trace,
+            [
+                (1, 0, 1),
+                (2, 1, 2),
+                (1, 2, 4),
+                (2, 4, 6),
+                (1, 6, 10),
+                (2, 10, 14),
+                (1, 14, 18),
+                (2, 18, 20),
+                // Boosted back to the one-tick top level.
+                (1, 20, 21),
+                (2, 21, 22),
+                (1, 22, 24),
+            ]
+        );
+    }
+
+    #[test]
+    fn config_from_toml() {
+        let config = MlfqConfig::from_toml("quanta = [5, 10]\nboost_interval = 100\n").unwrap();
+        assert_eq!(config.quanta, [5, 10]);
+        assert_eq!(config.boost_interval, Some(100));
+        assert_eq!(MlfqConfig::from_toml("").unwrap(), MlfqConfig::default());
+        assert_eq!(
+            MlfqConfig::from_toml("quanta = [5]")
+                .unwrap()
+                .boost_interval,
+            Some(200)
+        );
+
+        assert!(matches!(
+            MlfqConfig::from_toml("quanta = []"),
+            Err(MlfqConfigError::Invalid(_))
+        ));
+        for quanta in [vec![], vec![0]] {
+            let config = MlfqConfig {
+                quanta,
+                boost_interval: None,
+            };
+            assert!(matches!(
+                Mlfq::new(config),
+                Err(MlfqConfigError::Invalid(_))
+            ));
+        }
+        assert!(matches!(
+            MlfqConfig::from_toml("levels = 3"),
+            Err(MlfqConfigError::Parse(_))
+        ));
+        assert!(matches!(
+            MlfqConfig::load("/nonexistent/mlfq.toml"),
+            Err(MlfqConfigError::Io(_))
+        ));
+    }
+}
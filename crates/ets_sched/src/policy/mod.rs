@@ -0,0 +1,18 @@
+mod cfs;
+mod classic;
+mod deadline;
+mod eevdf;
+mod lottery;
+mod mlfq;
+mod rt;
+mod stride;
+mod timeline;
+
+pub use cfs::Cfs;
+pub use classic::{Fcfs, Hrrn, Priority, Sjf, Srtf};
+pub use deadline::{AdmissionError, Deadline, DlMode};
+pub use eevdf::Eevdf;
+pub use lottery::Lottery;
+pub use mlfq::{Mlfq, MlfqConfig, MlfqConfigError};
+pub use rt::Rt;
+pub use stride::{STRIDE1, Stride};
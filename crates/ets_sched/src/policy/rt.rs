@@ -0,0 +1,356 @@
+use std::collections::{HashMap, VecDeque};
+
+use sys_probe::SysProbe;
+
+use crate::{
+    sched::{Enqueue, RqEntry, Scheduler},
+    task::{Pid, Policy, Task, Time},
+};
+
+const MAX_RT_PRIO: usize = 100;
+
+#[derive(Clone, Copy, Debug)]
+struct Running {
+    pid: Pid,
+    /// Ticks left of the RR timeslice.
+    slice_left: Time,
+    /// Whether it goes to the tail of its queue when requeued, after using
+    /// up its slice or yielding, rather than back to the head.
+    to_tail: bool,
+}
+
+/// The real-time class: `SCHED_FIFO` and `SCHED_RR` tasks on 99 priority
+/// levels, always ahead of the normal tasks, which are handed to `fair`.
+#[derive(Clone)]
+pub struct Rt {
+    name: String,
+    queues: Vec<VecDeque<Pid>>,
+    /// Bit `p` is set when the queue for priority `p` is not empty.
+    bitmap: u128,
+    curr: Option<Running>,
+    /// RR slice left to tasks preempted before using it up.
+    slices: HashMap<Pid, Time>,
+    timeslice: Time,
+    fair: Box<dyn Scheduler>,
+}
+
+impl Rt {
+    /// Uses the RR timeslice the running kernel is configured with.
+    pub fn new(fair: Box<dyn Scheduler>) -> Rt {
+        let mut probe = SysProbe::new();
+        probe.set_quantum();
+        Rt::with_timeslice(fair, probe.quantum as Time)
+    }
+
+    pub fn with_timeslice(fair: Box<dyn Scheduler>, timeslice: Time) -> Rt {
+        assert!(timeslice > 0, "timeslice must be at least one tick");
+        Rt {
+            name: format!("rt+{}", fair.name()),
+            queues: vec![VecDeque::new(); MAX_RT_PRIO],
+            bitmap: 0,
+            curr: None,
+            slices: HashMap::new(),
+            timeslice,
+            fair,
+        }
+    }
+
+    pub fn timeslice(&self) -> Time {
+        self.timeslice
+    }
+
+    pub fn fair(&self) -> &dyn Scheduler {
+        self.fair.as_ref()
+    }
+
+    /// Priority of the highest queued real-time task.
+    pub fn top_priority(&self) -> Option<u16> {
+        (self.bitmap != 0).then(|| 127 - self.bitmap.leading_zeros() as u16)
+    }
+
+    fn push(&mut self, prio: u16, pid: Pid, tail: bool) {
+        let queue = &mut self.queues[prio as usize];
+        if tail {
+            queue.push_back(pid);
+        } else {
+            queue.push_front(pid);
+        }
+        self.bitmap |= 1 << prio;
+    }
+
+    fn update_bitmap(&mut self, prio: u16) {
+        if self.queues[prio as usize].is_empty() {
+            self.bitmap &= !(1 << prio);
+        }
+    }
+}
+
+impl Scheduler for Rt {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn enqueue(&mut self, task: &mut Task, now: Time, kind: Enqueue) {
+        if !task.policy.is_rt() {
+            return self.fair.enqueue(task, now, kind);
+        }
+        // A preempted task keeps its place at the head of the queue, and
+        // the rest of its slice.
+        let tail = match kind {
+            Enqueue::Requeue => match self.curr.take() {
+                Some(curr) if !curr.to_tail => {
+                    self.slices.insert(curr.pid, curr.slice_left);
+                    false
+                }
+                _ => true,
+            },
+            _ => true,
+        };
+        self.push(task.rt_priority, task.pid, tail);
+    }
+
+    fn dequeue(&mut self, task: &mut Task, now: Time) {
+        if !task.policy.is_rt() {
+            return self.fair.dequeue(task, now);
+        }
+        let prio = task.rt_priority;
+        self.queues[prio as usize].retain(|&pid| pid != task.pid);
+        self.update_bitmap(prio);
+        self.slices.remove(&task.pid);
+    }
+
+    fn pick_next(&mut self, now: Time) -> Option<Pid> {
+        let Some(prio) = self.top_priority() else {
+            self.curr = None;
+            return self.fair.pick_next(now);
+        };
+        let pid = self.queues[prio as usize].pop_front()?;
+        self.update_bitmap(prio);
+        let slice_left = self.slices.remove(&pid).unwrap_or(self.timeslice);
+        self.curr = Some(Running {
+            pid,
+            slice_left,
+            to_tail: false,
+        });
+        Some(pid)
+    }
+
+    fn tick(&mut self, curr: &mut Task, delta: Time, now: Time) -> bool {
+        if !curr.policy.is_rt() {
+            return self.fair.tick(curr, delta, now) || self.bitmap != 0;
+        }
+        if curr.policy != Policy::Rr {
+            return false;
+        }
+        let timeslice = self.timeslice;
+        let others = !self.queues[curr.rt_priority as usize].is_empty();
+        let running = self.curr.get_or_insert(Running {
+            pid: curr.pid,
+            slice_left: timeslice,
+            to_tail: false,
+        });
+        running.slice_left = running.slice_left.saturating_sub(delta);
+        if running.slice_left > 0 {
+            return false;
+        }
+        // Refill the slice and round-robin if anyone else shares the level.
+        running.slice_left = timeslice;
+        running.to_tail = true;
+        others
+    }
+
+    fn timer(&self, curr: Option<&Task>, now: Time) -> Option<Time> {
+        match curr {
+            Some(task) if task.policy == Policy::Rr => {
+                Some(self.curr.map_or(self.timeslice, |c| c.slice_left).max(1))
+            }
+            Some(task) if task.policy.is_rt() => None,
+            _ => self.fair.timer(curr, now),
+        }
+    }
+
+    fn task_dead(&mut self, curr: &mut Task, now: Time) {
+        if curr.policy.is_rt() {
+            self.curr = None;
+        } else {
+            self.fair.task_dead(curr, now);
+        }
+    }
+
+    fn block(&mut self, curr: &mut Task, now: Time) {
+        if curr.policy.is_rt() {
+            self.curr = None;
+        } else {
+            self.fair.block(curr, now);
+        }
+    }
+
+    fn yield_task(&mut self, curr: &mut Task, now: Time) {
+        match &mut self.curr {
+            Some(running) if curr.policy.is_rt() => running.to_tail = true,
+            _ => self.fair.yield_task(curr, now),
+        }
+    }
+
+    fn check_preempt(&self, curr: &Task, woken: &Task, now: Time) -> bool {
+        match (curr.policy.is_rt(), woken.policy.is_rt()) {
+            (_, true) => !curr.policy.is_rt() || woken.rt_priority > curr.rt_priority,
+            (true, false) => false,
+            (false, false) => self.fair.check_preempt(curr, woken, now),
+        }
+    }
+
+    fn nr_running(&self) -> usize {
+        self.queues.iter().map(VecDeque::len).sum::<usize>() + self.fair.nr_running()
+    }
+
+    /// Real-time tasks from the highest priority down, then the fair ones.
+    fn runqueue(&self, now: Time) -> Vec<RqEntry> {
+        let mut entries: Vec<RqEntry> = (0..MAX_RT_PRIO)
+            .rev()
+            .flat_map(|prio| {
+                self.queues[prio]
+                    .iter()
+                    .map(move |&pid| RqEntry::new(pid, [("rt_priority", prio as i64)]))
+            })
+            .collect();
+        entries.extend(self.fair.runqueue(now));
+        entries
+    }
+
+    fn pick_reason(&self, now: Time) -> Option<String> {
+        match self.top_priority() {
+            Some(prio) => Some(format!("first of the highest rt priority, {prio}")),
+            None => self
+                .fair
+                .pick_reason(now)
+                .map(|reason| format!("no rt task; {}: {reason}", self.fair.name())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        policy::{Cfs, Eevdf},
+        sim::Simulator,
+        task::TICK_NS,
+    };
+
+    fn task(pid: Pid, policy: Policy, prio: u16, arrival: Time, burst: Time) -> Task {
+        Task::builder()
+            .pid(pid)
+            .policy(policy)
+            .rt_priority(prio)
+            .arrival(arrival)
+            .burst(burst)
+            .build()
+    }
+
+    fn order(sim: &Simulator) -> Vec<(Pid, Time, Time)> {
+        sim.trace()
+            .iter()
+            .map(|s| (s.pid.unwrap(), s.start, s.end))
+            .collect()
+    }
+
+    #[test]
+    fn rr_shares_a_level() {
+        let tasks = vec![
+            task(1, Policy::Rr, 10, 0, 5),
+            task(2, Policy::Rr, 10, 0, 3),
+            task(3, Policy::Normal, 0, 0, 2),
+        ];
+        let mut sim = Simulator::new(Box::new(Rt::with_timeslice(Box::new(Cfs::new()), 2)), tasks);
+        sim.run();
+        assert_eq!(
+            order(&sim),
+            [
+                (1, 0, 2),
+                (2, 2, 4),
+                (1, 4, 6),
+                (2, 6, 7),
+                (1, 7, 8),
+                (3, 8, 10)
+            ]
+        );
+    }
+
+    #[test]
+    fn higher_priority_preempts() {
+        let tasks = vec![
+            task(1, Policy::Normal, 0, 0, 10),
+            task(2, Policy::Fifo, 5, 2, 4),
+            task(3, Policy::Fifo, 5, 3, 2),
+            task(4, Policy::Fifo, 50, 4, 1),
+        ];
+        let mut sim = Simulator::new(Box::new(Rt::with_timeslice(Box::new(Cfs::new()), 1)), tasks);
+        sim.run();
+        // FIFO ignores the timeslice; task 2 resumes at the head of its level
+        // after task 4 is done.
+        assert_eq!(
+            order(&sim),
+            [
+                (1, 0, 2),
+                (2, 2, 4),
+                (4, 4, 5),
+                (2, 5, 7),
+                (3, 7, 9),
+                (1, 9, 17)
+            ]
+        );
+    }
+
+    #[test]
+    fn preempted_rr_keeps_the_rest_of_its_slice() {
+        let tasks = vec![
+            task(1, Policy::Rr, 10, 0, 6),
+            task(2, Policy::Rr, 10, 0, 4),
+            task(3, Policy::Fifo, 50, 1, 2),
+        ];
+        let mut sim = Simulator::new(Box::new(Rt::with_timeslice(Box::new(Cfs::new()), 3)), tasks);
+        sim.run();
+        // Task 1 used one tick of its slice before task 3 came, so it runs
+        // two more before task 2's turn, not a fresh three.
+        assert_eq!(
+            order(&sim),
+            [
+                (1, 0, 1),
+                (3, 1, 3),
+                (1, 3, 5),
+                (2, 5, 8),
+                (1, 8, 11),
+                (2, 11, 12)
+            ]
+        );
+    }
+
+    #[test]
+    fn normal_tasks_block_in_the_fair_class() {
+        let mut rt = Rt::with_timeslice(Box::new(Eevdf::new()), 3);
+        let mut tasks = [
+            task(1, Policy::Normal, 0, 0, 10),
+            task(2, Policy::Normal, 0, 0, 10),
+        ];
+        for (task, vruntime) in tasks.iter_mut().zip([10, 20]) {
+            task.se.vruntime = vruntime * TICK_NS;
+            rt.enqueue(task, 0, Enqueue::Requeue);
+        }
+        assert_eq!(rt.pick_next(0), Some(1));
+        rt.tick(&mut tasks[0], 1, 1);
+        rt.block(&mut tasks[0], 1);
+        // EEVDF saw it leave 4.5 ms behind the average of 11 and 20 ms.
+        assert_eq!(tasks[0].se.vlag, 4_500_000);
+    }
+
+    #[test]
+    fn timeslice_defaults_to_probed_quantum() {
+        let mut probe = SysProbe::new();
+        probe.set_quantum();
+        let rt = Rt::new(Box::new(Cfs::new()));
+        assert_eq!(rt.timeslice(), probe.quantum as Time);
+        assert_eq!(rt.name(), "rt+cfs");
+        assert_eq!(rt.top_priority(), None);
+    }
+}
@@ -0,0 +1,190 @@
+use rbtree::CachedRbTree;
+
+use super::timeline;
+use crate::{
+    sched::{Enqueue, RqEntry, Scheduler},
+    task::{Pid, Task, Time},
+};
+
+/// Numerator of the stride; large enough that every weight divides it into
+/// a useful integer.
+pub const STRIDE1: u64 = 1 << 24;
+
+#[derive(Clone, Copy, Debug)]
+struct Running {
+    pass: u64,
+    used: Time,
+}
+
+/// Stride scheduling, the deterministic counterpart of lottery: each task
+/// advances its pass by `STRIDE1 / tickets` per tick run and the lowest pass
+/// runs next. Tickets are the task's load weight.
+#[derive(Clone)]
+pub struct Stride {
+    timeline: CachedRbTree<u64, Pid>,
+    /// Highest the lowest pass on the runqueue has been; newcomers start
+    /// here.
+    global_pass: u64,
+    curr: Option<Running>,
+    pub quantum: Time,
+}
+
+impl Default for Stride {
+    fn default() -> Self {
+        Stride::new()
+    }
+}
+
+impl Stride {
+    pub fn new() -> Stride {
+        Stride {
+            timeline: CachedRbTree::new(),
+            global_pass: 0,
+            curr: None,
+            quantum: 10,
+        }
+    }
+
+    pub fn stride(tickets: u64) -> u64 {
+        STRIDE1 / tickets
+    }
+
+    /// Queued tasks in pass order.
+    pub fn timeline(&self) -> impl Iterator<Item = (u64, Pid)> + '_ {
+        self.timeline.iter().map(|(&pass, &pid)| (pass, pid))
+    }
+
+    fn update_global_pass(&mut self) {
+        let curr = self.curr.map(|c| c.pass);
+        timeline::update_min(&mut self.global_pass, curr, &self.timeline);
+    }
+}
+
+impl Scheduler for Stride {
+    fn name(&self) -> &str {
+        "stride"
+    }
+
+    fn enqueue(&mut self, task: &mut Task, _now: Time, kind: Enqueue) {
+        let pass = match kind {
+            Enqueue::Requeue => self.curr.take().map_or(self.global_pass, |c| c.pass),
+            _ => self.global_pass,
+        };
+        task.se.node = Some(self.timeline.insert_multi(pass, task.pid));
+        self.update_global_pass();
+    }
+
+    fn dequeue(&mut self, task: &mut Task, _now: Time) {
+        if let Some(handle) = task.se.node.take() {
+            self.timeline.remove_handle(handle);
+        }
+    }
+
+    fn pick_next(&mut self, _now: Time) -> Option<Pid> {
+        let (pass, pid) = self.timeline.pop_first()?;
+        self.curr = Some(Running { pass, used: 0 });
+        Some(pid)
+    }
+
+    fn tick(&mut self, curr: &mut Task, delta: Time, _now: Time) -> bool {
+        curr.se.node = None;
+        let quantum = self.quantum;
+        let running = self.curr.get_or_insert(Running {
+            pass: self.global_pass,
+            used: 0,
+        });
+        running.pass += Stride::stride(curr.se.weight) * delta;
+        running.used += delta;
+        let expired = running.used >= quantum;
+        self.update_global_pass();
+        expired && !self.timeline.is_empty()
+    }
+
+    fn timer(&self, curr: Option<&Task>, _now: Time) -> Option<Time> {
+        curr?;
+        let used = self.curr.map_or(0, |running| running.used);
+        (!self.timeline.is_empty()).then(|| self.quantum.saturating_sub(used).max(1))
+    }
+
+    fn task_dead(&mut self, _curr: &mut Task, _now: Time) {
+        self.curr = None;
+    }
+
+    fn nr_running(&self) -> usize {
+        self.timeline.len()
+    }
+
+    fn runqueue(&self, _now: Time) -> Vec<RqEntry> {
+        self.timeline()
+            .map(|(pass, pid)| RqEntry::new(pid, [("pass", pass as i64)]))
+            .collect()
+    }
+
+    fn pick_reason(&self, _now: Time) -> Option<String> {
+        let (pass, _) = self.timeline.first()?;
+        Some(format!("lowest pass, {pass}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        policy::{Cfs, Lottery},
+        share::{contending_tasks, fair_share},
+        sim::Simulator,
+    };
+
+    /// Largest gap, in ticks, between what a task received and its fair
+    /// share at any point of the run.
+    fn worst_error(scheduler: Box<dyn Scheduler>, until: Time) -> f64 {
+        let mut sim = Simulator::new(scheduler, contending_tasks());
+        let fair = fair_share(sim.tasks());
+        let mut worst: f64 = 0.0;
+        while sim.now() < until {
+            sim.step();
+            for (task, (_, share)) in sim.tasks().iter().zip(&fair) {
+                let error = task.se.sum_exec_runtime as f64 - share * sim.now() as f64;
+                worst = worst.max(error.abs());
+            }
+        }
+        worst
+    }
+
+    #[test]
+    fn stride_is_precise() {
+        let mut stride = Stride::new();
+        stride.quantum = 1;
+        assert!(worst_error(Box::new(stride), 5_000) < 2.0);
+    }
+
+    #[test]
+    fn lottery_varies_stride_and_cfs_do_not() {
+        let mut stride = Stride::new();
+        stride.quantum = 1;
+        let mut lottery = Lottery::new(3);
+        lottery.quantum = 1;
+        let stride = worst_error(Box::new(stride), 5_000);
+        let lottery = worst_error(Box::new(lottery), 5_000);
+        let cfs = worst_error(Box::new(Cfs::new()), 5_000);
+        assert!(
+            lottery > 10.0 * stride,
+            "lottery {lottery}, stride {stride}"
+        );
+        assert!(lottery > 5.0 * cfs, "lottery {lottery}, cfs {cfs}");
+    }
+
+    #[test]
+    fn newcomer_starts_at_global_pass() {
+        let mut tasks = contending_tasks();
+        tasks[2].arrival = 100;
+        let mut stride = Stride::new();
+        stride.quantum = 1;
+        let mut sim = Simulator::new(Box::new(stride), tasks);
+        sim.run_until(150);
+        // Task 3 gets about its 14% of the last 50 ticks, not 100 ticks of
+        // catch-up.
+        let ran = sim.task(3).unwrap().se.sum_exec_runtime;
+        assert!(ran <= 8, "{ran}");
+    }
+}
@@ -0,0 +1,38 @@
+//! Bookkeeping shared by the classes that queue tasks on a red-black
+//! timeline ordered by virtual time and take the running one off it.
+
+use rbtree::{Augment, CachedRbTree};
+
+use crate::task::{Pid, Time};
+
+/// Queued task as stored on the timeline.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Entity {
+    pub pid: Pid,
+    pub weight: u64,
+}
+
+/// Bookkeeping for the task that is on the CPU, which is off the timeline.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Running {
+    pub weight: u64,
+    pub vruntime: u64,
+    /// Ticks run since it was picked.
+    pub slice_exec: Time,
+}
+
+/// Moves `min` up to the lowest virtual time on the runqueue, counting the
+/// running task. It never goes back, so sleepers cannot drag it down.
+pub(super) fn update_min<V, A: Augment<u64, V>>(
+    min: &mut u64,
+    curr: Option<u64>,
+    timeline: &CachedRbTree<u64, V, A>,
+) {
+    let leftmost = timeline.first().map(|(&key, _)| key);
+    let lowest = match (curr, leftmost) {
+        (Some(curr), Some(left)) => curr.min(left),
+        (Some(v), None) | (None, Some(v)) => v,
+        (None, None) => return,
+    };
+    *min = (*min).max(lowest);
+}
@@ -0,0 +1,122 @@
+use crate::task::{Pid, Task, Time};
+
+/// Why a task is being put on a runqueue.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Enqueue {
+    /// First time the task becomes runnable.
+    Arrival,
+    /// The task was blocked and is runnable again.
+    Wakeup,
+    /// Like [`Enqueue::Wakeup`], on another CPU than the one it blocked on.
+    WakeupMigrated,
+    /// The task was running and got preempted or yielded.
+    Requeue,
+    /// The task was moved here from another CPU's runqueue, where it was
+    /// dequeued.
+    Migrate,
+}
+
+/// A queued task as its policy sees it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RqEntry {
+    pub pid: Pid,
+    /// What the policy orders or chooses by, e.g. `("vruntime", 1_500_000)`.
+    pub keys: Vec<(&'static str, i64)>,
+}
+
+impl RqEntry {
+    pub fn new(pid: Pid, keys: impl Into<Vec<(&'static str, i64)>>) -> RqEntry {
+        RqEntry {
+            pid,
+            keys: keys.into(),
+        }
+    }
+
+    pub fn key(&self, name: &str) -> Option<i64> {
+        self.keys.iter().find(|&&(n, _)| n == name).map(|&(_, v)| v)
+    }
+}
+
+/// A scheduling policy. The simulator owns the tasks and drives the policy
+/// through these hooks; the policy only keeps its runqueue.
+///
+/// A task handed out by [`Scheduler::pick_next`] leaves the runqueue while it
+/// runs and comes back through [`Scheduler::enqueue`] if it is preempted.
+///
+/// Policies are `Clone` so that a whole simulation can be snapshotted.
+pub trait Scheduler: SchedulerClone {
+    fn name(&self) -> &str;
+
+    fn enqueue(&mut self, task: &mut Task, now: Time, kind: Enqueue);
+
+    /// Removes a queued task that is not running.
+    fn dequeue(&mut self, task: &mut Task, now: Time);
+
+    /// Takes the next task to run off the runqueue.
+    fn pick_next(&mut self, now: Time) -> Option<Pid>;
+
+    /// Charges `delta` ticks of CPU time to the running task. Returns `true`
+    /// when the task should be preempted.
+    fn tick(&mut self, curr: &mut Task, delta: Time, now: Time) -> bool;
+
+    /// Ticks from `now` until the policy next needs [`Scheduler::tick`], with
+    /// `curr` running, or [`Scheduler::pick_next`], on an idle CPU. `None`
+    /// when only a task arriving or waking up can change its mind.
+    ///
+    /// The simulator skips the ticks in between, so the policy must behave
+    /// as if they were charged one by one. The default asks for every tick.
+    fn timer(&self, _curr: Option<&Task>, _now: Time) -> Option<Time> {
+        Some(1)
+    }
+
+    /// Called when the running task finishes; it is not requeued.
+    fn task_dead(&mut self, _curr: &mut Task, _now: Time) {}
+
+    /// Called when the running task goes to sleep; it comes back through
+    /// [`Scheduler::enqueue`] with [`Enqueue::Wakeup`]. By default the same
+    /// as [`Scheduler::task_dead`].
+    fn block(&mut self, curr: &mut Task, now: Time) {
+        self.task_dead(curr, now);
+    }
+
+    /// Called when the running task gives up the CPU voluntarily, right
+    /// before it is requeued.
+    fn yield_task(&mut self, _curr: &mut Task, _now: Time) {}
+
+    /// Whether `woken`, which just became runnable, should preempt `curr`.
+    fn check_preempt(&self, _curr: &Task, _woken: &Task, _now: Time) -> bool {
+        false
+    }
+
+    /// Number of queued tasks, not counting the running one.
+    fn nr_running(&self) -> usize;
+
+    /// The queued tasks, in the order the policy keeps them, with the values
+    /// it schedules by.
+    fn runqueue(&self, _now: Time) -> Vec<RqEntry> {
+        Vec::new()
+    }
+
+    /// Why [`Scheduler::pick_next`] would choose what it chooses, if called
+    /// now.
+    fn pick_reason(&self, _now: Time) -> Option<String> {
+        None
+    }
+}
+
+/// Boxed cloning for [`Scheduler`], implemented for every `Clone` policy.
+pub trait SchedulerClone {
+    fn clone_box(&self) -> Box<dyn Scheduler>;
+}
+
+impl<T: Scheduler + Clone + 'static> SchedulerClone for T {
+    fn clone_box(&self) -> Box<dyn Scheduler> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Scheduler> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
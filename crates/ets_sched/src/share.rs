@@ -0,0 +1,63 @@
+//! CPU share per task, to put a simulated run next to what the live system
+//! reports.
+
+use sys_probe::Process;
+
+use crate::task::{Pid, Task};
+
+/// Share each task gets under a perfectly fair weighted scheduler when all of
+/// them stay runnable.
+pub fn fair_share(tasks: &[Task]) -> Vec<(Pid, f64)> {
+    let total: u64 = tasks.iter().map(|task| task.se.weight).sum();
+    tasks
+        .iter()
+        .map(|task| (task.pid, task.se.weight as f64 / total.max(1) as f64))
+        .collect()
+}
+
+/// Share of the CPU time used by `processes` that went to each of them, as
+/// measured by `sysinfo` between the last two refreshes.
+pub fn measured_share<'a>(processes: impl IntoIterator<Item = &'a Process>) -> Vec<(Pid, f64)> {
+    let usage: Vec<(Pid, f64)> = processes
+        .into_iter()
+        .map(|process| (process.pid, process.cpu_usage as f64))
+        .collect();
+    let total: f64 = usage.iter().map(|(_, cpu)| cpu).sum();
+    usage
+        .into_iter()
+        .map(|(pid, cpu)| (pid, if total > 0.0 { cpu / total } else { 0.0 }))
+        .collect()
+}
+
+/// Two nice-0 tasks and a nice-5 one that stay runnable through any test,
+/// for checking how a policy splits the CPU.
+#[cfg(test)]
+pub(crate) fn contending_tasks() -> Vec<Task> {
+    [(1, 0), (2, 0), (3, 5)]
+        .into_iter()
+        .map(|(pid, nice)| Task::builder().pid(pid).nice(nice).burst(100_000).build())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shares_sum_to_one() {
+        let tasks: Vec<Task> = [0, 0, 5]
+            .into_iter()
+            .zip(1..)
+            .map(|(nice, pid)| Task::builder().pid(pid).nice(nice).burst(1).build())
+            .collect();
+        let shares = fair_share(&tasks);
+        assert!((shares[0].1 - 1024.0 / 2383.0).abs() < 1e-9);
+        assert!((shares.iter().map(|(_, s)| s).sum::<f64>() - 1.0).abs() < 1e-9);
+
+        let mut probe = sys_probe::SysProbe::new();
+        probe.refresh_processes();
+        let measured = measured_share(probe.processes.values());
+        assert_eq!(measured.len(), probe.processes.len());
+        assert!(measured.iter().all(|&(_, s)| (0.0..=1.0).contains(&s)));
+    }
+}
@@ -0,0 +1,1096 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, VecDeque},
+    mem,
+};
+
+use crate::{
+    device::{Device, DeviceId},
+    metrics::Metrics,
+    rng::Rng,
+    sched::{Enqueue, Scheduler},
+    task::{Io, Job, Pid, Policy, Task, TaskState, Time},
+};
+
+/// A stretch of time during which a CPU ran one task, or idled when `pid` is
+/// `None`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Slice {
+    pub cpu: usize,
+    pub pid: Option<Pid>,
+    pub start: Time,
+    pub end: Time,
+}
+
+/// A job of a periodic task that completed after its deadline.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeadlineMiss {
+    pub pid: Pid,
+    pub release: Time,
+    pub deadline: Time,
+    pub completion: Time,
+}
+
+/// Something that happened to a task, logged when
+/// [`Simulator::log_events`] is set.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    pub time: Time,
+    pub cpu: usize,
+    pub pid: Pid,
+    pub kind: EventKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    Arrival,
+    /// Chosen to run, with the policy's reason when it gives one.
+    Pick(Option<String>),
+    /// Put back on the runqueue while still runnable.
+    Preempt,
+    Yield,
+    /// Went to sleep until the given time.
+    Block(Time),
+    /// Queued a request on a device, which serves it by `done`.
+    Request {
+        device: DeviceId,
+        done: Time,
+    },
+    Wakeup,
+    /// Moved here from another CPU.
+    Migrate {
+        from: usize,
+    },
+    Exit,
+}
+
+/// One CPU and its runqueue.
+#[derive(Clone)]
+struct Cpu {
+    scheduler: Box<dyn Scheduler>,
+    current: Option<usize>,
+    last_pid: Option<Pid>,
+    /// Index in the trace of this CPU's latest slice.
+    last_slice: Option<usize>,
+}
+
+/// A device and the requests queued on it.
+#[derive(Clone)]
+struct DeviceQueue {
+    device: Device,
+    rng: Rng,
+    /// Task and completion time of each request, the first one in service.
+    requests: VecDeque<(usize, Time)>,
+}
+
+/// Discrete-event simulator: each step jumps to the next time something
+/// happens, an arrival, a wake-up, a task finishing or starting I/O, a
+/// policy timer or a balancing pass, and charges the CPUs for the ticks in
+/// between. Each CPU has its own instance of the policy; normal tasks are
+/// spread between them by a periodic load balancer.
+#[derive(Clone)]
+pub struct Simulator {
+    cpus: Vec<Cpu>,
+    tasks: Vec<Task>,
+    index: HashMap<Pid, usize>,
+    arrivals: Vec<usize>,
+    next_arrival: usize,
+    /// Blocked tasks by wake-up time.
+    sleepers: BinaryHeap<Reverse<(Time, usize)>>,
+    devices: Vec<DeviceQueue>,
+    /// Device each task waits on and the requests it has left to make after
+    /// the one being served.
+    waiting_on: Vec<Option<(DeviceId, Time)>>,
+    /// When the next job of each periodic task that finished its last one
+    /// is released, one period after that one whether or not it ran late.
+    next_release: Vec<Option<Time>>,
+    /// Ticks each task still spends warming up caches after a migration.
+    cold: Vec<Time>,
+    finished: usize,
+    now: Time,
+    trace: Vec<Slice>,
+    context_switches: usize,
+    migrations: usize,
+    misses: Vec<DeadlineMiss>,
+    events: Vec<Event>,
+    /// Ticks between load balancing passes; 0 disables balancing.
+    pub balance_interval: Time,
+    /// Ticks a task loses after moving to another CPU.
+    pub migration_cost: Time,
+    /// Whether to keep a log of [`Event`]s, off by default.
+    pub log_events: bool,
+    /// Steps one tick at a time instead of from event to event. Both give
+    /// the same run for policies whose [`Scheduler::timer`] is exact.
+    pub fixed_tick: bool,
+}
+
+impl Simulator {
+    pub fn new(scheduler: Box<dyn Scheduler>, tasks: Vec<Task>) -> Simulator {
+        Simulator::smp(vec![scheduler], tasks)
+    }
+
+    /// One CPU per scheduler, at most 64.
+    pub fn smp(schedulers: Vec<Box<dyn Scheduler>>, tasks: Vec<Task>) -> Simulator {
+        let ncpus = schedulers.len();
+        assert!((1..=64).contains(&ncpus), "between 1 and 64 CPUs");
+        let online = u64::MAX >> (64 - ncpus);
+        let mut index = HashMap::new();
+        for (i, task) in tasks.iter().enumerate() {
+            assert!(
+                index.insert(task.pid, i).is_none(),
+                "duplicate pid {}",
+                task.pid
+            );
+            assert!(
+                task.affinity & online != 0,
+                "task {} cannot run on any CPU",
+                task.pid
+            );
+        }
+        let mut arrivals: Vec<usize> = (0..tasks.len()).collect();
+        arrivals.sort_by_key(|&i| (tasks[i].arrival, tasks[i].pid));
+        let cpus = schedulers
+            .into_iter()
+            .map(|scheduler| Cpu {
+                scheduler,
+                current: None,
+                last_pid: None,
+                last_slice: None,
+            })
+            .collect();
+        Simulator {
+            cpus,
+            cold: vec![0; tasks.len()],
+            waiting_on: vec![None; tasks.len()],
+            next_release: vec![None; tasks.len()],
+            finished: 0,
+            tasks,
+            index,
+            arrivals,
+            next_arrival: 0,
+            sleepers: BinaryHeap::new(),
+            devices: Vec::new(),
+            now: 0,
+            trace: Vec::new(),
+            context_switches: 0,
+            migrations: 0,
+            misses: Vec::new(),
+            events: Vec::new(),
+            balance_interval: 4,
+            migration_cost: 1,
+            log_events: false,
+            fixed_tick: false,
+        }
+    }
+
+    /// Adds a device for tasks to wait on. Its service times come from a
+    /// generator seeded with its id, so runs are reproducible.
+    pub fn add_device(&mut self, device: Device) -> DeviceId {
+        let id = self.devices.len();
+        self.devices.push(DeviceQueue {
+            device,
+            rng: Rng::new(id as u64 + 1),
+            requests: VecDeque::new(),
+        });
+        id
+    }
+
+    pub fn devices(&self) -> impl Iterator<Item = &Device> {
+        self.devices.iter().map(|queue| &queue.device)
+    }
+
+    /// Tasks waiting on a device, the one being served first.
+    pub fn device_queue(&self, device: DeviceId) -> Vec<Pid> {
+        self.devices[device]
+            .requests
+            .iter()
+            .map(|&(i, _)| self.tasks[i].pid)
+            .collect()
+    }
+
+    pub fn ncpus(&self) -> usize {
+        self.cpus.len()
+    }
+
+    /// Scheduler of the first CPU.
+    pub fn scheduler(&self) -> &dyn Scheduler {
+        self.scheduler_on(0)
+    }
+
+    pub fn scheduler_on(&self, cpu: usize) -> &dyn Scheduler {
+        self.cpus[cpu].scheduler.as_ref()
+    }
+
+    pub fn now(&self) -> Time {
+        self.now
+    }
+
+    pub fn tasks(&self) -> &[Task] {
+        &self.tasks
+    }
+
+    pub fn task(&self, pid: Pid) -> Option<&Task> {
+        self.index.get(&pid).map(|&i| &self.tasks[i])
+    }
+
+    /// Task running on the first CPU.
+    pub fn current(&self) -> Option<Pid> {
+        self.current_on(0)
+    }
+
+    pub fn current_on(&self, cpu: usize) -> Option<Pid> {
+        self.cpus[cpu].current.map(|i| self.tasks[i].pid)
+    }
+
+    /// Slices of every CPU, ordered by start time.
+    pub fn trace(&self) -> &[Slice] {
+        &self.trace
+    }
+
+    pub fn context_switches(&self) -> usize {
+        self.context_switches
+    }
+
+    pub fn migrations(&self) -> usize {
+        self.migrations
+    }
+
+    pub fn deadline_misses(&self) -> &[DeadlineMiss] {
+        &self.misses
+    }
+
+    /// Events so far, in order, if they are being logged.
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /// A copy with an empty trace and event log, which only grow and would
+    /// make copies of a long run ever more expensive.
+    pub(crate) fn without_history(&mut self) -> Simulator {
+        let trace = mem::take(&mut self.trace);
+        let events = mem::take(&mut self.events);
+        let copy = self.clone();
+        self.trace = trace;
+        self.events = events;
+        copy
+    }
+
+    /// Goes back to `earlier`, a copy from [`Simulator::without_history`]
+    /// taken when the trace and event log had the given lengths, and cuts
+    /// them back to match.
+    pub(crate) fn rewind(&mut self, earlier: &Simulator, trace_len: usize, events_len: usize) {
+        let mut trace = mem::take(&mut self.trace);
+        let mut events = mem::take(&mut self.events);
+        *self = earlier.clone();
+        trace.truncate(trace_len);
+        events.truncate(events_len);
+        // The latest slice of each CPU may have been extended since.
+        for cpu in &self.cpus {
+            if let Some(j) = cpu.last_slice {
+                trace[j].end = trace[j].end.min(self.now);
+            }
+        }
+        self.trace = trace;
+        self.events = events;
+    }
+
+    pub fn metrics(&self) -> Metrics {
+        Metrics::new(self)
+    }
+
+    /// Fraction of the elapsed time each task spent on a CPU, in task order.
+    pub fn cpu_share(&self) -> Vec<(Pid, f64)> {
+        let elapsed = self.now.max(1) as f64;
+        self.tasks
+            .iter()
+            .map(|task| (task.pid, task.se.sum_exec_runtime as f64 / elapsed))
+            .collect()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.finished == self.tasks.len()
+    }
+
+    /// Runs until every task has finished.
+    pub fn run(&mut self) {
+        while !self.is_done() {
+            self.step();
+        }
+    }
+
+    pub fn run_until(&mut self, time: Time) {
+        while self.now < time && !self.is_done() {
+            self.step_until(time);
+        }
+    }
+
+    /// Advances the clock to the next event, or by one tick in fixed-tick
+    /// mode.
+    pub fn step(&mut self) {
+        self.step_until(Time::MAX);
+    }
+
+    /// Like [`Simulator::step`], but stops at `limit` if the next event comes
+    /// later.
+    pub fn step_until(&mut self, limit: Time) {
+        if self.is_done() {
+            return;
+        }
+        self.wake_sleepers();
+        self.admit_arrivals();
+        if self.balance_interval > 0 && self.now.is_multiple_of(self.balance_interval) {
+            self.load_balance();
+        }
+        for cpu in 0..self.cpus.len() {
+            if self.cpus[cpu].current.is_none() {
+                if self.cpus[cpu].scheduler.nr_running() == 0 {
+                    self.pull_task(cpu);
+                }
+                self.pick_next(cpu);
+            }
+        }
+
+        let start = self.now;
+        self.now = self.next_event().min(limit).max(start + 1);
+        for cpu in 0..self.cpus.len() {
+            let pid = self.cpus[cpu].current.map(|i| self.tasks[i].pid);
+            self.record(cpu, pid, start, self.now);
+            if let Some(i) = self.cpus[cpu].current {
+                self.run_for(cpu, i, self.now - start);
+            }
+        }
+    }
+
+    /// Time of the next event after the current one.
+    fn next_event(&self) -> Time {
+        if self.fixed_tick {
+            return self.now + 1;
+        }
+        let arrival = self
+            .arrivals
+            .get(self.next_arrival)
+            .map(|&i| self.tasks[i].arrival);
+        let wakeup = self.sleepers.peek().map(|&Reverse((at, _))| at);
+        // Loads only change at events, so a balancing pass that would find
+        // nothing to do now will not later either.
+        let balance = (self.balance_interval > 0 && self.imbalanced())
+            .then(|| (self.now / self.balance_interval + 1) * self.balance_interval);
+        let cpus = self.cpus.iter().flat_map(|rq| {
+            let curr = rq.current.map(|i| &self.tasks[i]);
+            let timer = rq.scheduler.timer(curr, self.now);
+            let run = rq.current.map(|i| self.run_length(i));
+            [timer, run]
+                .into_iter()
+                .flatten()
+                .map(|ticks| self.now + ticks)
+        });
+        [arrival, wakeup, balance]
+            .into_iter()
+            .flatten()
+            .chain(cpus)
+            .min()
+            .unwrap_or(Time::MAX)
+    }
+
+    /// Ticks task `i` can run before it finishes, ends a job or starts I/O.
+    fn run_length(&self, i: usize) -> Time {
+        let task = &self.tasks[i];
+        let work = [
+            Some(task.remaining),
+            task.job.map(|job| job.left),
+            task.cpu_until_io(),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+        .unwrap();
+        self.cold[i] + work
+    }
+
+    /// Makes the task on `cpu` give up the CPU.
+    pub fn yield_current(&mut self, cpu: usize) {
+        if let Some(i) = self.cpus[cpu].current {
+            let rq = &mut self.cpus[cpu];
+            rq.scheduler.yield_task(&mut self.tasks[i], self.now);
+            self.requeue_current(cpu, EventKind::Yield);
+        }
+    }
+
+    /// Charges `delta` ticks, which end no later than the task's next event,
+    /// to the task running on `cpu`.
+    fn run_for(&mut self, cpu: usize, i: usize, delta: Time) {
+        let task = &mut self.tasks[i];
+        task.se.sum_exec_runtime += delta;
+        let cold = self.cold[i].min(delta);
+        self.cold[i] -= cold;
+        let work = delta - cold;
+        let mut job_done = false;
+        let mut io = None;
+        if work > 0 {
+            task.remaining -= work;
+            job_done = task.job.as_mut().is_some_and(|job| {
+                job.left -= work;
+                job.left == 0
+            });
+            if !task.bursts.is_empty() {
+                io = task.io_after(task.burst - task.remaining);
+            }
+        }
+        let resched = self.cpus[cpu].scheduler.tick(task, delta, self.now);
+        if job_done {
+            self.complete_job(i);
+        }
+        let task = &mut self.tasks[i];
+        if task.remaining == 0 {
+            task.state = TaskState::Finished;
+            task.completion = Some(self.now);
+            self.finished += 1;
+            self.cpus[cpu].scheduler.task_dead(task, self.now);
+            self.cpus[cpu].current = None;
+            self.log(cpu, i, EventKind::Exit);
+        } else if job_done {
+            // A late task gets its next job right away, but with the
+            // release and deadline it would have had on time.
+            let release = task.job.unwrap().release + task.dl.unwrap().period;
+            self.next_release[i] = Some(release);
+            self.sleep_current(cpu, release);
+        } else if let Some(Io::Sleep(ticks)) = io {
+            self.sleep_current(cpu, self.now + ticks);
+        } else if let Some(Io::Device(device, requests)) = io {
+            self.waiting_on[i] = Some((device, requests - 1));
+            let done = self.submit(device, i);
+            self.sleep_current(cpu, done);
+        } else if resched {
+            self.requeue_current(cpu, EventKind::Preempt);
+        }
+    }
+
+    /// Blocks the task running on `cpu` until `until`, or just past the
+    /// current tick if that has already gone by.
+    fn sleep_current(&mut self, cpu: usize, until: Time) {
+        if let Some(i) = self.cpus[cpu].current.take() {
+            let task = &mut self.tasks[i];
+            task.state = TaskState::Blocked;
+            self.cpus[cpu].scheduler.block(task, self.now);
+            let until = until.max(self.now);
+            self.sleepers.push(Reverse((until, i)));
+            self.log(cpu, i, EventKind::Block(until));
+        }
+    }
+
+    /// Queues a request from task `i` on `device` and returns when it will
+    /// be served, after every request ahead of it.
+    fn submit(&mut self, device: DeviceId, i: usize) -> Time {
+        let pid = self.tasks[i].pid;
+        let queue = self
+            .devices
+            .get_mut(device)
+            .unwrap_or_else(|| panic!("task {pid} waits on device {device}, which was not added"));
+        let start = queue
+            .requests
+            .back()
+            .map_or(self.now, |&(_, done)| done.max(self.now));
+        let done = start + queue.device.service.sample(&mut queue.rng);
+        queue.requests.push_back((i, done));
+        self.log(self.tasks[i].cpu, i, EventKind::Request { device, done });
+        done
+    }
+
+    fn complete_job(&mut self, i: usize) {
+        let task = &self.tasks[i];
+        let job = task.job.unwrap();
+        if self.now > job.deadline {
+            self.misses.push(DeadlineMiss {
+                pid: task.pid,
+                release: job.release,
+                deadline: job.deadline,
+                completion: self.now,
+            });
+        }
+    }
+
+    fn wake_sleepers(&mut self) {
+        while let Some(&Reverse((at, i))) = self.sleepers.peek() {
+            if at > self.now {
+                break;
+            }
+            self.sleepers.pop();
+            if let Some((device, left)) = self.waiting_on[i] {
+                self.devices[device].requests.pop_front();
+                if left > 0 {
+                    self.waiting_on[i] = Some((device, left - 1));
+                    let done = self.submit(device, i);
+                    self.sleepers.push(Reverse((done, i)));
+                    continue;
+                }
+                self.waiting_on[i] = None;
+            }
+            let release = self.next_release[i].take().unwrap_or(at);
+            self.release_job(i, release);
+            let prev = self.tasks[i].cpu;
+            let cpu = if self.tasks[i].policy == Policy::Normal {
+                self.select_cpu(i, Some(prev))
+            } else {
+                prev
+            };
+            let kind = if cpu != prev {
+                self.note_migration(i, cpu);
+                Enqueue::WakeupMigrated
+            } else {
+                Enqueue::Wakeup
+            };
+            self.log(cpu, i, EventKind::Wakeup);
+            self.make_ready(cpu, i, kind);
+        }
+    }
+
+    fn admit_arrivals(&mut self) {
+        while let Some(&i) = self.arrivals.get(self.next_arrival) {
+            if self.tasks[i].arrival > self.now {
+                break;
+            }
+            self.next_arrival += 1;
+            self.release_job(i, self.tasks[i].arrival);
+            let cpu = self.select_cpu(i, None);
+            self.tasks[i].cpu = cpu;
+            self.log(cpu, i, EventKind::Arrival);
+            self.make_ready(cpu, i, Enqueue::Arrival);
+        }
+    }
+
+    /// Starts the next job of a periodic task.
+    fn release_job(&mut self, i: usize, release: Time) {
+        let task = &mut self.tasks[i];
+        if let Some(dl) = task.dl {
+            task.job = Some(Job {
+                release,
+                deadline: release + dl.deadline,
+                left: task.burst.min(task.remaining),
+            });
+        }
+    }
+
+    fn make_ready(&mut self, cpu: usize, i: usize, kind: Enqueue) {
+        let task = &mut self.tasks[i];
+        // A migrating task keeps waiting from when it was first queued.
+        if kind != Enqueue::Migrate {
+            task.se.wait_start = self.now;
+        }
+        task.state = TaskState::Ready;
+        let rq = &mut self.cpus[cpu];
+        rq.scheduler.enqueue(task, self.now, kind);
+        if let Some(curr) = rq.current
+            && rq
+                .scheduler
+                .check_preempt(&self.tasks[curr], &self.tasks[i], self.now)
+        {
+            self.requeue_current(cpu, EventKind::Preempt);
+        }
+    }
+
+    fn pick_next(&mut self, cpu: usize) {
+        let rq = &mut self.cpus[cpu];
+        let reason = self
+            .log_events
+            .then(|| rq.scheduler.pick_reason(self.now))
+            .flatten();
+        let Some(pid) = rq.scheduler.pick_next(self.now) else {
+            return;
+        };
+        let i = self.index[&pid];
+        let task = &mut self.tasks[i];
+        task.state = TaskState::Running;
+        task.first_run.get_or_insert(self.now);
+        task.se.wait_sum += self.now - task.se.wait_start;
+        if rq.last_pid.is_some_and(|last| last != pid) {
+            self.context_switches += 1;
+        }
+        rq.last_pid = Some(pid);
+        rq.current = Some(i);
+        self.log(cpu, i, EventKind::Pick(reason));
+    }
+
+    /// Puts the running task back on the runqueue, logged as `kind`.
+    fn requeue_current(&mut self, cpu: usize, kind: EventKind) {
+        let rq = &mut self.cpus[cpu];
+        if let Some(i) = rq.current.take() {
+            let task = &mut self.tasks[i];
+            task.state = TaskState::Ready;
+            task.se.wait_start = self.now;
+            rq.scheduler.enqueue(task, self.now, Enqueue::Requeue);
+            self.log(cpu, i, kind);
+        }
+    }
+
+    /// Runnable tasks on a CPU, counting the running one.
+    fn load(&self, cpu: usize) -> usize {
+        let rq = &self.cpus[cpu];
+        rq.scheduler.nr_running() + rq.current.is_some() as usize
+    }
+
+    /// The previous CPU if it is idle, otherwise the least loaded allowed
+    /// one.
+    fn select_cpu(&self, i: usize, prev: Option<usize>) -> usize {
+        let affinity = self.tasks[i].affinity;
+        let allowed = |cpu: &usize| affinity & (1 << cpu) != 0;
+        if let Some(prev) = prev
+            && allowed(&prev)
+            && self.load(prev) == 0
+        {
+            return prev;
+        }
+        (0..self.cpus.len())
+            .filter(allowed)
+            .min_by_key(|&cpu| self.load(cpu))
+            .unwrap()
+    }
+
+    /// Whether a balancing pass would find CPUs to even out.
+    fn imbalanced(&self) -> bool {
+        let loads = (0..self.cpus.len()).map(|cpu| self.load(cpu));
+        let (min, max) = loads.fold((usize::MAX, 0), |(min, max), load| {
+            (min.min(load), max.max(load))
+        });
+        max >= min + 2
+    }
+
+    /// A queued normal task on `src` that may run on `dst`.
+    fn movable(&self, src: usize, dst: usize) -> Option<usize> {
+        self.tasks.iter().position(|task| {
+            task.cpu == src
+                && task.state == TaskState::Ready
+                && task.policy == Policy::Normal
+                && task.affinity & (1 << dst) != 0
+        })
+    }
+
+    fn migrate(&mut self, i: usize, src: usize, dst: usize) {
+        let task = &mut self.tasks[i];
+        self.cpus[src].scheduler.dequeue(task, self.now);
+        self.note_migration(i, dst);
+        self.make_ready(dst, i, Enqueue::Migrate);
+    }
+
+    fn note_migration(&mut self, i: usize, dst: usize) {
+        let task = &mut self.tasks[i];
+        let from = task.cpu;
+        task.cpu = dst;
+        task.se.nr_migrations += 1;
+        self.migrations += 1;
+        self.cold[i] += self.migration_cost;
+        self.log(dst, i, EventKind::Migrate { from });
+    }
+
+    /// Moves tasks from the busiest CPU to the idlest until no two CPUs
+    /// differ by more than one task, or nothing can move.
+    fn load_balance(&mut self) {
+        loop {
+            let by_load = |cpu: &usize| self.load(*cpu);
+            let cpus = 0..self.cpus.len();
+            let busiest = cpus.clone().max_by_key(by_load).unwrap();
+            let idlest = cpus.min_by_key(by_load).unwrap();
+            if self.load(busiest) < self.load(idlest) + 2 {
+                return;
+            }
+            let Some(i) = self.movable(busiest, idlest) else {
+                return;
+            };
+            self.migrate(i, busiest, idlest);
+        }
+    }
+
+    /// Idle balancing: an empty CPU pulls a waiting task from the busiest
+    /// CPU that is also running one.
+    fn pull_task(&mut self, cpu: usize) {
+        let source = (0..self.cpus.len())
+            .filter(|&src| src != cpu && self.load(src) > 1)
+            .filter_map(|src| Some((src, self.movable(src, cpu)?)))
+            .max_by_key(|&(src, _)| self.load(src));
+        if let Some((src, i)) = source {
+            self.migrate(i, src, cpu);
+        }
+    }
+
+    fn log(&mut self, cpu: usize, i: usize, kind: EventKind) {
+        if self.log_events {
+            self.events.push(Event {
+                time: self.now,
+                cpu,
+                pid: self.tasks[i].pid,
+                kind,
+            });
+        }
+    }
+
+    fn record(&mut self, cpu: usize, pid: Option<Pid>, start: Time, end: Time) {
+        let rq = &mut self.cpus[cpu];
+        if let Some(last) = rq.last_slice.map(|j| &mut self.trace[j])
+            && last.pid == pid
+            && last.end == start
+        {
+            last.end = end;
+            return;
+        }
+        rq.last_slice = Some(self.trace.len());
+        self.trace.push(Slice {
+            cpu,
+            pid,
+            start,
+            end,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        device::ServiceTime,
+        policy::{
+            Cfs, Deadline, DlMode, Eevdf, Fcfs, Hrrn, Lottery, Mlfq, MlfqConfig, Priority, Rt, Sjf,
+            Srtf, Stride,
+        },
+        task::DlParams,
+    };
+
+    /// Run-to-completion FIFO with an optional round-robin quantum.
+    #[derive(Clone)]
+    struct Fifo {
+        queue: VecDeque<Pid>,
+        quantum: Option<Time>,
+        used: Time,
+    }
+
+    impl Scheduler for Fifo {
+        fn name(&self) -> &str {
+            "fifo"
+        }
+
+        fn enqueue(&mut self, task: &mut Task, _now: Time, _kind: Enqueue) {
+            self.queue.push_back(task.pid);
+        }
+
+        fn dequeue(&mut self, task: &mut Task, _now: Time) {
+            self.queue.retain(|&pid| pid != task.pid);
+        }
+
+        fn pick_next(&mut self, _now: Time) -> Option<Pid> {
+            self.used = 0;
+            self.queue.pop_front()
+        }
+
+        fn tick(&mut self, _curr: &mut Task, delta: Time, _now: Time) -> bool {
+            self.used += delta;
+            self.quantum.is_some_and(|q| self.used >= q)
+        }
+
+        fn timer(&self, _curr: Option<&Task>, _now: Time) -> Option<Time> {
+            self.quantum.map(|q| q.saturating_sub(self.used).max(1))
+        }
+
+        fn nr_running(&self) -> usize {
+            self.queue.len()
+        }
+    }
+
+    fn task(pid: Pid, arrival: Time, burst: Time) -> Task {
+        Task::builder()
+            .pid(pid)
+            .arrival(arrival)
+            .burst(burst)
+            .build()
+    }
+
+    fn fifo(quantum: Option<Time>) -> Box<dyn Scheduler> {
+        Box::new(Fifo {
+            queue: VecDeque::new(),
+            quantum,
+            used: 0,
+        })
+    }
+
+    #[test]
+    fn runs_tasks_to_completion() {
+        let tasks = vec![task(1, 0, 3), task(2, 1, 2), task(3, 8, 1)];
+        let mut sim = Simulator::new(fifo(None), tasks);
+        sim.run();
+
+        let slices: Vec<(Option<Pid>, Time, Time)> = sim
+            .trace()
+            .iter()
+            .map(|s| (s.pid, s.start, s.end))
+            .collect();
+        assert_eq!(
+            slices,
+            [
+                (Some(1), 0, 3),
+                (Some(2), 3, 5),
+                (None, 5, 8),
+                (Some(3), 8, 9)
+            ]
+        );
+        assert_eq!(sim.task(2).unwrap().first_run, Some(3));
+        assert_eq!(sim.task(3).unwrap().completion, Some(9));
+        assert_eq!(sim.context_switches(), 2);
+    }
+
+    #[test]
+    fn tick_can_preempt() {
+        let tasks = vec![task(1, 0, 4), task(2, 0, 2)];
+        let mut sim = Simulator::new(fifo(Some(2)), tasks);
+        sim.run();
+
+        let order: Vec<Option<Pid>> = sim.trace().iter().map(|s| s.pid).collect();
+        assert_eq!(order, [Some(1), Some(2), Some(1)]);
+        assert_eq!(sim.task(1).unwrap().completion, Some(6));
+        assert_eq!(sim.task(1).unwrap().se.sum_exec_runtime, 4);
+    }
+
+    #[test]
+    fn io_bursts_block_the_task() {
+        let tasks = vec![
+            Task::builder().pid(1).bursts([2, 3, 2]).build(),
+            task(2, 0, 2),
+        ];
+        let mut sim = Simulator::new(fifo(None), tasks);
+        sim.run();
+
+        let slices: Vec<(Option<Pid>, Time, Time)> = sim
+            .trace()
+            .iter()
+            .map(|s| (s.pid, s.start, s.end))
+            .collect();
+        assert_eq!(
+            slices,
+            [
+                (Some(1), 0, 2),
+                (Some(2), 2, 4),
+                (None, 4, 5),
+                (Some(1), 5, 7)
+            ]
+        );
+    }
+
+    #[test]
+    fn device_requests_wait_their_turn() {
+        let on_disk = |pid, requests| {
+            Task::builder()
+                .pid(pid)
+                .bursts([2, requests, 1])
+                .devices([Some(0)])
+                .build()
+        };
+        let tasks = vec![on_disk(1, 2), on_disk(2, 1)];
+        let mut sim = Simulator::smp(vec![fifo(None), fifo(None)], tasks);
+        sim.log_events = true;
+        sim.add_device(Device::new("disk", ServiceTime::Fixed(5)));
+        sim.run_until(3);
+        assert_eq!(sim.device_queue(0), [1, 2]);
+        sim.run();
+
+        // Task 1's second request queues behind task 2's.
+        let requests: Vec<(Time, Pid, Time)> = sim
+            .events()
+            .iter()
+            .filter_map(|e| match e.kind {
+                EventKind::Request { device: 0, done } => Some((e.time, e.pid, done)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(requests, [(2, 1, 7), (2, 2, 12), (7, 1, 17)]);
+        assert_eq!(sim.task(2).unwrap().completion, Some(13));
+        assert_eq!(sim.task(1).unwrap().completion, Some(18));
+        assert!(sim.device_queue(0).is_empty());
+    }
+
+    #[test]
+    fn waking_from_a_device_keeps_tasks_interactive() {
+        // Someone typing into an editor while a long job runs.
+        let mut bursts = vec![1; 21];
+        bursts[0] = 2;
+        let tasks = vec![
+            task(1, 0, 3000),
+            Task::builder()
+                .pid(2)
+                .bursts(bursts)
+                .devices([Some(0); 10])
+                .build(),
+        ];
+        let waiting = |scheduler: Box<dyn Scheduler>| {
+            let mut sim = Simulator::new(scheduler, tasks.clone());
+            sim.add_device(Device::keyboard());
+            sim.run();
+            sim.task(2).unwrap().se.wait_sum
+        };
+        // Enough allotment on the upper levels for the editor's 12 ticks.
+        let mlfq = MlfqConfig {
+            quanta: vec![5, 10, 20],
+            boost_interval: None,
+        };
+        // The editor only runs once the job is done, or right away.
+        assert!(waiting(Box::new(Fcfs::new())) > 2000);
+        assert!(waiting(Box::<Cfs>::default()) < 10);
+        assert!(waiting(Box::new(Mlfq::new(mlfq).unwrap())) < 10);
+    }
+
+    #[test]
+    fn idle_cpu_pulls_and_pays_migration() {
+        let tasks = vec![task(1, 0, 20), task(2, 0, 10), task(3, 0, 10)];
+        let mut sim = Simulator::smp(vec![fifo(None), fifo(None)], tasks);
+        sim.migration_cost = 3;
+        sim.run();
+
+        // Task 3 queues behind task 1 and moves once CPU 1 goes idle.
+        let task = sim.task(3).unwrap();
+        assert_eq!(task.cpu, 1);
+        assert_eq!(task.se.nr_migrations, 1);
+        assert_eq!(task.se.sum_exec_runtime, 13);
+        assert_eq!(task.completion, Some(23));
+        assert_eq!(sim.migrations(), 1);
+    }
+
+    #[test]
+    fn yield_gives_up_one_cpu() {
+        let tasks = (1..=4).map(|pid| task(pid, 0, 10)).collect();
+        let mut sim = Simulator::smp(vec![fifo(None), fifo(None)], tasks);
+        sim.balance_interval = 0;
+        sim.run_until(2);
+        assert_eq!((sim.current_on(0), sim.current_on(1)), (Some(1), Some(2)));
+
+        sim.yield_current(1);
+        sim.run();
+        let starts = |pid| {
+            sim.trace()
+                .iter()
+                .filter(|s| s.pid == Some(pid))
+                .map(|s| (s.cpu, s.start))
+                .collect::<Vec<_>>()
+        };
+        // Task 1 keeps CPU 0; task 4 takes over CPU 1.
+        assert_eq!(starts(1), [(0, 0)]);
+        assert_eq!(starts(4), [(1, 2)]);
+    }
+
+    #[test]
+    fn balancing_spreads_work_within_affinity() {
+        // Arrival placement alternates CPUs, leaving the long tasks on CPU 0.
+        let mut tasks: Vec<Task> = (1..=6)
+            .map(|pid| task(pid, 0, if pid % 2 == 1 { 60 } else { 10 }))
+            .collect();
+        tasks[0].affinity = 0b01;
+        let schedulers = (0..2).map(|_| fifo(Some(5))).collect();
+        let mut sim = Simulator::smp(schedulers, tasks);
+        sim.run();
+
+        assert!(
+            sim.trace()
+                .iter()
+                .filter(|s| s.pid == Some(1))
+                .all(|s| s.cpu == 0)
+        );
+        assert!(sim.migrations() > 0);
+        // 210 ticks of work over two CPUs, instead of 180 on CPU 0 alone.
+        let end = sim.now();
+        assert!(end <= 110, "{end}");
+    }
+
+    #[test]
+    fn events_and_fixed_ticks_give_the_same_run() {
+        let tasks = vec![
+            task(1, 0, 40),
+            Task::builder()
+                .pid(2)
+                .arrival(3)
+                .nice(5)
+                .bursts([6, 9, 4, 2, 7])
+                .devices([None, Some(0)])
+                .build(),
+            Task::builder().pid(3).arrival(5).nice(-3).burst(25).build(),
+            Task::builder()
+                .pid(4)
+                .arrival(8)
+                .policy(Policy::Rr)
+                .rt_priority(10)
+                .bursts([5, 3, 5])
+                .devices([Some(0)])
+                .build(),
+            Task::builder()
+                .pid(5)
+                .arrival(2)
+                .sched_deadline(DlParams::implicit(2, 10))
+                .jobs(4)
+                .build(),
+        ];
+        let policies: [fn() -> Box<dyn Scheduler>; 13] = [
+            || Box::new(Cfs::new()),
+            || Box::new(Eevdf::new()),
+            || Box::new(Rt::with_timeslice(Box::new(Cfs::new()), 3)),
+            || {
+                let rt = Rt::with_timeslice(Box::new(Eevdf::new()), 3);
+                Box::new(Deadline::new(DlMode::Cbs, Box::new(rt)))
+            },
+            || Box::new(Fcfs::new()),
+            || Box::new(Sjf::new()),
+            || Box::new(Srtf::new()),
+            || Box::new(Hrrn::new()),
+            || {
+                let mut priority = Priority::new();
+                priority.preemptive = true;
+                priority.aging = Some(4);
+                Box::new(priority)
+            },
+            || {
+                Box::new(
+                    Mlfq::new(MlfqConfig {
+                        quanta: vec![2, 4, 8],
+                        boost_interval: Some(30),
+                    })
+                    .unwrap(),
+                )
+            },
+            || Box::new(Lottery::new(7)),
+            || Box::new(Stride::new()),
+            || fifo(Some(3)),
+        ];
+        for build in policies {
+            for ncpus in [1, 2] {
+                let run = |fixed_tick| {
+                    let schedulers = (0..ncpus).map(|_| build()).collect();
+                    let mut sim = Simulator::smp(schedulers, tasks.clone());
+                    sim.add_device(Device::disk());
+                    sim.fixed_tick = fixed_tick;
+                    sim.log_events = true;
+                    sim.run();
+                    sim
+                };
+                let (ticks, events) = (run(true), run(false));
+                let name = format!("{} on {ncpus} CPU(s)", ticks.scheduler().name());
+                assert_eq!(events.trace(), ticks.trace(), "{name}");
+                assert_eq!(events.events(), ticks.events(), "{name}");
+                assert_eq!(events.metrics(), ticks.metrics(), "{name}");
+            }
+        }
+    }
+
+    #[test]
+    fn long_runs_take_few_steps() {
+        // Two hours of CPU-bound work.
+        let tasks = vec![task(1, 0, 3_600_000), task(2, 60_000, 3_600_000)];
+        let mut sim = Simulator::new(Box::new(Fcfs::new()), tasks);
+        let mut steps = 0;
+        while !sim.is_done() {
+            sim.step();
+            steps += 1;
+        }
+        assert_eq!(steps, 3);
+        assert_eq!(sim.now(), 7_200_000);
+        assert_eq!(sim.task(2).unwrap().se.wait_sum, 3_540_000);
+
+        // Stepping a finished run leaves it as it is.
+        let slices = sim.trace().len();
+        sim.step();
+        sim.step();
+        assert_eq!(sim.now(), 7_200_000);
+        assert_eq!(sim.trace().len(), slices);
+    }
+}
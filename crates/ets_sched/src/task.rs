@@ -0,0 +1,540 @@
+use serde::Deserialize;
+use sys_probe::Process;
+
+use crate::device::DeviceId;
+
+pub type Pid = u32;
+
+/// Simulated time, in ticks of one millisecond.
+pub type Time = u64;
+
+pub const TICK_NS: u64 = 1_000_000;
+
+/// Load weight of a nice-0 task.
+pub const NICE_0_LOAD: u64 = 1024;
+
+/// Scales `delta` nanoseconds of runtime by the inverse of `weight`, giving
+/// the virtual runtime the fair policies charge.
+pub(crate) fn calc_delta_fair(delta: u64, weight: u64) -> u64 {
+    if weight == NICE_0_LOAD {
+        delta
+    } else {
+        delta * NICE_0_LOAD / weight
+    }
+}
+
+/// Burst given to tasks seeded from a live process, whose CPU demand is
+/// unknown.
+pub const DEFAULT_BURST: Time = 100;
+
+/// The kernel's `sched_prio_to_weight` table: each nice level is worth about
+/// 10% of CPU time relative to its neighbour.
+pub const SCHED_PRIO_TO_WEIGHT: [u64; 40] = [
+    /* -20 */ 88761, 71755, 56483, 46273, 36291, //
+    /* -15 */ 29154, 23254, 18705, 14949, 11916, //
+    /* -10 */ 9548, 7620, 6100, 4904, 3906, //
+    /*  -5 */ 3121, 2501, 1991, 1586, 1277, //
+    /*   0 */ 1024, 820, 655, 526, 423, //
+    /*   5 */ 335, 272, 215, 172, 137, //
+    /*  10 */ 110, 87, 70, 56, 45, //
+    /*  15 */ 36, 29, 23, 18, 15, //
+];
+
+pub fn nice_to_weight(nice: i16) -> u64 {
+    SCHED_PRIO_TO_WEIGHT[(nice.clamp(-20, 19) + 20) as usize]
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TaskState {
+    New,
+    Ready,
+    Running,
+    Blocked,
+    Finished,
+}
+
+/// Scheduling policy of a task, after the kernel's `SCHED_*` constants.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Policy {
+    #[default]
+    Normal,
+    /// Real-time, runs until it blocks, yields or a higher priority task
+    /// arrives.
+    Fifo,
+    /// Real-time with a timeslice shared round-robin among equal priorities.
+    Rr,
+    /// Periodic reservation described by [`DlParams`].
+    Deadline,
+}
+
+impl Policy {
+    pub fn is_rt(self) -> bool {
+        matches!(self, Policy::Fifo | Policy::Rr)
+    }
+
+    pub fn is_dl(self) -> bool {
+        self == Policy::Deadline
+    }
+
+    fn from_raw(policy: u32) -> Policy {
+        match policy {
+            1 => Policy::Fifo,
+            2 => Policy::Rr,
+            _ => Policy::Normal,
+        }
+    }
+}
+
+/// `SCHED_DEADLINE` attributes: `runtime` ticks of CPU every `period`, each
+/// due `deadline` ticks after its release.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DlParams {
+    pub runtime: Time,
+    pub deadline: Time,
+    pub period: Time,
+}
+
+impl DlParams {
+    pub fn new(runtime: Time, deadline: Time, period: Time) -> DlParams {
+        assert!(
+            0 < runtime && runtime <= deadline && deadline <= period,
+            "deadline parameters must satisfy 0 < runtime <= deadline <= period"
+        );
+        DlParams {
+            runtime,
+            deadline,
+            period,
+        }
+    }
+
+    /// Same as [`DlParams::new`] with the deadline equal to the period.
+    pub fn implicit(runtime: Time, period: Time) -> DlParams {
+        DlParams::new(runtime, period, period)
+    }
+
+    pub fn utilization(&self) -> f64 {
+        self.runtime as f64 / self.period as f64
+    }
+}
+
+/// Current job of a periodic task.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Job {
+    pub release: Time,
+    /// Absolute deadline.
+    pub deadline: Time,
+    /// Work left in this job.
+    pub left: Time,
+}
+
+/// Per-task scheduling state, the counterpart of the kernel's
+/// `sched_entity`.
+#[derive(Clone, Debug)]
+pub struct SchedEntity {
+    pub weight: u64,
+    /// Weighted runtime in nanoseconds.
+    pub vruntime: u64,
+    /// Virtual deadline in nanoseconds, used by EEVDF.
+    pub deadline: u64,
+    /// Service owed to the task (positive) or received in excess (negative)
+    /// when it last left the runqueue, in virtual nanoseconds.
+    pub vlag: i64,
+    pub sum_exec_runtime: Time,
+    /// Time spent runnable but not running.
+    pub wait_sum: Time,
+    /// When the task last became runnable.
+    pub wait_start: Time,
+    pub nr_migrations: u32,
+    /// Position of the task in its runqueue's tree, if it is queued in one.
+    pub node: Option<rbtree::Handle>,
+    pub mlfq: MlfqLevel,
+}
+
+/// Where a task stands in an MLFQ. It is kept with the task so that a
+/// demotion survives migrating or waking up on another CPU.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MlfqLevel {
+    /// 0 is the top.
+    pub level: usize,
+    /// Ticks used of the level's allotment, kept across yields so a task
+    /// cannot stay on top by giving up the CPU just before its quantum runs
+    /// out.
+    pub used: Time,
+    /// When the policy last saved it, to tell whether a boost came since.
+    pub saved: Time,
+}
+
+impl SchedEntity {
+    fn new(nice: i16) -> SchedEntity {
+        SchedEntity {
+            weight: nice_to_weight(nice),
+            vruntime: 0,
+            deadline: 0,
+            vlag: 0,
+            sum_exec_runtime: 0,
+            wait_sum: 0,
+            wait_start: 0,
+            nr_migrations: 0,
+            node: None,
+            mlfq: MlfqLevel::default(),
+        }
+    }
+}
+
+/// What a task does between two CPU bursts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Io {
+    /// Sleeps for that many ticks.
+    Sleep(Time),
+    /// Makes that many requests to a device, one after another.
+    Device(DeviceId, Time),
+}
+
+#[derive(Clone, Debug)]
+pub struct Task {
+    pub pid: Pid,
+    pub name: String,
+    pub nice: i16,
+    pub policy: Policy,
+    /// 1 (lowest) to 99 for real-time tasks, 0 otherwise.
+    pub rt_priority: u16,
+    pub dl: Option<DlParams>,
+    /// CPUs the task may run on, one bit per CPU.
+    pub affinity: u64,
+    /// CPU the task last ran or was queued on.
+    pub cpu: usize,
+    pub arrival: Time,
+    /// CPU time needed; for periodic tasks, per job.
+    pub burst: Time,
+    /// Alternating CPU and I/O bursts, starting and ending with CPU, that
+    /// add up to `burst` of CPU. Empty when the task never blocks on I/O.
+    pub bursts: Vec<Time>,
+    /// Device each I/O burst waits on, in order. Such a burst counts
+    /// requests to the device rather than ticks; without one, it is a sleep.
+    pub devices: Vec<Option<DeviceId>>,
+    /// Number of jobs, one unless the task is periodic.
+    pub jobs: u32,
+    /// CPU time left over all jobs.
+    pub remaining: Time,
+    pub job: Option<Job>,
+    pub state: TaskState,
+    pub first_run: Option<Time>,
+    pub completion: Option<Time>,
+    pub se: SchedEntity,
+}
+
+impl Task {
+    pub fn builder() -> TaskBuilder {
+        TaskBuilder::new()
+    }
+
+    pub fn set_nice(&mut self, nice: i16) {
+        self.nice = nice.clamp(-20, 19);
+        self.se.weight = nice_to_weight(self.nice);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.state == TaskState::Finished
+    }
+
+    /// I/O burst that starts once the task has run `done` ticks of CPU, if a
+    /// CPU burst ends there.
+    pub fn io_after(&self, done: Time) -> Option<Io> {
+        let mut cpu = 0;
+        for (i, pair) in self.bursts.chunks(2).enumerate() {
+            cpu += pair[0];
+            if cpu >= done {
+                let &len = pair.get(1).filter(|_| cpu == done)?;
+                return Some(match self.devices.get(i).copied().flatten() {
+                    Some(device) => Io::Device(device, len),
+                    None => Io::Sleep(len),
+                });
+            }
+        }
+        None
+    }
+
+    /// CPU ticks left in the current burst: up to the next I/O, the end of
+    /// the job or the end of the task.
+    pub fn burst_left(&self) -> Time {
+        self.cpu_until_io()
+            .or(self.job.map(|job| job.left))
+            .unwrap_or(self.remaining)
+    }
+
+    /// CPU ticks left before the next I/O burst, if one is still to come.
+    pub fn cpu_until_io(&self) -> Option<Time> {
+        if self.bursts.is_empty() {
+            return None;
+        }
+        let done = self.burst - self.remaining;
+        let mut cpu = 0;
+        for pair in self.bursts.chunks(2) {
+            cpu += pair[0];
+            if cpu > done {
+                return pair.get(1).map(|_| cpu - done);
+            }
+        }
+        None
+    }
+}
+
+impl From<&Process> for Task {
+    fn from(process: &Process) -> Task {
+        TaskBuilder::from(process).build()
+    }
+}
+
+#[derive(Clone)]
+pub struct TaskBuilder {
+    pid: Option<Pid>,
+    name: Option<String>,
+    nice: i16,
+    policy: Policy,
+    rt_priority: u16,
+    dl: Option<DlParams>,
+    affinity: u64,
+    arrival: Time,
+    burst: Option<Time>,
+    bursts: Vec<Time>,
+    devices: Vec<Option<DeviceId>>,
+    jobs: u32,
+}
+
+impl TaskBuilder {
+    fn new() -> TaskBuilder {
+        TaskBuilder {
+            pid: None,
+            name: None,
+            nice: 0,
+            policy: Policy::Normal,
+            rt_priority: 0,
+            dl: None,
+            affinity: u64::MAX,
+            arrival: 0,
+            burst: None,
+            bursts: Vec::new(),
+            devices: Vec::new(),
+            jobs: 1,
+        }
+    }
+
+    pub fn pid(&mut self, pid: Pid) -> &mut Self {
+        self.pid = Some(pid);
+        self
+    }
+
+    pub fn name(&mut self, name: impl Into<String>) -> &mut Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn nice(&mut self, nice: i16) -> &mut Self {
+        self.nice = nice.clamp(-20, 19);
+        self
+    }
+
+    pub fn policy(&mut self, policy: Policy) -> &mut Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn rt_priority(&mut self, rt_priority: u16) -> &mut Self {
+        self.rt_priority = rt_priority;
+        self
+    }
+
+    /// Makes the task a periodic `SCHED_DEADLINE` task. Its burst, the work
+    /// of each job, defaults to the runtime.
+    pub fn sched_deadline(&mut self, params: DlParams) -> &mut Self {
+        self.policy = Policy::Deadline;
+        self.dl = Some(params);
+        self
+    }
+
+    pub fn affinity(&mut self, affinity: u64) -> &mut Self {
+        self.affinity = affinity;
+        self
+    }
+
+    pub fn jobs(&mut self, jobs: u32) -> &mut Self {
+        self.jobs = jobs;
+        self
+    }
+
+    pub fn arrival(&mut self, arrival: Time) -> &mut Self {
+        self.arrival = arrival;
+        self
+    }
+
+    pub fn burst(&mut self, burst: Time) -> &mut Self {
+        self.burst = Some(burst);
+        self.bursts.clear();
+        self.devices.clear();
+        self
+    }
+
+    /// CPU and I/O bursts in turn, e.g. `[4, 10, 3]` for 4 ticks of CPU, 10
+    /// of I/O and 3 more of CPU.
+    pub fn bursts(&mut self, bursts: impl Into<Vec<Time>>) -> &mut Self {
+        let bursts = bursts.into();
+        assert!(
+            bursts.len() % 2 == 1,
+            "bursts must start and end with a CPU burst"
+        );
+        assert!(!bursts.contains(&0), "bursts must be at least one tick");
+        self.burst = Some(bursts.iter().step_by(2).sum());
+        self.bursts = bursts;
+        self.devices.clear();
+        self
+    }
+
+    /// Devices the I/O bursts wait on, in order, after [`bursts`]. `None`
+    /// keeps a burst a plain sleep.
+    ///
+    /// [`bursts`]: TaskBuilder::bursts
+    pub fn devices(&mut self, devices: impl Into<Vec<Option<DeviceId>>>) -> &mut Self {
+        let devices = devices.into();
+        assert!(
+            devices.len() <= self.bursts.len() / 2,
+            "more devices than I/O bursts"
+        );
+        self.devices = devices;
+        self
+    }
+
+    pub fn build(&self) -> Task {
+        let pid = self.pid.unwrap();
+        let burst = self.burst.or(self.dl.map(|dl| dl.runtime)).unwrap();
+        assert!(burst > 0, "burst must be at least one tick");
+        assert!(self.jobs > 0, "a task needs at least one job");
+        assert!(
+            self.jobs == 1 || self.dl.is_some(),
+            "only deadline tasks have several jobs"
+        );
+        assert_eq!(
+            self.policy.is_dl(),
+            self.dl.is_some(),
+            "deadline tasks need deadline parameters"
+        );
+        assert!(
+            self.dl.is_none() || self.bursts.is_empty(),
+            "deadline tasks cannot block on I/O"
+        );
+        if self.policy.is_rt() {
+            assert!(
+                (1..=99).contains(&self.rt_priority),
+                "real-time priority must be within 1..=99"
+            );
+        }
+        Task {
+            pid,
+            name: self.name.clone().unwrap_or_else(|| format!("task{pid}")),
+            nice: self.nice,
+            policy: self.policy,
+            rt_priority: if self.policy.is_rt() {
+                self.rt_priority
+            } else {
+                0
+            },
+            dl: self.dl,
+            affinity: self.affinity,
+            cpu: 0,
+            arrival: self.arrival,
+            burst,
+            bursts: self.bursts.clone(),
+            devices: self.devices.clone(),
+            jobs: self.jobs,
+            remaining: burst * self.jobs as Time,
+            job: None,
+            state: TaskState::New,
+            first_run: None,
+            completion: None,
+            se: SchedEntity::new(self.nice),
+        }
+    }
+}
+
+/// Seeds a builder from a live process. The burst defaults to
+/// [`DEFAULT_BURST`] and can be overridden before building.
+impl From<&Process> for TaskBuilder {
+    fn from(process: &Process) -> TaskBuilder {
+        let rt_priority = process.rt_priority.unwrap_or(0);
+        let policy = match rt_priority {
+            0 => Policy::Normal,
+            _ => Policy::from_raw(process.policy.unwrap_or(0)),
+        };
+        let mut builder = TaskBuilder::new();
+        builder
+            .pid(process.pid)
+            .name(process.name.clone())
+            .nice(process.nice.unwrap_or(0))
+            .policy(policy)
+            .rt_priority(rt_priority)
+            .burst(DEFAULT_BURST);
+        builder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weight_follows_nice() {
+        assert_eq!(nice_to_weight(0), NICE_0_LOAD);
+        assert_eq!(nice_to_weight(-20), 88761);
+        assert_eq!(nice_to_weight(19), 15);
+        assert_eq!(nice_to_weight(40), 15);
+
+        let mut task = Task::builder().pid(7).burst(10).nice(5).build();
+        assert_eq!(task.se.weight, 335);
+        assert_eq!(task.name, "task7");
+        task.set_nice(-5);
+        assert_eq!(task.se.weight, 3121);
+    }
+
+    #[test]
+    fn task_from_live_processes() {
+        let mut probe = sys_probe::SysProbe::new();
+        probe.refresh_processes();
+        let process = probe.processes.values().next().unwrap();
+
+        let task = Task::from(process);
+        assert_eq!(task.pid, process.pid);
+        assert_eq!(task.nice, process.nice.unwrap_or(0));
+        assert_eq!(task.policy.is_rt(), task.rt_priority > 0);
+        assert_eq!(task.remaining, DEFAULT_BURST);
+        assert_eq!(task.state, TaskState::New);
+
+        let task = TaskBuilder::from(process).burst(5).arrival(3).build();
+        assert_eq!((task.burst, task.arrival), (5, 3));
+    }
+
+    #[test]
+    fn io_follows_cpu_bursts() {
+        let task = Task::builder().pid(1).bursts([4, 10, 3, 2, 5]).build();
+        assert_eq!(task.burst, 12);
+        assert_eq!(task.remaining, 12);
+        let io: Vec<_> = (0..=12).filter_map(|done| task.io_after(done)).collect();
+        assert_eq!(io, [Io::Sleep(10), Io::Sleep(2)]);
+        assert_eq!(task.io_after(4), Some(Io::Sleep(10)));
+        assert_eq!(task.io_after(7), Some(Io::Sleep(2)));
+        assert_eq!(task.io_after(12), None);
+        let on_disk = Task::builder()
+            .pid(1)
+            .bursts([4, 10, 3, 2, 5])
+            .devices([None, Some(0)])
+            .build();
+        assert_eq!(on_disk.io_after(7), Some(Io::Device(0, 2)));
+
+        let mut task = task;
+        assert_eq!(task.cpu_until_io(), Some(4));
+        task.remaining = 8;
+        assert_eq!(task.cpu_until_io(), Some(3));
+        task.remaining = 5;
+        assert_eq!(task.cpu_until_io(), None);
+        assert_eq!(task.burst_left(), 5);
+    }
+}
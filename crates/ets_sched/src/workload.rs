@@ -0,0 +1,448 @@
+use std::{collections::HashMap, fmt, fs, io, ops::Range, path::Path};
+
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::{
+    device::{Device, DeviceId, ServiceTime},
+    sched::Scheduler,
+    sim::Simulator,
+    task::{DlParams, Pid, Policy, Task, Time},
+};
+
+/// A set of tasks to simulate, and the devices they wait on, loadable from
+/// TOML:
+///
+/// ```toml
+/// [[device]]
+/// name = "disk"
+///
+/// [[device]]
+/// name = "printer"
+/// service = { uniform = [50, 150] }
+///
+/// [[task]]
+/// pid = 1
+/// name = "editor"
+/// bursts = [4, 10, 3] # 4 ticks of CPU, 10 of I/O, 3 of CPU
+///
+/// [[task]]
+/// pid = 5
+/// bursts = [2, "disk", 3, { device = "printer", requests = 2 }, 1]
+///
+/// [[task]]
+/// pid = 2
+/// arrival = 5
+/// nice = 5
+/// bursts = [20]
+///
+/// [[task]]
+/// pid = 3
+/// policy = "rr"
+/// rt_priority = 10
+/// bursts = [6]
+///
+/// [[task]]
+/// pid = 4
+/// policy = "deadline"
+/// runtime = 2
+/// period = 10
+/// jobs = 5
+/// ```
+///
+/// `policy` is one of `normal` (the default), `fifo`, `rr` or `deadline`.
+/// Deadline tasks take `runtime`, `period`, an optional `deadline` and a
+/// number of `jobs` instead of bursts. `affinity` is an optional CPU mask.
+///
+/// An I/O burst is either a number of ticks to sleep or a device to wait
+/// on, by name, for one request or the given number of them. A device's
+/// `service` time is `{ fixed = t }`, `{ uniform = [min, max] }` or
+/// `{ exponential = mean }`; `disk`, `network` and `keyboard` have a
+/// default one.
+#[derive(Clone, Debug, Default)]
+pub struct Workload {
+    pub tasks: Vec<Task>,
+    pub devices: Vec<Device>,
+}
+
+/// What is wrong with a workload file. Line numbers start at 1.
+#[derive(Debug)]
+pub enum WorkloadError {
+    Io(io::Error),
+    Syntax {
+        line: usize,
+        message: String,
+    },
+    Invalid {
+        line: usize,
+        message: String,
+    },
+    /// A task's affinity allows none of the simulated CPUs.
+    NoCpu {
+        pid: Pid,
+        ncpus: usize,
+    },
+}
+
+impl From<Vec<Task>> for Workload {
+    fn from(tasks: Vec<Task>) -> Workload {
+        Workload {
+            tasks,
+            devices: Vec::new(),
+        }
+    }
+}
+
+impl WorkloadError {
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            WorkloadError::Io(_) | WorkloadError::NoCpu { .. } => None,
+            WorkloadError::Syntax { line, .. } | WorkloadError::Invalid { line, .. } => Some(*line),
+        }
+    }
+}
+
+impl fmt::Display for WorkloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WorkloadError::Io(err) => write!(f, "cannot read workload: {err}"),
+            WorkloadError::NoCpu { pid, ncpus } => {
+                write!(f, "task {pid} cannot run on any of the {ncpus} CPUs")
+            }
+            WorkloadError::Syntax { line, message } | WorkloadError::Invalid { line, message } => {
+                write!(f, "line {line}: {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for WorkloadError {}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+    #[serde(default, rename = "device")]
+    devices: Vec<Spanned<DeviceSpec>>,
+    #[serde(default, rename = "task")]
+    tasks: Vec<Spanned<TaskSpec>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DeviceSpec {
+    name: String,
+    service: Option<ServiceTime>,
+}
+
+impl DeviceSpec {
+    fn to_device(&self) -> Result<Device, String> {
+        let service = match (self.service, Device::preset(&self.name)) {
+            (Some(service), _) => service,
+            (None, Some(preset)) => preset.service,
+            (None, None) => return Err(format!("device {} needs a service time", self.name)),
+        };
+        service.validate()?;
+        Ok(Device::new(self.name.clone(), service))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BurstSpec {
+    Ticks(Time),
+    Device(String),
+    Requests { device: String, requests: Time },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TaskSpec {
+    pid: Pid,
+    name: Option<String>,
+    #[serde(default)]
+    arrival: Time,
+    #[serde(default)]
+    nice: i16,
+    #[serde(default)]
+    policy: Policy,
+    #[serde(default)]
+    rt_priority: u16,
+    affinity: Option<u64>,
+    bursts: Option<Vec<BurstSpec>>,
+    runtime: Option<Time>,
+    deadline: Option<Time>,
+    period: Option<Time>,
+    jobs: Option<u32>,
+}
+
+impl TaskSpec {
+    /// Checks everything [`TaskBuilder::build`](crate::TaskBuilder::build)
+    /// would otherwise assert.
+    fn to_task(&self, devices: &HashMap<&str, DeviceId>) -> Result<Task, String> {
+        if !(-20..=19).contains(&self.nice) {
+            return Err(format!("nice {} is not within -20..=19", self.nice));
+        }
+        if self.policy.is_rt() != (self.rt_priority > 0) {
+            return Err("rt_priority is set for, and only for, fifo and rr tasks".into());
+        }
+        if self.rt_priority > 99 {
+            return Err(format!("rt_priority {} is above 99", self.rt_priority));
+        }
+        if self.affinity == Some(0) {
+            return Err("affinity allows no CPU".into());
+        }
+        let mut builder = Task::builder();
+        builder
+            .pid(self.pid)
+            .arrival(self.arrival)
+            .nice(self.nice)
+            .policy(self.policy)
+            .rt_priority(self.rt_priority);
+        if let Some(name) = &self.name {
+            builder.name(name.clone());
+        }
+        if let Some(affinity) = self.affinity {
+            builder.affinity(affinity);
+        }
+        if self.policy.is_dl() {
+            if self.bursts.is_some() {
+                return Err("deadline tasks take runtime and period, not bursts".into());
+            }
+            if self.jobs == Some(0) {
+                return Err("a task needs at least one job".into());
+            }
+            builder
+                .sched_deadline(self.dl_params()?)
+                .jobs(self.jobs.unwrap_or(1));
+        } else {
+            if self.runtime.is_some() || self.deadline.is_some() || self.period.is_some() {
+                return Err("runtime, deadline and period are for deadline tasks".into());
+            }
+            if self.jobs.is_some() {
+                return Err("jobs are for deadline tasks".into());
+            }
+            let specs = self.bursts.as_deref().ok_or("bursts are missing")?;
+            if specs.len() % 2 == 0 {
+                return Err("bursts must start and end with a CPU burst".into());
+            }
+            let mut bursts = Vec::with_capacity(specs.len());
+            let mut io_devices = Vec::new();
+            for (i, spec) in specs.iter().enumerate() {
+                let (ticks, device) = match spec {
+                    BurstSpec::Ticks(ticks) => (*ticks, None),
+                    _ if i % 2 == 0 => return Err("CPU bursts must be a number of ticks".into()),
+                    BurstSpec::Device(name) => (1, Some(name)),
+                    BurstSpec::Requests { device, requests } => (*requests, Some(device)),
+                };
+                if ticks == 0 {
+                    return Err("bursts must be at least one tick or request".into());
+                }
+                bursts.push(ticks);
+                if i % 2 == 1 {
+                    let device = device
+                        .map(|name| {
+                            devices
+                                .get(name.as_str())
+                                .copied()
+                                .ok_or_else(|| format!("there is no device {name}"))
+                        })
+                        .transpose()?;
+                    io_devices.push(device);
+                }
+            }
+            builder.bursts(bursts).devices(io_devices);
+        }
+        Ok(builder.build())
+    }
+
+    fn dl_params(&self) -> Result<DlParams, String> {
+        let (Some(runtime), Some(period)) = (self.runtime, self.period) else {
+            return Err("deadline tasks need a runtime and a period".into());
+        };
+        let deadline = self.deadline.unwrap_or(period);
+        if !(0 < runtime && runtime <= deadline && deadline <= period) {
+            return Err(
+                "deadline parameters must satisfy 0 < runtime <= deadline <= period".into(),
+            );
+        }
+        Ok(DlParams::new(runtime, deadline, period))
+    }
+}
+
+impl Workload {
+    pub fn from_toml(text: &str) -> Result<Workload, WorkloadError> {
+        let file: File = toml::from_str(text).map_err(|err| WorkloadError::Syntax {
+            line: err.span().map_or(1, |span| line_of(text, span)),
+            message: err.message().to_string(),
+        })?;
+        let mut names = HashMap::new();
+        let mut devices = Vec::with_capacity(file.devices.len());
+        for spec in &file.devices {
+            let line = line_of(text, spec.span());
+            let invalid = |message| WorkloadError::Invalid { line, message };
+            let device = spec.get_ref().to_device().map_err(invalid)?;
+            if let Some(first) = names.insert(spec.get_ref().name.as_str(), devices.len()) {
+                let first = line_of(text, file.devices[first].span());
+                return Err(invalid(format!(
+                    "device {} is already defined on line {first}",
+                    device.name
+                )));
+            }
+            devices.push(device);
+        }
+
+        let mut lines = HashMap::new();
+        let mut tasks = Vec::with_capacity(file.tasks.len());
+        for spec in &file.tasks {
+            let line = line_of(text, spec.span());
+            let invalid = |message| WorkloadError::Invalid { line, message };
+            let task = spec.get_ref().to_task(&names).map_err(invalid)?;
+            if let Some(first) = lines.insert(task.pid, line) {
+                return Err(invalid(format!(
+                    "pid {} is already used on line {first}",
+                    task.pid
+                )));
+            }
+            tasks.push(task);
+        }
+        Ok(Workload { tasks, devices })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Workload, WorkloadError> {
+        Workload::from_toml(&fs::read_to_string(path).map_err(WorkloadError::Io)?)
+    }
+
+    /// Checks that every task may run on one of the first `ncpus` CPUs.
+    pub fn check_cpus(&self, ncpus: usize) -> Result<(), WorkloadError> {
+        let online = u64::MAX >> (64 - ncpus.clamp(1, 64));
+        match self.tasks.iter().find(|task| task.affinity & online == 0) {
+            Some(task) => Err(WorkloadError::NoCpu {
+                pid: task.pid,
+                ncpus,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// A simulation of the workload with one CPU per scheduler.
+    pub fn simulator(
+        &self,
+        schedulers: Vec<Box<dyn Scheduler>>,
+    ) -> Result<Simulator, WorkloadError> {
+        self.check_cpus(schedulers.len())?;
+        let mut sim = Simulator::smp(schedulers, self.tasks.clone());
+        for device in &self.devices {
+            sim.add_device(device.clone());
+        }
+        Ok(sim)
+    }
+}
+
+fn line_of(text: &str, span: Range<usize>) -> usize {
+    text[..span.start].matches('\n').count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_every_kind_of_task() {
+        let text = include_str!("../workloads/example.toml");
+        let workload = Workload::from_toml(text).unwrap();
+        let tasks = &workload.tasks;
+        assert_eq!(tasks.len(), 5);
+        assert_eq!(tasks[0].name, "editor");
+        assert_eq!(tasks[0].bursts, [4, 10, 3]);
+        assert_eq!(tasks[0].burst, 7);
+        assert_eq!((tasks[1].arrival, tasks[1].nice), (5, 5));
+        assert_eq!((tasks[2].policy, tasks[2].rt_priority), (Policy::Rr, 10));
+        assert_eq!(tasks[3].dl, Some(DlParams::implicit(2, 10)));
+        assert_eq!(tasks[3].remaining, 10);
+        assert_eq!(tasks[4].bursts, [2, 1, 2, 2, 1]);
+        assert_eq!(tasks[4].devices, [Some(1), Some(2)]);
+
+        let devices = &workload.devices;
+        assert_eq!(devices[0], Device::disk());
+        assert_eq!(devices[2].service, ServiceTime::Uniform(50, 150));
+        let mut sim = workload
+            .simulator(vec![Box::new(crate::Cfs::new())])
+            .unwrap();
+        sim.run_until(10_000);
+        assert!(sim.is_done());
+    }
+
+    fn error(text: &str) -> (usize, String) {
+        let err = Workload::from_toml(text).unwrap_err();
+        (err.line().unwrap(), err.to_string())
+    }
+
+    #[test]
+    fn errors_point_at_the_line() {
+        let (line, message) =
+            error("[[task]]\npid = 1\nbursts = [3]\n\n[[task]]\npid = 2\nbursts = [3, 1]\n");
+        assert_eq!(line, 5);
+        assert!(
+            message.contains("start and end with a CPU burst"),
+            "{message}"
+        );
+
+        let (line, message) =
+            error("[[task]]\npid = 1\nbursts = [3]\n[[task]]\npid = 1\nbursts = [2]\n");
+        assert_eq!(line, 4);
+        assert!(message.contains("already used on line 1"), "{message}");
+
+        let (line, message) = error("[[task]]\npid = 1\nburst = [3]\n");
+        assert_eq!(line, 3);
+        assert!(message.contains("unknown field"), "{message}");
+
+        let (line, _) = error("[[task]]\npid = 1\npolicy = \"idle\"\nbursts = [3]\n");
+        assert_eq!(line, 3);
+    }
+
+    #[test]
+    fn affinity_must_allow_a_simulated_cpu() {
+        let workload =
+            Workload::from_toml("[[task]]\npid = 7\nbursts = [3]\naffinity = 4\n").unwrap();
+        let one_cpu = || vec![Box::new(crate::Cfs::new()) as Box<dyn Scheduler>];
+        let Err(err) = workload.simulator(one_cpu()) else {
+            panic!("no CPU can run task 7");
+        };
+        assert!(matches!(err, WorkloadError::NoCpu { pid: 7, ncpus: 1 }));
+        assert_eq!(err.to_string(), "task 7 cannot run on any of the 1 CPUs");
+        assert!(workload.check_cpus(3).is_ok());
+    }
+
+    #[test]
+    fn rejects_inconsistent_tasks() {
+        let cases = [
+            "pid = 1\nbursts = [3]\nnice = 30",
+            "pid = 1\nbursts = [3]\npolicy = \"fifo\"",
+            "pid = 1\nbursts = [3]\nrt_priority = 5",
+            "pid = 1\nbursts = [3, 0, 2]",
+            "pid = 1",
+            "pid = 1\nbursts = [3]\nperiod = 10",
+            "pid = 1\npolicy = \"deadline\"\nruntime = 5\nperiod = 4",
+            "pid = 1\npolicy = \"deadline\"\nruntime = 2\nperiod = 4\nbursts = [2]",
+            "pid = 1\nbursts = [3]\naffinity = 0",
+            "pid = 1\nbursts = [3, \"tape\", 2]",
+            "pid = 1\nbursts = [\"disk\"]",
+            "pid = 1\nbursts = [3, { device = \"disk\", requests = 0 }, 2]",
+        ];
+        for case in cases {
+            let (line, _) = error(&format!(
+                "[[device]]\nname = \"disk\"\n# comment\n[[task]]\n{case}\n"
+            ));
+            assert_eq!(line, 4, "{case}");
+        }
+
+        let (line, message) = error("[[device]]\nname = \"tape\"\n");
+        assert_eq!(line, 1);
+        assert!(message.contains("needs a service time"), "{message}");
+        let (line, message) = error("[[device]]\nname = \"disk\"\n[[device]]\nname = \"disk\"\n");
+        assert_eq!(line, 3);
+        assert!(message.contains("already defined on line 1"), "{message}");
+        let (line, _) = error("[[device]]\nname = \"a\"\nservice = { uniform = [3, 2] }\n");
+        assert_eq!(line, 1);
+    }
+}
@@ -0,0 +1,214 @@
+//! Compares the tree against `BTreeMap` and `BinaryHeap` on the operations a
+//! CFS timeline performs: insert, erase and pick-min. Run with
+//! `cargo bench -p rbtree`.
+//!
+//! Medians on one x86-64 machine, filling or emptying the whole structure,
+//! and one pop and push for pick-min:
+//!
+//! | operation        | RbTree   | CachedRbTree | BTreeMap | BinaryHeap |
+//! |------------------|----------|--------------|----------|------------|
+//! | insert, 1k       | 55.4 µs  | 56.6 µs      | 34.6 µs  | 5.2 µs     |
+//! | insert, 1M       | 846 ms   | 972 ms       | 98 ms    | 12.3 ms    |
+//! | erase, 1k        | 51.6 µs  | 50.5 µs      | 63.8 µs  | -          |
+//! | erase, 1M        | 329 ms   | 322 ms       | 361 ms   | -          |
+//! | pick-min, 1k     | 216 ns   | 189 ns       | 251 ns   | 94 ns      |
+//! | pick-min, 1M     | 268 ns   | 220 ns       | 130 ns   | 375 ns     |
+//! | peek, 1M         | 25.7 ns  | 1.7 ns       | -        | -          |
+//!
+//! `BTreeMap` inserts several times faster, since its nodes hold many keys,
+//! and wins pick-min on large trees. The tree erases by handle faster than
+//! `BTreeMap` erases by key, and the cached leftmost makes peeking at the
+//! next task constant time. `BinaryHeap` is fastest while small but cannot
+//! erase a blocked or migrated task, so it does not fit a runqueue. A CFS
+//! runqueue holds tens of tasks, where all of them take well under a
+//! microsecond, so `CachedRbTree` stays for its handles and leftmost cache.
+
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap},
+    hint::black_box,
+};
+
+use criterion::{
+    BatchSize, BenchmarkGroup, BenchmarkId, Criterion, criterion_group, criterion_main,
+    measurement::WallTime,
+};
+use rbtree::{CachedRbTree, RbTree};
+
+const SIZES: [usize; 6] = [10, 100, 1_000, 10_000, 100_000, 1_000_000];
+
+/// Pseudo-random vruntimes; duplicates are expected and must be kept.
+fn vruntimes(n: usize) -> Vec<u64> {
+    let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+    (0..n)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) % (n as u64 * 4)
+        })
+        .collect()
+}
+
+fn rbtree_of(keys: &[u64]) -> RbTree<u64, usize> {
+    let mut tree = RbTree::with_capacity(keys.len());
+    for (id, key) in keys.iter().enumerate() {
+        tree.insert_multi(*key, id);
+    }
+    tree
+}
+
+fn cached_of(keys: &[u64]) -> CachedRbTree<u64, usize> {
+    let mut tree = CachedRbTree::new();
+    for (id, key) in keys.iter().enumerate() {
+        tree.insert_multi(*key, id);
+    }
+    tree
+}
+
+fn btree_of(keys: &[u64]) -> BTreeMap<(u64, usize), ()> {
+    keys.iter()
+        .enumerate()
+        .map(|(id, key)| ((*key, id), ()))
+        .collect()
+}
+
+fn heap_of(keys: &[u64]) -> BinaryHeap<Reverse<(u64, usize)>> {
+    keys.iter()
+        .enumerate()
+        .map(|(id, key)| Reverse((*key, id)))
+        .collect()
+}
+
+fn configure<'a>(c: &'a mut Criterion, name: &str) -> BenchmarkGroup<'a, WallTime> {
+    let mut group = c.benchmark_group(name);
+    group.sample_size(10);
+    group
+}
+
+fn insert(c: &mut Criterion) {
+    let mut group = configure(c, "insert");
+    for n in SIZES {
+        let keys = vruntimes(n);
+        group.bench_with_input(BenchmarkId::new("RbTree", n), &keys, |b, keys| {
+            b.iter(|| rbtree_of(black_box(keys)))
+        });
+        group.bench_with_input(BenchmarkId::new("CachedRbTree", n), &keys, |b, keys| {
+            b.iter(|| cached_of(black_box(keys)))
+        });
+        group.bench_with_input(BenchmarkId::new("BTreeMap", n), &keys, |b, keys| {
+            b.iter(|| btree_of(black_box(keys)))
+        });
+        group.bench_with_input(BenchmarkId::new("BinaryHeap", n), &keys, |b, keys| {
+            b.iter(|| heap_of(black_box(keys)))
+        });
+    }
+    group.finish();
+}
+
+/// Removes every entry in insertion order: by handle from the trees, by key
+/// from `BTreeMap`. `BinaryHeap` cannot erase arbitrary entries, which is one
+/// reason it does not fit a CFS runqueue.
+fn erase(c: &mut Criterion) {
+    let mut group = configure(c, "erase");
+    for n in SIZES {
+        let keys = vruntimes(n);
+        group.bench_with_input(BenchmarkId::new("RbTree", n), &keys, |b, keys| {
+            b.iter_batched(
+                || {
+                    let mut tree: RbTree<u64, usize> = RbTree::with_capacity(keys.len());
+                    let handles: Vec<_> = keys
+                        .iter()
+                        .enumerate()
+                        .map(|(id, key)| tree.insert_multi(*key, id))
+                        .collect();
+                    (tree, handles)
+                },
+                |(mut tree, handles)| {
+                    for handle in handles {
+                        black_box(tree.remove_handle(handle));
+                    }
+                },
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_with_input(BenchmarkId::new("CachedRbTree", n), &keys, |b, keys| {
+            b.iter_batched(
+                || {
+                    let mut tree = CachedRbTree::new();
+                    let handles: Vec<_> = keys
+                        .iter()
+                        .enumerate()
+                        .map(|(id, key)| tree.insert_multi(*key, id))
+                        .collect();
+                    (tree, handles)
+                },
+                |(mut tree, handles)| {
+                    for handle in handles {
+                        black_box(tree.remove_handle(handle));
+                    }
+                },
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_with_input(BenchmarkId::new("BTreeMap", n), &keys, |b, keys| {
+            b.iter_batched(
+                || btree_of(keys),
+                |mut map| {
+                    for (id, key) in keys.iter().enumerate() {
+                        black_box(map.remove(&(*key, id)));
+                    }
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+/// One scheduler tick per iteration: take the minimum, advance its vruntime
+/// and put it back.
+fn pick_min(c: &mut Criterion) {
+    let mut group = configure(c, "pick_min");
+    for n in SIZES {
+        let keys = vruntimes(n);
+        let mut tree = rbtree_of(&keys);
+        group.bench_function(BenchmarkId::new("RbTree", n), |b| {
+            b.iter(|| {
+                let (key, id) = tree.pop_first().unwrap();
+                tree.insert_multi(key + 3, id);
+            })
+        });
+        let mut cached = cached_of(&keys);
+        group.bench_function(BenchmarkId::new("CachedRbTree", n), |b| {
+            b.iter(|| {
+                let (key, id) = cached.pop_first().unwrap();
+                cached.insert_multi(key + 3, id);
+            })
+        });
+        let mut map = btree_of(&keys);
+        group.bench_function(BenchmarkId::new("BTreeMap", n), |b| {
+            b.iter(|| {
+                let ((key, id), ()) = map.pop_first().unwrap();
+                map.insert((key + 3, id), ());
+            })
+        });
+        let mut heap = heap_of(&keys);
+        group.bench_function(BenchmarkId::new("BinaryHeap", n), |b| {
+            b.iter(|| {
+                let Reverse((key, id)) = heap.pop().unwrap();
+                heap.push(Reverse((key + 3, id)));
+            })
+        });
+        group.bench_function(BenchmarkId::new("CachedRbTree/peek", n), |b| {
+            b.iter(|| black_box(cached.first()))
+        });
+        group.bench_function(BenchmarkId::new("RbTree/peek", n), |b| {
+            b.iter(|| black_box(tree.first()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, insert, erase, pick_min);
+criterion_main!(benches);
@@ -0,0 +1,132 @@
+/// Slab of values addressed by index. Freed slots go on a free list and are
+/// reused; each slot counts how many times it has been freed so that handles
+/// to a previous occupant can be told apart from the current one.
+#[derive(Clone, Debug)]
+pub(crate) struct Arena<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<usize>,
+    len: usize,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Slot<T> {
+    pub(crate) generation: u32,
+    pub(crate) value: Option<T>,
+}
+
+impl<T> Arena<T> {
+    pub(crate) fn with_capacity(capacity: usize) -> Arena<T> {
+        Arena {
+            slots: Vec::with_capacity(capacity),
+            free: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// Rebuilds an arena from raw slots, e.g. when restoring a snapshot.
+    pub(crate) fn from_slots(slots: Vec<Slot<T>>) -> Arena<T> {
+        let free = (0..slots.len())
+            .rev()
+            .filter(|&idx| slots[idx].value.is_none())
+            .collect::<Vec<_>>();
+        let len = slots.len() - free.len();
+        Arena { slots, free, len }
+    }
+
+    pub(crate) fn slots(&self) -> &[Slot<T>] {
+        &self.slots
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.slots.capacity()
+    }
+
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        self.slots
+            .reserve(additional.saturating_sub(self.free.len()));
+    }
+
+    pub(crate) fn insert(&mut self, value: T) -> usize {
+        self.len += 1;
+        match self.free.pop() {
+            Some(idx) => {
+                self.slots[idx].value = Some(value);
+                idx
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    value: Some(value),
+                });
+                self.slots.len() - 1
+            }
+        }
+    }
+
+    pub(crate) fn remove(&mut self, idx: usize) -> T {
+        let slot = &mut self.slots[idx];
+        let value = slot.value.take().expect("dangling rbtree index");
+        slot.generation = slot.generation.wrapping_add(1);
+        self.len -= 1;
+        self.free.push(idx);
+        value
+    }
+
+    /// Empties every slot but keeps them allocated, so that handles taken
+    /// before the clear stay invalid afterwards.
+    pub(crate) fn clear(&mut self) {
+        self.free.clear();
+        for (idx, slot) in self.slots.iter_mut().enumerate().rev() {
+            if slot.value.take().is_some() {
+                slot.generation = slot.generation.wrapping_add(1);
+            }
+            self.free.push(idx);
+        }
+        self.len = 0;
+    }
+
+    pub(crate) fn get(&self, idx: usize) -> Option<&T> {
+        self.slots.get(idx)?.value.as_ref()
+    }
+
+    pub(crate) fn get_mut(&mut self, idx: usize) -> Option<&mut T> {
+        self.slots.get_mut(idx)?.value.as_mut()
+    }
+
+    pub(crate) fn generation(&self, idx: usize) -> u32 {
+        self.slots[idx].generation
+    }
+
+    /// Index of the occupied slot matching `generation`, if any.
+    pub(crate) fn resolve(&self, idx: usize, generation: u32) -> Option<usize> {
+        let slot = self.slots.get(idx)?;
+        (slot.value.is_some() && slot.generation == generation).then_some(idx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuses_slots_with_new_generation() {
+        let mut arena = Arena::with_capacity(4);
+        let a = arena.insert('a');
+        let b = arena.insert('b');
+        assert_eq!(arena.remove(a), 'a');
+        let c = arena.insert('c');
+        assert_eq!(c, a);
+        assert_eq!(arena.resolve(a, 0), None);
+        assert_eq!(arena.resolve(c, 1), Some(c));
+        assert_eq!(arena.get(b), Some(&'b'));
+        assert_eq!(arena.len(), 2);
+
+        arena.clear();
+        assert_eq!(arena.resolve(b, 0), None);
+        assert_eq!(arena.len(), 0);
+    }
+}
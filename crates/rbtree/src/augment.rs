@@ -0,0 +1,24 @@
+/// Callbacks for an augmented tree, where every node caches a summary of its
+/// whole subtree (e.g. the minimum deadline or the total weight below it).
+///
+/// The tree recomputes the summary bottom-up after every insertion, erase and
+/// rotation, the same way the kernel's `rb_augment_callbacks` do.
+pub trait Augment<K, V> {
+    type Value: PartialEq;
+
+    /// Builds the summary of a node from its own entry and the summaries of
+    /// its children, if it has any.
+    fn compute(
+        key: &K,
+        value: &V,
+        left: Option<&Self::Value>,
+        right: Option<&Self::Value>,
+    ) -> Self::Value;
+}
+
+/// Plain, non-augmented tree.
+impl<K, V> Augment<K, V> for () {
+    type Value = ();
+
+    fn compute(_: &K, _: &V, _: Option<&()>, _: Option<&()>) {}
+}
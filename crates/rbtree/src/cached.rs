@@ -0,0 +1,339 @@
+use std::{
+    borrow::Borrow,
+    fmt,
+    ops::{Bound, RangeBounds},
+};
+
+use crate::{
+    augment::Augment,
+    cursor::{Cursor, CursorMut, Range},
+    tree::{Handle, InvariantError, Iter, NIL, RbTree},
+};
+
+/// [`RbTree`] that remembers its leftmost node, like the kernel's
+/// `rb_root_cached`, so the minimum can be read in O(1).
+pub struct CachedRbTree<K, V, A: Augment<K, V> = ()> {
+    tree: RbTree<K, V, A>,
+    leftmost: usize,
+}
+
+impl<K, V> CachedRbTree<K, V> {
+    pub fn new() -> CachedRbTree<K, V> {
+        CachedRbTree::new_augmented()
+    }
+}
+
+impl<K, V, A: Augment<K, V>> CachedRbTree<K, V, A> {
+    pub fn new_augmented() -> CachedRbTree<K, V, A> {
+        CachedRbTree {
+            tree: RbTree::new_augmented(),
+            leftmost: NIL,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.tree.clear();
+        self.leftmost = NIL;
+    }
+
+    /// Smallest entry, read from the cache without walking the tree.
+    pub fn first(&self) -> Option<(&K, &V)> {
+        self.tree.entry_at(self.leftmost)
+    }
+
+    pub fn last(&self) -> Option<(&K, &V)> {
+        self.tree.last()
+    }
+
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        match self.leftmost {
+            NIL => None,
+            idx => Some(self.erase(idx)),
+        }
+    }
+
+    pub fn get_handle(&self, handle: Handle) -> Option<(&K, &V)> {
+        self.tree.get_handle(handle)
+    }
+
+    pub fn get_handle_mut(&mut self, handle: Handle) -> Option<&mut V> {
+        self.tree.get_handle_mut(handle)
+    }
+
+    pub fn remove_handle(&mut self, handle: Handle) -> Option<(K, V)> {
+        let idx = self.tree.resolve(handle)?;
+        Some(self.erase(idx))
+    }
+
+    /// Handle of the smallest entry, read from the cache.
+    pub fn first_handle(&self) -> Option<Handle> {
+        self.tree.handle(self.leftmost)
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V, A> {
+        self.tree.iter()
+    }
+
+    pub fn cursor_front(&self) -> Cursor<'_, K, V, A> {
+        Cursor::new(&self.tree, self.leftmost)
+    }
+
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, K, V, A> {
+        CursorMut::new(&mut self.tree, self.leftmost, Some(&mut self.leftmost))
+    }
+
+    pub fn cursor_at_mut(&mut self, handle: Handle) -> Option<CursorMut<'_, K, V, A>> {
+        let idx = self.tree.resolve(handle)?;
+        Some(CursorMut::new(
+            &mut self.tree,
+            idx,
+            Some(&mut self.leftmost),
+        ))
+    }
+
+    pub fn as_tree(&self) -> &RbTree<K, V, A> {
+        &self.tree
+    }
+
+    fn erase(&mut self, idx: usize) -> (K, V) {
+        if idx == self.leftmost {
+            self.leftmost = self.tree.next(idx);
+        }
+        self.tree.erase(idx)
+    }
+}
+
+impl<K: Ord, V, A: Augment<K, V>> CachedRbTree<K, V, A> {
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let (idx, old) = self.tree.insert_idx(key, value);
+        if old.is_none()
+            && (self.leftmost == NIL || self.tree.node(idx).key < self.tree.node(self.leftmost).key)
+        {
+            self.leftmost = idx;
+        }
+        old
+    }
+
+    pub fn insert_multi(&mut self, key: K, value: V) -> Handle {
+        let idx = self.tree.insert_multi_idx(key, value);
+        if self.leftmost == NIL || self.tree.node(idx).key < self.tree.node(self.leftmost).key {
+            self.leftmost = idx;
+        }
+        self.tree.handle(idx).unwrap()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match self.tree.find(key) {
+            NIL => None,
+            idx => Some(self.erase(idx)),
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.tree.get(key)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.tree.get_mut(key)
+    }
+
+    pub fn update<Q, F>(&mut self, key: &Q, f: F) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        F: FnOnce(&mut V),
+    {
+        self.tree.update(key, f)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.tree.contains_key(key)
+    }
+
+    pub fn range<Q, R>(&self, range: R) -> Range<'_, K, V, A>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        self.tree.range(range)
+    }
+
+    pub fn lower_bound_mut<Q>(&mut self, bound: Bound<&Q>) -> CursorMut<'_, K, V, A>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let idx = self.tree.lower_bound_idx(bound);
+        CursorMut::new(&mut self.tree, idx, Some(&mut self.leftmost))
+    }
+
+    /// Runs [`RbTree::check_invariants`] and also checks that the cached node
+    /// really is the leftmost one.
+    pub fn check_invariants(&self) -> Result<usize, InvariantError> {
+        let height = self.tree.check_invariants()?;
+        if self.leftmost != self.tree.leftmost() {
+            return Err(InvariantError::StaleLeftmost {
+                node: self.leftmost,
+            });
+        }
+        Ok(height)
+    }
+}
+
+impl<K: Clone, V: Clone, A: Augment<K, V>> Clone for CachedRbTree<K, V, A>
+where
+    A::Value: Clone,
+{
+    fn clone(&self) -> Self {
+        CachedRbTree {
+            tree: self.tree.clone(),
+            leftmost: self.leftmost,
+        }
+    }
+}
+
+impl<K, V, A: Augment<K, V>> Default for CachedRbTree<K, V, A> {
+    fn default() -> Self {
+        Self::new_augmented()
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, A: Augment<K, V>> fmt::Debug for CachedRbTree<K, V, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.tree.fmt(f)
+    }
+}
+
+impl<K: Ord, V, A: Augment<K, V>> FromIterator<(K, V)> for CachedRbTree<K, V, A> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut tree = CachedRbTree::new_augmented();
+        tree.extend(iter);
+        tree
+    }
+}
+
+impl<K: Ord, V, A: Augment<K, V>> Extend<(K, V)> for CachedRbTree<K, V, A> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<'a, K, V, A: Augment<K, V>> IntoIterator for &'a CachedRbTree<K, V, A> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, A>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_leftmost_on_insert() {
+        let mut tree = CachedRbTree::new();
+        for key in [50, 70, 30, 40, 10, 60] {
+            tree.insert(key, ());
+            tree.check_invariants().unwrap();
+        }
+        assert_eq!(tree.first(), Some((&10, &())));
+    }
+
+    #[test]
+    fn tracks_leftmost_on_erase() {
+        let mut tree: CachedRbTree<u32, u32> = (0..64).map(|k| (k * 3 % 64, k)).collect();
+        for expected in 0..64 {
+            assert_eq!(tree.first().map(|(k, _)| *k), Some(expected));
+            if expected % 2 == 0 {
+                tree.pop_first();
+            } else {
+                tree.remove(&expected);
+            }
+            tree.check_invariants().unwrap();
+        }
+        assert_eq!(tree.first(), None);
+    }
+
+    #[test]
+    fn equal_keys_leave_oldest_leftmost() {
+        let mut tree = CachedRbTree::new();
+        let first = tree.insert_multi(5, 'a');
+        let second = tree.insert_multi(5, 'b');
+        tree.insert_multi(9, 'c');
+        assert_eq!(tree.first_handle(), Some(first));
+        tree.remove_handle(first);
+        tree.check_invariants().unwrap();
+        assert_eq!(tree.first_handle(), Some(second));
+        let third = tree.insert_multi(2, 'd');
+        assert_eq!(tree.first(), Some((&2, &'d')));
+        assert_eq!(tree.first_handle(), Some(third));
+    }
+
+    #[test]
+    fn cursor_keeps_cache() {
+        let mut tree: CachedRbTree<u32, ()> = (10..20).map(|k| (k, ())).collect();
+        let mut cursor = tree.cursor_front_mut();
+        cursor.remove_current();
+        cursor.remove_current();
+        cursor.insert_before(5, ()).unwrap();
+        tree.check_invariants().unwrap();
+        assert_eq!(tree.first(), Some((&5, &())));
+
+        let mut cursor = tree.lower_bound_mut(Bound::Included(&15));
+        cursor.move_prev();
+        cursor.move_prev();
+        cursor.move_prev();
+        assert_eq!(cursor.peek(), Some((&12, &())));
+        cursor.move_prev();
+        cursor.remove_current();
+        tree.check_invariants().unwrap();
+        assert_eq!(tree.first(), Some((&12, &())));
+    }
+
+    #[test]
+    fn removing_other_nodes_keeps_cache() {
+        let mut tree: CachedRbTree<u32, ()> = (0..32).map(|k| (k, ())).collect();
+        for key in (1..32).rev() {
+            tree.remove(&key);
+            tree.check_invariants().unwrap();
+            assert_eq!(tree.first(), Some((&0, &())));
+        }
+    }
+}
@@ -0,0 +1,466 @@
+use std::{
+    borrow::Borrow,
+    fmt,
+    iter::FusedIterator,
+    ops::{Bound, RangeBounds},
+};
+
+use crate::{
+    augment::Augment,
+    tree::{Handle, NIL, RbTree},
+};
+
+/// Read-only cursor over an [`RbTree`].
+///
+/// Besides the entries, a cursor can sit on a "ghost" position that lies
+/// after the last entry and before the first one, like the cursors of
+/// `std::collections::LinkedList`.
+pub struct Cursor<'a, K, V, A: Augment<K, V> = ()> {
+    tree: &'a RbTree<K, V, A>,
+    idx: usize,
+}
+
+/// Cursor that can also remove the current entry and insert next to it.
+pub struct CursorMut<'a, K, V, A: Augment<K, V> = ()> {
+    tree: &'a mut RbTree<K, V, A>,
+    idx: usize,
+    leftmost: Option<&'a mut usize>,
+}
+
+/// Returned when an insertion through a cursor would break key order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnorderedKeyError;
+
+impl fmt::Display for UnorderedKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "key does not fit at the cursor position")
+    }
+}
+
+impl std::error::Error for UnorderedKeyError {}
+
+impl<K, V, A: Augment<K, V>> Clone for Cursor<'_, K, V, A> {
+    fn clone(&self) -> Self {
+        Cursor {
+            tree: self.tree,
+            idx: self.idx,
+        }
+    }
+}
+
+impl<'a, K, V, A: Augment<K, V>> Cursor<'a, K, V, A> {
+    pub(crate) fn new(tree: &'a RbTree<K, V, A>, idx: usize) -> Self {
+        Cursor { tree, idx }
+    }
+
+    pub fn peek(&self) -> Option<(&'a K, &'a V)> {
+        self.tree.entry_at(self.idx)
+    }
+
+    pub fn peek_next(&self) -> Option<(&'a K, &'a V)> {
+        self.tree.entry_at(step_next(self.tree, self.idx))
+    }
+
+    pub fn peek_prev(&self) -> Option<(&'a K, &'a V)> {
+        self.tree.entry_at(step_prev(self.tree, self.idx))
+    }
+
+    pub fn handle(&self) -> Option<Handle> {
+        self.tree.handle(self.idx)
+    }
+
+    pub fn move_next(&mut self) {
+        self.idx = step_next(self.tree, self.idx);
+    }
+
+    pub fn move_prev(&mut self) {
+        self.idx = step_prev(self.tree, self.idx);
+    }
+}
+
+impl<'a, K, V, A: Augment<K, V>> CursorMut<'a, K, V, A> {
+    pub(crate) fn new(
+        tree: &'a mut RbTree<K, V, A>,
+        idx: usize,
+        leftmost: Option<&'a mut usize>,
+    ) -> Self {
+        CursorMut {
+            tree,
+            idx,
+            leftmost,
+        }
+    }
+
+    pub fn peek(&self) -> Option<(&K, &V)> {
+        self.tree.entry_at(self.idx)
+    }
+
+    pub fn peek_next(&self) -> Option<(&K, &V)> {
+        self.tree.entry_at(step_next(self.tree, self.idx))
+    }
+
+    pub fn peek_prev(&self) -> Option<(&K, &V)> {
+        self.tree.entry_at(step_prev(self.tree, self.idx))
+    }
+
+    pub fn handle(&self) -> Option<Handle> {
+        self.tree.handle(self.idx)
+    }
+
+    pub fn as_cursor(&self) -> Cursor<'_, K, V, A> {
+        Cursor::new(self.tree, self.idx)
+    }
+
+    pub fn move_next(&mut self) {
+        self.idx = step_next(self.tree, self.idx);
+    }
+
+    pub fn move_prev(&mut self) {
+        self.idx = step_prev(self.tree, self.idx);
+    }
+
+    /// Applies `f` to the current value and refreshes the aggregates above
+    /// it. Returns `false` on the ghost position.
+    pub fn update<F: FnOnce(&mut V)>(&mut self, f: F) -> bool {
+        if self.idx == NIL {
+            return false;
+        }
+        f(&mut self.tree.node_mut(self.idx).value);
+        self.tree.propagate(self.idx);
+        true
+    }
+
+    /// Removes the current entry and moves the cursor to the next one.
+    pub fn remove_current(&mut self) -> Option<(K, V)> {
+        if self.idx == NIL {
+            return None;
+        }
+        let idx = self.idx;
+        self.idx = self.tree.next(idx);
+        if let Some(leftmost) = self.leftmost.as_deref_mut()
+            && *leftmost == idx
+        {
+            *leftmost = self.idx;
+        }
+        Some(self.tree.erase(idx))
+    }
+
+    fn inserted(&mut self, idx: usize) -> Handle {
+        if let Some(leftmost) = self.leftmost.as_deref_mut()
+            && self.tree.prev(idx) == NIL
+        {
+            *leftmost = idx;
+        }
+        self.tree.handle(idx).unwrap()
+    }
+}
+
+impl<K: Ord, V, A: Augment<K, V>> CursorMut<'_, K, V, A> {
+    /// Inserts right after the current entry (at the front when the cursor
+    /// is on the ghost position). The cursor does not move.
+    pub fn insert_after(&mut self, key: K, value: V) -> Result<Handle, UnorderedKeyError> {
+        let next = step_next(self.tree, self.idx);
+        if self.idx != NIL && key < self.tree.node(self.idx).key
+            || next != NIL && key > self.tree.node(next).key
+        {
+            return Err(UnorderedKeyError);
+        }
+        let idx = if self.idx == NIL {
+            match self.tree.leftmost() {
+                NIL => self.tree.link(NIL, true, key, value),
+                first => self.tree.link(first, true, key, value),
+            }
+        } else if self.tree.right(self.idx) == NIL {
+            self.tree.link(self.idx, false, key, value)
+        } else {
+            self.tree.link(next, true, key, value)
+        };
+        Ok(self.inserted(idx))
+    }
+
+    /// Inserts right before the current entry (at the back when the cursor
+    /// is on the ghost position). The cursor does not move.
+    pub fn insert_before(&mut self, key: K, value: V) -> Result<Handle, UnorderedKeyError> {
+        let prev = step_prev(self.tree, self.idx);
+        if self.idx != NIL && key > self.tree.node(self.idx).key
+            || prev != NIL && key < self.tree.node(prev).key
+        {
+            return Err(UnorderedKeyError);
+        }
+        let idx = if self.idx == NIL {
+            match self.tree.rightmost() {
+                NIL => self.tree.link(NIL, true, key, value),
+                last => self.tree.link(last, false, key, value),
+            }
+        } else if self.tree.left(self.idx) == NIL {
+            self.tree.link(self.idx, true, key, value)
+        } else {
+            self.tree.link(prev, false, key, value)
+        };
+        Ok(self.inserted(idx))
+    }
+}
+
+fn step_next<K, V, A: Augment<K, V>>(tree: &RbTree<K, V, A>, idx: usize) -> usize {
+    if idx == NIL {
+        tree.leftmost()
+    } else {
+        tree.next(idx)
+    }
+}
+
+fn step_prev<K, V, A: Augment<K, V>>(tree: &RbTree<K, V, A>, idx: usize) -> usize {
+    if idx == NIL {
+        tree.rightmost()
+    } else {
+        tree.prev(idx)
+    }
+}
+
+/// Iterator over the entries whose keys fall inside a range.
+pub struct Range<'a, K, V, A: Augment<K, V> = ()> {
+    tree: &'a RbTree<K, V, A>,
+    front: usize,
+    back: usize,
+}
+
+impl<'a, K: Ord, V, A: Augment<K, V>> Range<'a, K, V, A> {
+    pub(crate) fn new<Q, R>(tree: &'a RbTree<K, V, A>, range: R) -> Self
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let front = tree.lower_bound_idx(range.start_bound());
+        let back = tree.upper_bound_idx(range.end_bound());
+        if front == NIL || back == NIL || tree.node(front).key > tree.node(back).key {
+            return Range {
+                tree,
+                front: NIL,
+                back: NIL,
+            };
+        }
+        Range { tree, front, back }
+    }
+}
+
+impl<'a, K, V, A: Augment<K, V>> Iterator for Range<'a, K, V, A> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let idx = self.front;
+        if idx == NIL {
+            return None;
+        }
+        if idx == self.back {
+            self.front = NIL;
+            self.back = NIL;
+        } else {
+            self.front = self.tree.next(idx);
+        }
+        self.tree.entry_at(idx)
+    }
+}
+
+impl<K, V, A: Augment<K, V>> DoubleEndedIterator for Range<'_, K, V, A> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let idx = self.back;
+        if idx == NIL {
+            return None;
+        }
+        if idx == self.front {
+            self.front = NIL;
+            self.back = NIL;
+        } else {
+            self.back = self.tree.prev(idx);
+        }
+        self.tree.entry_at(idx)
+    }
+}
+
+impl<K, V, A: Augment<K, V>> FusedIterator for Range<'_, K, V, A> {}
+
+impl<K: Ord, V, A: Augment<K, V>> RbTree<K, V, A> {
+    /// First node whose key satisfies the lower `bound`.
+    pub(crate) fn lower_bound_idx<Q>(&self, bound: Bound<&Q>) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut idx = self.root_idx();
+        let mut found = NIL;
+        while idx != NIL {
+            let key = self.node(idx).key.borrow();
+            let inside = match bound {
+                Bound::Included(q) => key >= q,
+                Bound::Excluded(q) => key > q,
+                Bound::Unbounded => true,
+            };
+            if inside {
+                found = idx;
+                idx = self.left(idx);
+            } else {
+                idx = self.right(idx);
+            }
+        }
+        found
+    }
+
+    /// Last node whose key satisfies the upper `bound`.
+    pub(crate) fn upper_bound_idx<Q>(&self, bound: Bound<&Q>) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut idx = self.root_idx();
+        let mut found = NIL;
+        while idx != NIL {
+            let key = self.node(idx).key.borrow();
+            let inside = match bound {
+                Bound::Included(q) => key <= q,
+                Bound::Excluded(q) => key < q,
+                Bound::Unbounded => true,
+            };
+            if inside {
+                found = idx;
+                idx = self.right(idx);
+            } else {
+                idx = self.left(idx);
+            }
+        }
+        found
+    }
+
+    /// Entries whose keys fall inside `range`, in order.
+    pub fn range<Q, R>(&self, range: R) -> Range<'_, K, V, A>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        Range::new(self, range)
+    }
+
+    /// Cursor on the first entry satisfying `bound`, or on the ghost
+    /// position if there is none.
+    pub fn lower_bound<Q>(&self, bound: Bound<&Q>) -> Cursor<'_, K, V, A>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        Cursor::new(self, self.lower_bound_idx(bound))
+    }
+
+    pub fn lower_bound_mut<Q>(&mut self, bound: Bound<&Q>) -> CursorMut<'_, K, V, A>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let idx = self.lower_bound_idx(bound);
+        CursorMut::new(self, idx, None)
+    }
+}
+
+impl<K, V, A: Augment<K, V>> RbTree<K, V, A> {
+    pub fn cursor_front(&self) -> Cursor<'_, K, V, A> {
+        Cursor::new(self, self.leftmost())
+    }
+
+    pub fn cursor_back(&self) -> Cursor<'_, K, V, A> {
+        Cursor::new(self, self.rightmost())
+    }
+
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, K, V, A> {
+        let idx = self.leftmost();
+        CursorMut::new(self, idx, None)
+    }
+
+    pub fn cursor_back_mut(&mut self) -> CursorMut<'_, K, V, A> {
+        let idx = self.rightmost();
+        CursorMut::new(self, idx, None)
+    }
+
+    /// Cursor on the entry behind `handle`, or `None` if it was removed.
+    pub fn cursor_at(&self, handle: Handle) -> Option<Cursor<'_, K, V, A>> {
+        let idx = self.resolve(handle)?;
+        Some(Cursor::new(self, idx))
+    }
+
+    pub fn cursor_at_mut(&mut self, handle: Handle) -> Option<CursorMut<'_, K, V, A>> {
+        let idx = self.resolve(handle)?;
+        Some(CursorMut::new(self, idx, None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn walks_both_ways_through_ghost() {
+        let tree: RbTree<u32, ()> = (1..=3).map(|k| (k, ())).collect();
+        let mut cursor = tree.cursor_front();
+        assert_eq!(cursor.peek().map(|(k, _)| *k), Some(1));
+        cursor.move_prev();
+        assert_eq!(cursor.peek(), None);
+        assert_eq!(cursor.peek_prev().map(|(k, _)| *k), Some(3));
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.peek().map(|(k, _)| *k), Some(2));
+        assert_eq!(cursor.peek_next().map(|(k, _)| *k), Some(3));
+    }
+
+    #[test]
+    fn cursor_mut_edits_in_place() {
+        let mut tree: RbTree<u32, char> = [(10, 'a'), (20, 'b'), (30, 'c')].into_iter().collect();
+        let mut cursor = tree.cursor_front_mut();
+        cursor.move_next();
+        let inserted = cursor.insert_after(25, 'x').unwrap();
+        cursor.insert_before(15, 'y').unwrap();
+        assert_eq!(cursor.insert_before(5, 'z'), Err(UnorderedKeyError));
+        assert_eq!(cursor.remove_current(), Some((20, 'b')));
+        assert_eq!(cursor.handle(), Some(inserted));
+        tree.check_invariants().unwrap();
+
+        let keys: Vec<u32> = tree.keys().copied().collect();
+        assert_eq!(keys, [10, 15, 25, 30]);
+
+        let mut cursor = tree.cursor_back_mut();
+        cursor.move_next();
+        cursor.insert_after(1, 'f').unwrap();
+        cursor.insert_before(99, 'l').unwrap();
+        tree.check_invariants().unwrap();
+        assert_eq!(tree.first(), Some((&1, &'f')));
+        assert_eq!(tree.last(), Some((&99, &'l')));
+    }
+
+    #[test]
+    fn range_respects_bounds() {
+        let tree: RbTree<u32, ()> = (0..50).map(|k| (k * 2, ())).collect();
+        let keys = |r: Range<'_, u32, ()>| r.map(|(k, _)| *k).collect::<Vec<_>>();
+        assert_eq!(keys(tree.range(10..16)), [10, 12, 14]);
+        assert_eq!(keys(tree.range(9..=16)), [10, 12, 14, 16]);
+        assert_eq!(keys(tree.range(95..)), [96, 98]);
+        assert_eq!(keys(tree.range(..3)), [0, 2]);
+        assert_eq!(keys(tree.range(11..12)), Vec::<u32>::new());
+        assert_eq!(
+            tree.range((Bound::Excluded(90), Bound::Unbounded))
+                .rev()
+                .map(|(k, _)| *k)
+                .collect::<Vec<_>>(),
+            [98, 96, 94, 92]
+        );
+    }
+
+    #[test]
+    fn range_includes_all_duplicates() {
+        let mut tree = RbTree::new();
+        for (i, key) in [5, 3, 5, 7, 5].into_iter().enumerate() {
+            tree.insert_multi(key, i);
+        }
+        let values: Vec<usize> = tree.range(5..=5).map(|(_, v)| *v).collect();
+        assert_eq!(values, [0, 2, 4]);
+        let cursor = tree.lower_bound(Bound::Excluded(&5));
+        assert_eq!(cursor.peek(), Some((&7, &3)));
+    }
+}
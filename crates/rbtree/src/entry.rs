@@ -0,0 +1,178 @@
+use std::cmp::Ordering;
+
+use crate::{
+    augment::Augment,
+    tree::{Handle, NIL, RbTree},
+};
+
+/// View into a single key of an [`RbTree`], obtained from [`RbTree::entry`].
+pub enum Entry<'a, K, V, A: Augment<K, V> = ()> {
+    Occupied(OccupiedEntry<'a, K, V, A>),
+    Vacant(VacantEntry<'a, K, V, A>),
+}
+
+pub struct OccupiedEntry<'a, K, V, A: Augment<K, V> = ()> {
+    tree: &'a mut RbTree<K, V, A>,
+    idx: usize,
+}
+
+pub struct VacantEntry<'a, K, V, A: Augment<K, V> = ()> {
+    tree: &'a mut RbTree<K, V, A>,
+    key: K,
+    parent: usize,
+    left: bool,
+}
+
+impl<K: Ord, V, A: Augment<K, V>> RbTree<K, V, A> {
+    /// Entry for `key`. With duplicates present it refers to the oldest one.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, A> {
+        let idx = self.find(&key);
+        if idx != NIL {
+            return Entry::Occupied(OccupiedEntry { tree: self, idx });
+        }
+        let mut parent = NIL;
+        let mut left = false;
+        let mut idx = self.root_idx();
+        while idx != NIL {
+            parent = idx;
+            left = key.cmp(&self.node(idx).key) == Ordering::Less;
+            idx = if left {
+                self.left(idx)
+            } else {
+                self.right(idx)
+            };
+        }
+        Entry::Vacant(VacantEntry {
+            tree: self,
+            key,
+            parent,
+            left,
+        })
+    }
+}
+
+impl<'a, K, V, A: Augment<K, V>> Entry<'a, K, V, A> {
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default),
+        }
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Applies `f` to an occupied value, refreshing the aggregates above it.
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(mut entry) => {
+                entry.update(f);
+                Entry::Occupied(entry)
+            }
+            vacant => vacant,
+        }
+    }
+}
+
+impl<'a, K, V: Default, A: Augment<K, V>> Entry<'a, K, V, A> {
+    pub fn or_default(self) -> &'a mut V {
+        self.or_insert_with(V::default)
+    }
+}
+
+impl<'a, K, V, A: Augment<K, V>> OccupiedEntry<'a, K, V, A> {
+    pub fn key(&self) -> &K {
+        &self.tree.node(self.idx).key
+    }
+
+    pub fn get(&self) -> &V {
+        &self.tree.node(self.idx).value
+    }
+
+    /// Mutable access without refreshing aggregates; see [`RbTree::get_mut`].
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.tree.node_mut(self.idx).value
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.tree.node_mut(self.idx).value
+    }
+
+    pub fn update<F: FnOnce(&mut V)>(&mut self, f: F) {
+        f(&mut self.tree.node_mut(self.idx).value);
+        self.tree.propagate(self.idx);
+    }
+
+    pub fn insert(&mut self, value: V) -> V {
+        let old = std::mem::replace(&mut self.tree.node_mut(self.idx).value, value);
+        self.tree.propagate(self.idx);
+        old
+    }
+
+    pub fn handle(&self) -> Handle {
+        self.tree.handle(self.idx).unwrap()
+    }
+
+    pub fn remove_entry(self) -> (K, V) {
+        self.tree.erase(self.idx)
+    }
+
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+}
+
+impl<'a, K, V, A: Augment<K, V>> VacantEntry<'a, K, V, A> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    pub fn insert(self, value: V) -> &'a mut V {
+        let idx = self.tree.link(self.parent, self.left, self.key, value);
+        &mut self.tree.node_mut(idx).value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_with_entries() {
+        let mut tree: RbTree<char, u32> = RbTree::new();
+        for c in "abracadabra".chars() {
+            *tree.entry(c).or_default() += 1;
+        }
+        tree.check_invariants().unwrap();
+        let counts: Vec<(char, u32)> = tree.iter().map(|(k, v)| (*k, *v)).collect();
+        assert_eq!(counts, [('a', 5), ('b', 2), ('c', 1), ('d', 1), ('r', 2)]);
+    }
+
+    #[test]
+    fn occupied_entry_edits_and_removes() {
+        let mut tree: RbTree<u32, u32> = (0..10).map(|k| (k, k)).collect();
+        tree.entry(4).and_modify(|v| *v = 40).or_insert(0);
+        assert_eq!(tree.get(&4), Some(&40));
+        match tree.entry(7) {
+            Entry::Occupied(entry) => assert_eq!(entry.remove(), 7),
+            Entry::Vacant(_) => panic!("7 should be present"),
+        }
+        assert!(!tree.contains_key(&7));
+        tree.check_invariants().unwrap();
+    }
+}
@@ -1,14 +1,15 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+mod arena;
+mod augment;
+mod cached;
+mod cursor;
+mod entry;
+mod render;
+mod snapshot;
+mod tree;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+pub use augment::Augment;
+pub use cached::CachedRbTree;
+pub use cursor::{Cursor, CursorMut, Range, UnorderedKeyError};
+pub use entry::{Entry, OccupiedEntry, VacantEntry};
+pub use snapshot::{Snapshot, SnapshotNode, SnapshotSlot};
+pub use tree::{Color, Handle, InvariantError, Iter, NodeRef, RbTree};
@@ -0,0 +1,150 @@
+use std::fmt::{Display, Write};
+
+use crate::{
+    augment::Augment,
+    tree::{Color, NodeRef, RbTree},
+};
+
+const RED: &str = "\x1b[31m";
+const BLACK: &str = "\x1b[1;90m";
+const RESET: &str = "\x1b[0m";
+
+impl<K, V, A: Augment<K, V>> RbTree<K, V, A> {
+    /// Indented drawing of the tree, root first and left child above right.
+    /// With `ansi` the labels are coloured, otherwise they carry an `[R]` or
+    /// `[B]` tag.
+    pub fn to_ascii(&self, ansi: bool) -> String
+    where
+        K: Display,
+    {
+        self.to_ascii_with(ansi, |key, _| key.to_string())
+    }
+
+    pub fn to_ascii_with<F>(&self, ansi: bool, label: F) -> String
+    where
+        F: Fn(&K, &V) -> String,
+    {
+        let mut out = String::new();
+        if let Some(root) = self.root_node() {
+            ascii_node(&mut out, root, "", "", ansi, &label);
+        }
+        out
+    }
+
+    /// Graphviz DOT source for the tree; feed it to `dot -Tsvg`.
+    pub fn to_dot(&self) -> String
+    where
+        K: Display,
+    {
+        self.to_dot_with(|key, _| key.to_string())
+    }
+
+    pub fn to_dot_with<F>(&self, label: F) -> String
+    where
+        F: Fn(&K, &V) -> String,
+    {
+        let mut out = String::from("digraph rbtree {\n");
+        out.push_str("    node [shape=circle, style=filled, fontcolor=white];\n");
+        let mut nil = 0;
+        let mut stack: Vec<(usize, NodeRef<'_, K, V, A>)> = Vec::new();
+        let mut next_id = 0;
+        if let Some(root) = self.root_node() {
+            stack.push((next_id, root));
+            next_id += 1;
+        }
+        while let Some((id, node)) = stack.pop() {
+            let fill = match node.color() {
+                Color::Red => "red",
+                Color::Black => "black",
+            };
+            let text = label(node.key(), node.value())
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"");
+            let _ = writeln!(out, "    n{id} [label=\"{text}\", fillcolor={fill}];");
+            for child in [node.left(), node.right()] {
+                match child {
+                    Some(child) => {
+                        let _ = writeln!(out, "    n{id} -> n{next_id};");
+                        stack.push((next_id, child));
+                        next_id += 1;
+                    }
+                    None => {
+                        let _ = writeln!(out, "    nil{nil} [shape=point, fillcolor=black];");
+                        let _ = writeln!(out, "    n{id} -> nil{nil};");
+                        nil += 1;
+                    }
+                }
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+fn ascii_node<K, V, A, F>(
+    out: &mut String,
+    node: NodeRef<'_, K, V, A>,
+    lead: &str,
+    prefix: &str,
+    ansi: bool,
+    label: &F,
+) where
+    A: Augment<K, V>,
+    F: Fn(&K, &V) -> String,
+{
+    let text = label(node.key(), node.value());
+    let _ = match (ansi, node.color()) {
+        (true, Color::Red) => writeln!(out, "{lead}{RED}{text}{RESET}"),
+        (true, Color::Black) => writeln!(out, "{lead}{BLACK}{text}{RESET}"),
+        (false, Color::Red) => writeln!(out, "{lead}{text} [R]"),
+        (false, Color::Black) => writeln!(out, "{lead}{text} [B]"),
+    };
+    let (left, right) = (node.left(), node.right());
+    if left.is_none() && right.is_none() {
+        return;
+    }
+    for (child, last) in [(left, false), (right, true)] {
+        let (branch, indent) = if last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        let lead = format!("{prefix}{branch}");
+        match child {
+            Some(child) => ascii_node(out, child, &lead, &format!("{prefix}{indent}"), ansi, label),
+            None => {
+                let _ = writeln!(out, "{lead}·");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_ascii_tree() {
+        let tree: RbTree<u32, ()> = (1..=4).map(|k| (k, ())).collect();
+        let expected = "\
+2 [B]
+├── 1 [B]
+└── 3 [B]
+    ├── ·
+    └── 4 [R]
+";
+        assert_eq!(tree.to_ascii(false), expected);
+        assert!(tree.to_ascii(true).contains(&format!("{RED}4{RESET}")));
+        assert_eq!(RbTree::<u32, ()>::new().to_ascii(false), "");
+    }
+
+    #[test]
+    fn emits_dot_graph() {
+        let tree: RbTree<&str, u32> = [("b", 2), ("a", 1), ("c\"", 3)].into_iter().collect();
+        let dot = tree.to_dot_with(|k, v| format!("{k}={v}"));
+        assert!(dot.starts_with("digraph rbtree {"));
+        assert!(dot.contains("n0 [label=\"b=2\", fillcolor=black];"));
+        assert!(dot.contains("[label=\"c\\\"=3\", fillcolor=red];"));
+        assert_eq!(dot.matches("->").count(), 6);
+    }
+}
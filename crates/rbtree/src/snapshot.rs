@@ -0,0 +1,186 @@
+use crate::{
+    arena::{Arena, Slot},
+    augment::Augment,
+    tree::{Color, InvariantError, NIL, Node, RbTree},
+};
+
+/// Plain-data copy of a tree's arena, slot by slot.
+///
+/// Restoring a snapshot puts every entry back in the same slot with the same
+/// generation, so [`Handle`](crate::Handle)s taken before the snapshot keep
+/// working on the restored tree. Aggregates are not stored; they are rebuilt
+/// on restore.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot<K, V> {
+    pub root: Option<usize>,
+    pub slots: Vec<SnapshotSlot<K, V>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SnapshotSlot<K, V> {
+    pub generation: u32,
+    pub node: Option<SnapshotNode<K, V>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SnapshotNode<K, V> {
+    pub key: K,
+    pub value: V,
+    pub color: Color,
+    pub parent: Option<usize>,
+    pub left: Option<usize>,
+    pub right: Option<usize>,
+}
+
+fn link(idx: usize) -> Option<usize> {
+    (idx != NIL).then_some(idx)
+}
+
+fn unlink(idx: Option<usize>) -> usize {
+    idx.unwrap_or(NIL)
+}
+
+impl<K: Clone, V: Clone, A: Augment<K, V>> RbTree<K, V, A> {
+    pub fn snapshot(&self) -> Snapshot<K, V> {
+        let slots = self
+            .arena()
+            .slots()
+            .iter()
+            .map(|slot| SnapshotSlot {
+                generation: slot.generation,
+                node: slot.value.as_ref().map(|node| SnapshotNode {
+                    key: node.key.clone(),
+                    value: node.value.clone(),
+                    color: node.color,
+                    parent: link(node.parent),
+                    left: link(node.left),
+                    right: link(node.right),
+                }),
+            })
+            .collect();
+        Snapshot {
+            root: link(self.root_idx()),
+            slots,
+        }
+    }
+}
+
+impl<K: Ord, V, A: Augment<K, V>> RbTree<K, V, A> {
+    /// Rebuilds a tree from a snapshot, rejecting it unless every link
+    /// points at an occupied slot and all red-black invariants hold.
+    pub fn from_snapshot(snapshot: Snapshot<K, V>) -> Result<Self, InvariantError> {
+        let occupied = |idx: Option<usize>| match idx {
+            None => true,
+            Some(idx) => snapshot
+                .slots
+                .get(idx)
+                .is_some_and(|slot| slot.node.is_some()),
+        };
+        for (idx, slot) in snapshot.slots.iter().enumerate() {
+            if let Some(node) = &slot.node
+                && !(occupied(node.parent) && occupied(node.left) && occupied(node.right))
+            {
+                return Err(InvariantError::Dangling { node: idx });
+            }
+        }
+        if !occupied(snapshot.root) {
+            return Err(InvariantError::Dangling {
+                node: unlink(snapshot.root),
+            });
+        }
+
+        let slots = snapshot
+            .slots
+            .into_iter()
+            .map(|slot| Slot {
+                generation: slot.generation,
+                value: slot.node.map(|node| {
+                    let aug = A::compute(&node.key, &node.value, None, None);
+                    Node {
+                        key: node.key,
+                        value: node.value,
+                        aug,
+                        color: node.color,
+                        parent: unlink(node.parent),
+                        left: unlink(node.left),
+                        right: unlink(node.right),
+                    }
+                }),
+            })
+            .collect();
+        let mut tree = RbTree::from_arena(Arena::from_slots(slots), unlink(snapshot.root));
+        tree.rebuild_aggregates()?;
+        tree.check_invariants()?;
+        Ok(tree)
+    }
+
+    /// Recomputes every aggregate bottom-up. Fails instead of looping if the
+    /// links do not form a tree.
+    fn rebuild_aggregates(&mut self) -> Result<(), InvariantError> {
+        let mut order = Vec::with_capacity(self.len());
+        let mut stack = vec![self.root_idx()];
+        while let Some(idx) = stack.pop() {
+            if idx == NIL {
+                continue;
+            }
+            if order.len() == self.len() {
+                return Err(InvariantError::Len {
+                    expected: self.len(),
+                    found: order.len() + 1,
+                });
+            }
+            order.push(idx);
+            stack.push(self.left(idx));
+            stack.push(self.right(idx));
+        }
+        for idx in order.into_iter().rev() {
+            self.recompute(idx);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_keeps_handles() {
+        let mut tree = RbTree::new();
+        let handles: Vec<_> = (0..20).map(|k| tree.insert_multi(k % 7, k)).collect();
+        for handle in handles.iter().step_by(3) {
+            tree.remove_handle(*handle);
+        }
+        let restored: RbTree<u32, u32> = RbTree::from_snapshot(tree.snapshot()).unwrap();
+        assert_eq!(restored.snapshot(), tree.snapshot());
+        for handle in &handles {
+            assert_eq!(restored.get_handle(*handle), tree.get_handle(*handle));
+        }
+    }
+
+    #[test]
+    fn rejects_corrupt_snapshots() {
+        let tree: RbTree<u32, ()> = (0..10).map(|k| (k, ())).collect();
+
+        let mut dangling = tree.snapshot();
+        dangling.slots[3].node.as_mut().unwrap().left = Some(99);
+        assert_eq!(
+            RbTree::<u32, ()>::from_snapshot(dangling).err(),
+            Some(InvariantError::Dangling { node: 3 })
+        );
+
+        let mut cyclic = tree.snapshot();
+        let root = cyclic.root.unwrap();
+        let leaf = (0..10)
+            .find(|&i| {
+                let node = cyclic.slots[i].node.as_ref().unwrap();
+                node.left.is_none() && node.right.is_none()
+            })
+            .unwrap();
+        cyclic.slots[leaf].node.as_mut().unwrap().left = Some(root);
+        assert!(RbTree::<u32, ()>::from_snapshot(cyclic).is_err());
+    }
+}
@@ -0,0 +1,1123 @@
+use std::{borrow::Borrow, cmp::Ordering, fmt, iter::FusedIterator, marker::PhantomData};
+
+use crate::{arena::Arena, augment::Augment};
+
+pub(crate) const NIL: usize = usize::MAX;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Color {
+    Red,
+    Black,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Node<K, V, S> {
+    pub(crate) key: K,
+    pub(crate) value: V,
+    pub(crate) aug: S,
+    pub(crate) color: Color,
+    pub(crate) parent: usize,
+    pub(crate) left: usize,
+    pub(crate) right: usize,
+}
+
+/// Stable reference to one entry of a tree, returned by
+/// [`RbTree::insert_multi`]. It survives any other insertion or removal and
+/// stops resolving once its own entry is removed, even if the slot is reused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Handle {
+    index: usize,
+    generation: u32,
+}
+
+impl Handle {
+    /// Slot of the entry in the tree's arena.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+/// Ordered map backed by a red-black tree.
+///
+/// Nodes live in an arena and link to each other by index, so the tree needs
+/// no `unsafe`, rotations never move an entry and freed slots are recycled on
+/// the next insertion.
+///
+/// `A` selects the [`Augment`] callbacks; the default `()` keeps no subtree
+/// summary at all.
+pub struct RbTree<K, V, A: Augment<K, V> = ()> {
+    nodes: Arena<Node<K, V, A::Value>>,
+    root: usize,
+    augment: PhantomData<A>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvariantError {
+    RedRoot,
+    RedRed { node: usize },
+    BlackHeight { node: usize },
+    BrokenLink { node: usize },
+    Unordered { node: usize },
+    Len { expected: usize, found: usize },
+    Dangling { node: usize },
+    StaleLeftmost { node: usize },
+    StaleAggregate { node: usize },
+}
+
+impl fmt::Display for InvariantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvariantError::RedRoot => write!(f, "root is red"),
+            InvariantError::RedRed { node } => write!(f, "red node {node} has a red child"),
+            InvariantError::BlackHeight { node } => {
+                write!(f, "subtrees of node {node} have different black heights")
+            }
+            InvariantError::BrokenLink { node } => {
+                write!(f, "parent link of node {node} does not match")
+            }
+            InvariantError::Unordered { node } => write!(f, "node {node} is out of key order"),
+            InvariantError::Len { expected, found } => {
+                write!(f, "tree holds {found} nodes but len is {expected}")
+            }
+            InvariantError::Dangling { node } => {
+                write!(f, "node {node} links to an empty slot")
+            }
+            InvariantError::StaleLeftmost { node } => {
+                write!(f, "cached leftmost node {node} is not the minimum")
+            }
+            InvariantError::StaleAggregate { node } => {
+                write!(f, "aggregate of node {node} does not match its subtree")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvariantError {}
+
+impl<K, V> RbTree<K, V> {
+    pub fn new() -> RbTree<K, V> {
+        RbTree::new_augmented()
+    }
+}
+
+impl<K, V, A: Augment<K, V>> RbTree<K, V, A> {
+    /// Empty tree maintaining the aggregates described by `A`.
+    pub fn new_augmented() -> RbTree<K, V, A> {
+        RbTree::with_capacity(0)
+    }
+
+    /// Empty tree whose arena has room for `capacity` nodes.
+    pub fn with_capacity(capacity: usize) -> RbTree<K, V, A> {
+        RbTree::from_arena(Arena::with_capacity(capacity), NIL)
+    }
+
+    pub(crate) fn from_arena(nodes: Arena<Node<K, V, A::Value>>, root: usize) -> Self {
+        RbTree {
+            nodes,
+            root,
+            augment: PhantomData,
+        }
+    }
+
+    pub(crate) fn arena(&self) -> &Arena<Node<K, V, A::Value>> {
+        &self.nodes
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.nodes.capacity()
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.nodes.reserve(additional);
+    }
+
+    /// Removes every entry. Handles taken before the clear stop resolving.
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.root = NIL;
+    }
+
+    /// Smallest entry, i.e. the leftmost node.
+    pub fn first(&self) -> Option<(&K, &V)> {
+        self.entry_at(self.leftmost())
+    }
+
+    /// Largest entry, i.e. the rightmost node.
+    pub fn last(&self) -> Option<(&K, &V)> {
+        self.entry_at(self.rightmost())
+    }
+
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        match self.leftmost() {
+            NIL => None,
+            idx => Some(self.erase(idx)),
+        }
+    }
+
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        match self.rightmost() {
+            NIL => None,
+            idx => Some(self.erase(idx)),
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V, A> {
+        Iter {
+            tree: self,
+            front: self.leftmost(),
+            back: self.rightmost(),
+            remaining: self.len(),
+        }
+    }
+
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &K> + ExactSizeIterator {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &V> + ExactSizeIterator {
+        self.iter().map(|(_, v)| v)
+    }
+
+    /// Entry behind `handle`, or `None` if it has been removed.
+    pub fn get_handle(&self, handle: Handle) -> Option<(&K, &V)> {
+        self.entry_at(self.resolve(handle)?)
+    }
+
+    /// Mutable value behind `handle`. The same caveat as [`RbTree::get_mut`]
+    /// applies to augmented trees.
+    pub fn get_handle_mut(&mut self, handle: Handle) -> Option<&mut V> {
+        let idx = self.resolve(handle)?;
+        Some(&mut self.node_mut(idx).value)
+    }
+
+    /// Removes the exact entry behind `handle`, even among equal keys.
+    pub fn remove_handle(&mut self, handle: Handle) -> Option<(K, V)> {
+        let idx = self.resolve(handle)?;
+        Some(self.erase(idx))
+    }
+
+    pub fn first_handle(&self) -> Option<Handle> {
+        self.handle(self.leftmost())
+    }
+
+    pub(crate) fn handle(&self, idx: usize) -> Option<Handle> {
+        (idx != NIL).then(|| Handle {
+            index: idx,
+            generation: self.nodes.generation(idx),
+        })
+    }
+
+    pub(crate) fn resolve(&self, handle: Handle) -> Option<usize> {
+        self.nodes.resolve(handle.index, handle.generation)
+    }
+
+    /// Root of the tree, for walks that steer by the subtree aggregates.
+    pub fn root_node(&self) -> Option<NodeRef<'_, K, V, A>> {
+        self.node_ref(self.root)
+    }
+
+    /// Aggregate of the whole tree, i.e. the one cached at the root.
+    pub fn root_aggregate(&self) -> Option<&A::Value> {
+        self.root_node().map(|node| node.aggregate())
+    }
+
+    /// Height of the tree counting only black nodes on any root-to-leaf path.
+    pub fn black_height(&self) -> usize {
+        let mut height = 0;
+        let mut idx = self.root;
+        while idx != NIL {
+            if self.color(idx) == Color::Black {
+                height += 1;
+            }
+            idx = self.left(idx);
+        }
+        height
+    }
+
+    pub(crate) fn root_idx(&self) -> usize {
+        self.root
+    }
+
+    pub(crate) fn node(&self, idx: usize) -> &Node<K, V, A::Value> {
+        self.nodes.get(idx).expect("dangling rbtree index")
+    }
+
+    pub(crate) fn node_mut(&mut self, idx: usize) -> &mut Node<K, V, A::Value> {
+        self.nodes.get_mut(idx).expect("dangling rbtree index")
+    }
+
+    pub(crate) fn node_ref(&self, idx: usize) -> Option<NodeRef<'_, K, V, A>> {
+        (idx != NIL).then_some(NodeRef { tree: self, idx })
+    }
+
+    pub(crate) fn entry_at(&self, idx: usize) -> Option<(&K, &V)> {
+        if idx == NIL {
+            return None;
+        }
+        let node = self.node(idx);
+        Some((&node.key, &node.value))
+    }
+
+    pub(crate) fn left(&self, idx: usize) -> usize {
+        self.node(idx).left
+    }
+
+    pub(crate) fn right(&self, idx: usize) -> usize {
+        self.node(idx).right
+    }
+
+    pub(crate) fn parent(&self, idx: usize) -> usize {
+        self.node(idx).parent
+    }
+
+    pub(crate) fn color(&self, idx: usize) -> Color {
+        if idx == NIL {
+            Color::Black
+        } else {
+            self.node(idx).color
+        }
+    }
+
+    fn set_color(&mut self, idx: usize, color: Color) {
+        if idx != NIL {
+            self.node_mut(idx).color = color;
+        }
+    }
+
+    fn set_parent(&mut self, idx: usize, parent: usize) {
+        if idx != NIL {
+            self.node_mut(idx).parent = parent;
+        }
+    }
+
+    pub(crate) fn leftmost(&self) -> usize {
+        if self.root == NIL {
+            NIL
+        } else {
+            self.subtree_min(self.root)
+        }
+    }
+
+    pub(crate) fn rightmost(&self) -> usize {
+        let mut idx = self.root;
+        if idx == NIL {
+            return NIL;
+        }
+        while self.right(idx) != NIL {
+            idx = self.right(idx);
+        }
+        idx
+    }
+
+    fn subtree_min(&self, mut idx: usize) -> usize {
+        while self.left(idx) != NIL {
+            idx = self.left(idx);
+        }
+        idx
+    }
+
+    fn subtree_max(&self, mut idx: usize) -> usize {
+        while self.right(idx) != NIL {
+            idx = self.right(idx);
+        }
+        idx
+    }
+
+    pub(crate) fn next(&self, idx: usize) -> usize {
+        if self.right(idx) != NIL {
+            return self.subtree_min(self.right(idx));
+        }
+        let mut child = idx;
+        let mut parent = self.parent(idx);
+        while parent != NIL && child == self.right(parent) {
+            child = parent;
+            parent = self.parent(parent);
+        }
+        parent
+    }
+
+    pub(crate) fn prev(&self, idx: usize) -> usize {
+        if self.left(idx) != NIL {
+            return self.subtree_max(self.left(idx));
+        }
+        let mut child = idx;
+        let mut parent = self.parent(idx);
+        while parent != NIL && child == self.left(parent) {
+            child = parent;
+            parent = self.parent(parent);
+        }
+        parent
+    }
+
+    fn replace_child(&mut self, parent: usize, old: usize, new: usize) {
+        if parent == NIL {
+            self.root = new;
+        } else if self.left(parent) == old {
+            self.node_mut(parent).left = new;
+        } else {
+            self.node_mut(parent).right = new;
+        }
+    }
+
+    fn rotate_left(&mut self, x: usize) {
+        let y = self.right(x);
+        let y_left = self.left(y);
+        self.node_mut(x).right = y_left;
+        self.set_parent(y_left, x);
+        let x_parent = self.parent(x);
+        self.node_mut(y).parent = x_parent;
+        self.replace_child(x_parent, x, y);
+        self.node_mut(y).left = x;
+        self.node_mut(x).parent = y;
+        self.recompute(x);
+        self.recompute(y);
+    }
+
+    fn rotate_right(&mut self, x: usize) {
+        let y = self.left(x);
+        let y_right = self.right(y);
+        self.node_mut(x).left = y_right;
+        self.set_parent(y_right, x);
+        let x_parent = self.parent(x);
+        self.node_mut(y).parent = x_parent;
+        self.replace_child(x_parent, x, y);
+        self.node_mut(y).right = x;
+        self.node_mut(x).parent = y;
+        self.recompute(x);
+        self.recompute(y);
+    }
+
+    pub(crate) fn recompute(&mut self, idx: usize) {
+        let node = self.node(idx);
+        let left = self.node_ref(node.left).map(|n| n.aggregate());
+        let right = self.node_ref(node.right).map(|n| n.aggregate());
+        let aug = A::compute(&node.key, &node.value, left, right);
+        self.node_mut(idx).aug = aug;
+    }
+
+    /// Recomputes the aggregates from `idx` up to the root.
+    pub(crate) fn propagate(&mut self, mut idx: usize) {
+        // A zero-sized aggregate carries no information, so plain trees skip
+        // the walk entirely.
+        if size_of::<A::Value>() == 0 {
+            return;
+        }
+        while idx != NIL {
+            self.recompute(idx);
+            idx = self.parent(idx);
+        }
+    }
+
+    /// Links a new red node as the `left` or `right` child of `parent` (or as
+    /// the root when `parent` is `NIL`) and rebalances.
+    pub(crate) fn link(&mut self, parent: usize, left: bool, key: K, value: V) -> usize {
+        let aug = A::compute(&key, &value, None, None);
+        let idx = self.nodes.insert(Node {
+            key,
+            value,
+            aug,
+            color: Color::Red,
+            parent,
+            left: NIL,
+            right: NIL,
+        });
+        if parent == NIL {
+            self.root = idx;
+        } else if left {
+            self.node_mut(parent).left = idx;
+        } else {
+            self.node_mut(parent).right = idx;
+        }
+        self.propagate(parent);
+        self.insert_fixup(idx);
+        idx
+    }
+
+    fn insert_fixup(&mut self, mut z: usize) {
+        while self.color(self.parent(z)) == Color::Red {
+            let mut parent = self.parent(z);
+            let grand = self.parent(parent);
+            if parent == self.left(grand) {
+                let uncle = self.right(grand);
+                if self.color(uncle) == Color::Red {
+                    self.set_color(parent, Color::Black);
+                    self.set_color(uncle, Color::Black);
+                    self.set_color(grand, Color::Red);
+                    z = grand;
+                    continue;
+                }
+                if z == self.right(parent) {
+                    z = parent;
+                    self.rotate_left(z);
+                    parent = self.parent(z);
+                }
+                self.set_color(parent, Color::Black);
+                self.set_color(grand, Color::Red);
+                self.rotate_right(grand);
+            } else {
+                let uncle = self.left(grand);
+                if self.color(uncle) == Color::Red {
+                    self.set_color(parent, Color::Black);
+                    self.set_color(uncle, Color::Black);
+                    self.set_color(grand, Color::Red);
+                    z = grand;
+                    continue;
+                }
+                if z == self.left(parent) {
+                    z = parent;
+                    self.rotate_right(z);
+                    parent = self.parent(z);
+                }
+                self.set_color(parent, Color::Black);
+                self.set_color(grand, Color::Red);
+                self.rotate_left(grand);
+            }
+        }
+        let root = self.root;
+        self.set_color(root, Color::Black);
+    }
+
+    fn transplant(&mut self, u: usize, v: usize) {
+        let parent = self.parent(u);
+        self.replace_child(parent, u, v);
+        self.set_parent(v, parent);
+    }
+
+    /// Unlinks the node at `idx`, rebalances and returns its entry.
+    pub(crate) fn erase(&mut self, z: usize) -> (K, V) {
+        let mut removed_color = self.color(z);
+        let x;
+        let x_parent;
+        if self.left(z) == NIL {
+            x = self.right(z);
+            x_parent = self.parent(z);
+            self.transplant(z, x);
+        } else if self.right(z) == NIL {
+            x = self.left(z);
+            x_parent = self.parent(z);
+            self.transplant(z, x);
+        } else {
+            let y = self.subtree_min(self.right(z));
+            removed_color = self.color(y);
+            x = self.right(y);
+            if self.parent(y) == z {
+                x_parent = y;
+            } else {
+                x_parent = self.parent(y);
+                self.transplant(y, x);
+                let z_right = self.right(z);
+                self.node_mut(y).right = z_right;
+                self.set_parent(z_right, y);
+            }
+            self.transplant(z, y);
+            let z_left = self.left(z);
+            self.node_mut(y).left = z_left;
+            self.set_parent(z_left, y);
+            let z_color = self.color(z);
+            self.set_color(y, z_color);
+        }
+        self.propagate(x_parent);
+        if removed_color == Color::Black {
+            self.erase_fixup(x, x_parent);
+        }
+        let node = self.nodes.remove(z);
+        (node.key, node.value)
+    }
+
+    fn erase_fixup(&mut self, mut x: usize, mut parent: usize) {
+        while x != self.root && self.color(x) == Color::Black {
+            if x == self.left(parent) {
+                let mut w = self.right(parent);
+                if self.color(w) == Color::Red {
+                    self.set_color(w, Color::Black);
+                    self.set_color(parent, Color::Red);
+                    self.rotate_left(parent);
+                    w = self.right(parent);
+                }
+                if self.color(self.left(w)) == Color::Black
+                    && self.color(self.right(w)) == Color::Black
+                {
+                    self.set_color(w, Color::Red);
+                    x = parent;
+                    parent = self.parent(x);
+                } else {
+                    if self.color(self.right(w)) == Color::Black {
+                        let w_left = self.left(w);
+                        self.set_color(w_left, Color::Black);
+                        self.set_color(w, Color::Red);
+                        self.rotate_right(w);
+                        w = self.right(parent);
+                    }
+                    let parent_color = self.color(parent);
+                    self.set_color(w, parent_color);
+                    self.set_color(parent, Color::Black);
+                    let w_right = self.right(w);
+                    self.set_color(w_right, Color::Black);
+                    self.rotate_left(parent);
+                    x = self.root;
+                }
+            } else {
+                let mut w = self.left(parent);
+                if self.color(w) == Color::Red {
+                    self.set_color(w, Color::Black);
+                    self.set_color(parent, Color::Red);
+                    self.rotate_right(parent);
+                    w = self.left(parent);
+                }
+                if self.color(self.left(w)) == Color::Black
+                    && self.color(self.right(w)) == Color::Black
+                {
+                    self.set_color(w, Color::Red);
+                    x = parent;
+                    parent = self.parent(x);
+                } else {
+                    if self.color(self.left(w)) == Color::Black {
+                        let w_right = self.right(w);
+                        self.set_color(w_right, Color::Black);
+                        self.set_color(w, Color::Red);
+                        self.rotate_left(w);
+                        w = self.left(parent);
+                    }
+                    let parent_color = self.color(parent);
+                    self.set_color(w, parent_color);
+                    self.set_color(parent, Color::Black);
+                    let w_left = self.left(w);
+                    self.set_color(w_left, Color::Black);
+                    self.rotate_right(parent);
+                    x = self.root;
+                }
+            }
+        }
+        self.set_color(x, Color::Black);
+    }
+}
+
+impl<K: Ord, V, A: Augment<K, V>> RbTree<K, V, A> {
+    /// Inserts `key`, returning the previous value if the key was present.
+    /// When the tree holds duplicates, the oldest equal entry is replaced.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.insert_idx(key, value).1
+    }
+
+    /// Like [`RbTree::insert`] but also returns the index of the node that
+    /// now holds `key`.
+    pub(crate) fn insert_idx(&mut self, key: K, value: V) -> (usize, Option<V>) {
+        let mut parent = NIL;
+        let mut idx = self.root;
+        let mut left = false;
+        while idx != NIL {
+            parent = idx;
+            match key.cmp(&self.node(idx).key) {
+                Ordering::Less => {
+                    left = true;
+                    idx = self.left(idx);
+                }
+                Ordering::Greater => {
+                    left = false;
+                    idx = self.right(idx);
+                }
+                Ordering::Equal => {
+                    let idx = self.find(&key);
+                    let old = std::mem::replace(&mut self.node_mut(idx).value, value);
+                    self.propagate(idx);
+                    return (idx, Some(old));
+                }
+            }
+        }
+        (self.link(parent, left, key, value), None)
+    }
+
+    /// Inserts `key` even if equal keys are already present. The new entry
+    /// goes after all of them, so equal keys come out in insertion order.
+    pub fn insert_multi(&mut self, key: K, value: V) -> Handle {
+        let idx = self.insert_multi_idx(key, value);
+        Handle {
+            index: idx,
+            generation: self.nodes.generation(idx),
+        }
+    }
+
+    pub(crate) fn insert_multi_idx(&mut self, key: K, value: V) -> usize {
+        let mut parent = NIL;
+        let mut idx = self.root;
+        let mut left = false;
+        while idx != NIL {
+            parent = idx;
+            left = key < self.node(idx).key;
+            idx = if left {
+                self.left(idx)
+            } else {
+                self.right(idx)
+            };
+        }
+        self.link(parent, left, key, value)
+    }
+
+    /// Removes the oldest entry equal to `key`.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match self.find(key) {
+            NIL => None,
+            idx => Some(self.erase(idx)),
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.entry_at(self.find(key)).map(|(_, v)| v)
+    }
+
+    /// Mutable access to a value. On an augmented tree whose aggregate
+    /// depends on the value, use [`RbTree::update`] instead so the cached
+    /// summaries stay correct.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match self.find(key) {
+            NIL => None,
+            idx => Some(&mut self.node_mut(idx).value),
+        }
+    }
+
+    /// Applies `f` to the value stored under `key` and refreshes the
+    /// aggregates above it. Returns `false` if the key is absent.
+    pub fn update<Q, F>(&mut self, key: &Q, f: F) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        F: FnOnce(&mut V),
+    {
+        match self.find(key) {
+            NIL => false,
+            idx => {
+                f(&mut self.node_mut(idx).value);
+                self.propagate(idx);
+                true
+            }
+        }
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.find(key) != NIL
+    }
+
+    /// Index of the oldest entry equal to `key`, or `NIL`.
+    pub(crate) fn find<Q>(&self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut idx = self.root;
+        let mut found = NIL;
+        while idx != NIL {
+            match key.cmp(self.node(idx).key.borrow()) {
+                Ordering::Less => idx = self.left(idx),
+                Ordering::Greater => idx = self.right(idx),
+                Ordering::Equal => {
+                    found = idx;
+                    idx = self.left(idx);
+                }
+            }
+        }
+        found
+    }
+
+    /// Checks the red-black properties (black root, no red node with a red
+    /// child, equal black height on every path) plus parent links and key
+    /// order. Returns the black height on success.
+    pub fn check_invariants(&self) -> Result<usize, InvariantError> {
+        if self.color(self.root) == Color::Red {
+            return Err(InvariantError::RedRoot);
+        }
+        if self.root != NIL && self.parent(self.root) != NIL {
+            return Err(InvariantError::BrokenLink { node: self.root });
+        }
+        let mut count = 0;
+        let height = self.check_subtree(self.root, &mut count)?;
+        if count != self.len() {
+            return Err(InvariantError::Len {
+                expected: self.len(),
+                found: count,
+            });
+        }
+        let mut prev: Option<&K> = None;
+        let mut idx = self.leftmost();
+        while idx != NIL {
+            let key = &self.node(idx).key;
+            if prev.is_some_and(|p| p > key) {
+                return Err(InvariantError::Unordered { node: idx });
+            }
+            prev = Some(key);
+            idx = self.next(idx);
+        }
+        Ok(height)
+    }
+
+    fn check_subtree(&self, idx: usize, count: &mut usize) -> Result<usize, InvariantError> {
+        if idx == NIL {
+            return Ok(1);
+        }
+        *count += 1;
+        let (left, right) = (self.left(idx), self.right(idx));
+        for child in [left, right] {
+            if child != NIL && self.parent(child) != idx {
+                return Err(InvariantError::BrokenLink { node: child });
+            }
+        }
+        if self.color(idx) == Color::Red
+            && (self.color(left) == Color::Red || self.color(right) == Color::Red)
+        {
+            return Err(InvariantError::RedRed { node: idx });
+        }
+        let node = self.node(idx);
+        let expected = A::compute(
+            &node.key,
+            &node.value,
+            self.node_ref(left).map(|n| n.aggregate()),
+            self.node_ref(right).map(|n| n.aggregate()),
+        );
+        if expected != node.aug {
+            return Err(InvariantError::StaleAggregate { node: idx });
+        }
+        let left_height = self.check_subtree(left, count)?;
+        let right_height = self.check_subtree(right, count)?;
+        if left_height != right_height {
+            return Err(InvariantError::BlackHeight { node: idx });
+        }
+        Ok(left_height + usize::from(self.color(idx) == Color::Black))
+    }
+}
+
+impl<K: Clone, V: Clone, A: Augment<K, V>> Clone for RbTree<K, V, A>
+where
+    A::Value: Clone,
+{
+    fn clone(&self) -> Self {
+        RbTree {
+            nodes: self.nodes.clone(),
+            root: self.root,
+            augment: PhantomData,
+        }
+    }
+}
+
+impl<K, V, A: Augment<K, V>> Default for RbTree<K, V, A> {
+    fn default() -> Self {
+        Self::new_augmented()
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, A: Augment<K, V>> fmt::Debug for RbTree<K, V, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Ord, V, A: Augment<K, V>> FromIterator<(K, V)> for RbTree<K, V, A> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut tree = RbTree::new_augmented();
+        tree.extend(iter);
+        tree
+    }
+}
+
+impl<K: Ord, V, A: Augment<K, V>> Extend<(K, V)> for RbTree<K, V, A> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<'a, K, V, A: Augment<K, V>> IntoIterator for &'a RbTree<K, V, A> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, A>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// In-order iterator over the entries of an [`RbTree`].
+pub struct Iter<'a, K, V, A: Augment<K, V> = ()> {
+    tree: &'a RbTree<K, V, A>,
+    front: usize,
+    back: usize,
+    remaining: usize,
+}
+
+impl<'a, K, V, A: Augment<K, V>> Iterator for Iter<'a, K, V, A> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let idx = self.front;
+        self.remaining -= 1;
+        self.front = self.tree.next(idx);
+        self.tree.entry_at(idx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V, A: Augment<K, V>> DoubleEndedIterator for Iter<'_, K, V, A> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let idx = self.back;
+        self.remaining -= 1;
+        self.back = self.tree.prev(idx);
+        self.tree.entry_at(idx)
+    }
+}
+
+impl<K, V, A: Augment<K, V>> ExactSizeIterator for Iter<'_, K, V, A> {}
+
+impl<K, V, A: Augment<K, V>> FusedIterator for Iter<'_, K, V, A> {}
+
+/// Read-only view of a single node, used to walk the tree by hand.
+pub struct NodeRef<'a, K, V, A: Augment<K, V> = ()> {
+    tree: &'a RbTree<K, V, A>,
+    idx: usize,
+}
+
+impl<K, V, A: Augment<K, V>> Clone for NodeRef<'_, K, V, A> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K, V, A: Augment<K, V>> Copy for NodeRef<'_, K, V, A> {}
+
+impl<'a, K, V, A: Augment<K, V>> NodeRef<'a, K, V, A> {
+    pub fn handle(&self) -> Handle {
+        self.tree.handle(self.idx).unwrap()
+    }
+
+    pub fn key(&self) -> &'a K {
+        &self.tree.node(self.idx).key
+    }
+
+    pub fn value(&self) -> &'a V {
+        &self.tree.node(self.idx).value
+    }
+
+    pub fn aggregate(&self) -> &'a A::Value {
+        &self.tree.node(self.idx).aug
+    }
+
+    pub fn color(&self) -> Color {
+        self.tree.color(self.idx)
+    }
+
+    pub fn left(&self) -> Option<NodeRef<'a, K, V, A>> {
+        self.tree.node_ref(self.tree.left(self.idx))
+    }
+
+    pub fn right(&self) -> Option<NodeRef<'a, K, V, A>> {
+        self.tree.node_ref(self.tree.right(self.idx))
+    }
+
+    pub fn parent(&self) -> Option<NodeRef<'a, K, V, A>> {
+        self.tree.node_ref(self.tree.parent(self.idx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shuffled(n: u64, seed: u64) -> Vec<u64> {
+        let mut keys: Vec<u64> = (0..n).collect();
+        let mut state = seed;
+        for i in (1..keys.len()).rev() {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            keys.swap(i, (state >> 33) as usize % (i + 1));
+        }
+        keys
+    }
+
+    #[test]
+    fn insert_and_lookup() {
+        let mut tree = RbTree::new();
+        assert_eq!(tree.insert(5, "five"), None);
+        assert_eq!(tree.insert(1, "one"), None);
+        assert_eq!(tree.insert(9, "nine"), None);
+        assert_eq!(tree.insert(5, "FIVE"), Some("five"));
+
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree.get(&5), Some(&"FIVE"));
+        assert_eq!(tree.get(&7), None);
+        assert_eq!(tree.first(), Some((&1, &"one")));
+        assert_eq!(tree.last(), Some((&9, &"nine")));
+        assert!(tree.check_invariants().is_ok());
+    }
+
+    #[test]
+    fn iterates_in_order() {
+        let tree: RbTree<u64, ()> = shuffled(200, 7).into_iter().map(|k| (k, ())).collect();
+        let keys: Vec<u64> = tree.keys().copied().collect();
+        assert_eq!(keys, (0..200).collect::<Vec<_>>());
+        let rev: Vec<u64> = tree.keys().rev().copied().collect();
+        assert_eq!(rev, (0..200).rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn churn_keeps_invariants() {
+        let mut tree = RbTree::new();
+        for key in shuffled(1000, 1) {
+            tree.insert(key, key * 2);
+            tree.check_invariants().unwrap();
+        }
+        for key in shuffled(1000, 2).into_iter().take(700) {
+            assert_eq!(tree.remove(&key), Some(key * 2));
+            tree.check_invariants().unwrap();
+        }
+        assert_eq!(tree.len(), 300);
+        while let Some((key, value)) = tree.pop_first() {
+            assert_eq!(value, key * 2);
+            tree.check_invariants().unwrap();
+        }
+        assert!(tree.is_empty());
+    }
+
+    struct MinValue;
+
+    impl Augment<u64, u64> for MinValue {
+        type Value = u64;
+
+        fn compute(_: &u64, value: &u64, left: Option<&u64>, right: Option<&u64>) -> u64 {
+            [Some(value), left, right]
+                .into_iter()
+                .flatten()
+                .copied()
+                .min()
+                .unwrap()
+        }
+    }
+
+    #[test]
+    fn augmented_churn_keeps_aggregates() {
+        let mut tree: RbTree<u64, u64, MinValue> = RbTree::new_augmented();
+        for key in shuffled(500, 3) {
+            tree.insert(key, (key * 7919) % 1000);
+            tree.check_invariants().unwrap();
+        }
+        for key in shuffled(500, 4).into_iter().take(400) {
+            tree.remove(&key);
+            tree.check_invariants().unwrap();
+            let min = tree.values().copied().min();
+            assert_eq!(tree.root_aggregate().copied(), min);
+        }
+        let first = *tree.first().unwrap().0;
+        assert!(tree.update(&first, |v| *v = 0));
+        assert_eq!(tree.root_aggregate(), Some(&0));
+        tree.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn descends_by_aggregate() {
+        let tree: RbTree<u64, u64, MinValue> = shuffled(100, 5)
+            .into_iter()
+            .map(|k| (k, 1000 - k * k % 997))
+            .collect();
+        let mut node = tree.root_node().unwrap();
+        let target = *node.aggregate();
+        while *node.value() != target {
+            node = [node.left(), node.right()]
+                .into_iter()
+                .flatten()
+                .find(|child| *child.aggregate() == target)
+                .unwrap();
+        }
+        let expected = tree.iter().find(|(_, v)| **v == target).unwrap();
+        assert_eq!(node.key(), expected.0);
+        assert_eq!(tree.get_handle(node.handle()), Some(expected));
+    }
+
+    #[test]
+    fn duplicates_keep_insertion_order() {
+        let mut tree = RbTree::new();
+        let mut handles = Vec::new();
+        for (i, key) in [3, 1, 3, 2, 3, 1].into_iter().enumerate() {
+            handles.push(tree.insert_multi(key, i));
+            tree.check_invariants().unwrap();
+        }
+        let entries: Vec<(i32, usize)> = tree.iter().map(|(k, v)| (*k, *v)).collect();
+        assert_eq!(entries, [(1, 1), (1, 5), (2, 3), (3, 0), (3, 2), (3, 4)]);
+        assert_eq!(tree.get(&3), Some(&0));
+
+        assert_eq!(tree.remove_handle(handles[2]), Some((3, 2)));
+        assert_eq!(tree.remove_handle(handles[2]), None);
+        tree.check_invariants().unwrap();
+        assert_eq!(tree.remove(&3), Some(0));
+        assert_eq!(tree.get_handle(handles[4]), Some((&3, &4)));
+        assert_eq!(tree.first_handle(), Some(handles[1]));
+    }
+
+    #[test]
+    fn handles_outlive_other_entries_only() {
+        let mut tree = RbTree::new();
+        let keep = tree.insert_multi(10, "keep");
+        let gone = tree.insert_multi(20, "gone");
+        for key in 0..100 {
+            tree.insert_multi(key, "filler");
+        }
+        assert_eq!(tree.get_handle(keep), Some((&10, &"keep")));
+
+        tree.remove_handle(gone);
+        let reused = tree.insert_multi(30, "reused");
+        assert_eq!(reused.index(), gone.index());
+        assert_eq!(tree.get_handle(gone), None);
+        assert_eq!(tree.remove_handle(gone), None);
+        assert_eq!(tree.get_handle(reused), Some((&30, &"reused")));
+
+        tree.clear();
+        assert_eq!(tree.get_handle(keep), None);
+    }
+
+    #[test]
+    fn detects_broken_invariants() {
+        let mut tree: RbTree<u32, ()> = (0..16).map(|k| (k, ())).collect();
+        let root = tree.root;
+        tree.node_mut(root).color = Color::Red;
+        assert_eq!(tree.check_invariants(), Err(InvariantError::RedRoot));
+    }
+}
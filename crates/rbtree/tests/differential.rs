@@ -0,0 +1,269 @@
+//! Runs random operation sequences against `BTreeMap` as an oracle and checks
+//! the red-black invariants after every step.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Bound,
+};
+
+use proptest::prelude::*;
+use rbtree::{Augment, CachedRbTree, Handle, RbTree};
+
+#[derive(Clone, Debug)]
+enum MapOp {
+    Insert(u8, u32),
+    Remove(u8),
+    Get(u8),
+    PopFirst,
+    PopLast,
+    Update(u8, u32),
+    Range(u8, u8),
+}
+
+fn map_op() -> impl Strategy<Value = MapOp> {
+    prop_oneof![
+        4 => (any::<u8>(), any::<u32>()).prop_map(|(k, v)| MapOp::Insert(k, v)),
+        3 => any::<u8>().prop_map(MapOp::Remove),
+        1 => any::<u8>().prop_map(MapOp::Get),
+        1 => Just(MapOp::PopFirst),
+        1 => Just(MapOp::PopLast),
+        1 => (any::<u8>(), any::<u32>()).prop_map(|(k, v)| MapOp::Update(k, v)),
+        1 => (any::<u8>(), any::<u8>()).prop_map(|(a, b)| MapOp::Range(a, b)),
+    ]
+}
+
+#[derive(Clone, Debug)]
+enum MultiOp {
+    Insert(u8),
+    RemoveKey(u8),
+    RemoveHandle(usize),
+    PopFirst,
+}
+
+fn multi_op() -> impl Strategy<Value = MultiOp> {
+    prop_oneof![
+        4 => (0u8..16).prop_map(MultiOp::Insert),
+        1 => (0u8..16).prop_map(MultiOp::RemoveKey),
+        2 => any::<usize>().prop_map(MultiOp::RemoveHandle),
+        1 => Just(MultiOp::PopFirst),
+    ]
+}
+
+struct MinValue;
+
+impl Augment<u8, u32> for MinValue {
+    type Value = u32;
+
+    fn compute(_: &u8, value: &u32, left: Option<&u32>, right: Option<&u32>) -> u32 {
+        [Some(value), left, right]
+            .into_iter()
+            .flatten()
+            .copied()
+            .min()
+            .unwrap()
+    }
+}
+
+fn entries<'a>(iter: impl Iterator<Item = (&'a u8, &'a u32)>) -> Vec<(u8, u32)> {
+    iter.map(|(k, v)| (*k, *v)).collect()
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(128))]
+
+    #[test]
+    fn map_matches_btreemap(ops in prop::collection::vec(map_op(), 1..300)) {
+        let mut oracle = BTreeMap::new();
+        let mut tree = RbTree::new();
+        let mut cached = CachedRbTree::new();
+        let mut augmented: RbTree<u8, u32, MinValue> = RbTree::new_augmented();
+
+        for op in ops {
+            match op {
+                MapOp::Insert(k, v) => {
+                    let expected = oracle.insert(k, v);
+                    prop_assert_eq!(tree.insert(k, v), expected);
+                    prop_assert_eq!(cached.insert(k, v), expected);
+                    prop_assert_eq!(augmented.insert(k, v), expected);
+                }
+                MapOp::Remove(k) => {
+                    let expected = oracle.remove(&k);
+                    prop_assert_eq!(tree.remove(&k), expected);
+                    prop_assert_eq!(cached.remove(&k), expected);
+                    prop_assert_eq!(augmented.remove(&k), expected);
+                }
+                MapOp::Get(k) => {
+                    prop_assert_eq!(tree.get(&k), oracle.get(&k));
+                    prop_assert_eq!(cached.get(&k), oracle.get(&k));
+                }
+                MapOp::PopFirst => {
+                    let expected = oracle.pop_first();
+                    prop_assert_eq!(tree.pop_first(), expected);
+                    prop_assert_eq!(cached.pop_first(), expected);
+                    prop_assert_eq!(augmented.pop_first(), expected);
+                }
+                MapOp::PopLast => {
+                    let expected = oracle.pop_last();
+                    prop_assert_eq!(tree.pop_last(), expected);
+                    prop_assert_eq!(augmented.pop_last(), expected);
+                    if let Some((k, _)) = expected {
+                        cached.remove(&k);
+                    }
+                }
+                MapOp::Update(k, v) => {
+                    let expected = oracle.get_mut(&k).map(|old| *old = v).is_some();
+                    prop_assert_eq!(tree.update(&k, |old| *old = v), expected);
+                    prop_assert_eq!(augmented.update(&k, |old| *old = v), expected);
+                    cached.update(&k, |old| *old = v);
+                }
+                MapOp::Range(a, b) => {
+                    let (lo, hi) = (a.min(b), a.max(b));
+                    let expected = entries(oracle.range(lo..hi));
+                    prop_assert_eq!(entries(tree.range(lo..hi)), expected.clone());
+                    prop_assert_eq!(entries(cached.range(lo..hi)), expected);
+                    let bounds = (Bound::Excluded(lo), Bound::Included(hi));
+                    prop_assert_eq!(
+                        entries(tree.range(bounds).rev()),
+                        entries(oracle.range(bounds).rev())
+                    );
+                }
+            }
+
+            prop_assert!(tree.check_invariants().is_ok());
+            prop_assert!(cached.check_invariants().is_ok());
+            prop_assert!(augmented.check_invariants().is_ok());
+            prop_assert_eq!(tree.len(), oracle.len());
+            prop_assert_eq!(cached.first(), oracle.first_key_value());
+            prop_assert_eq!(tree.last(), oracle.last_key_value());
+            prop_assert_eq!(augmented.root_aggregate().copied(), oracle.values().copied().min());
+        }
+
+        prop_assert_eq!(entries(tree.iter()), entries(oracle.iter()));
+        prop_assert_eq!(entries(cached.iter()), entries(oracle.iter()));
+        prop_assert_eq!(entries(tree.iter().rev()), entries(oracle.iter().rev()));
+    }
+
+    #[test]
+    fn multimap_keeps_fifo_order(ops in prop::collection::vec(multi_op(), 1..300)) {
+        // Equal keys are ordered by a sequence number, which is exactly the
+        // insertion order the tree must preserve.
+        let mut oracle: BTreeMap<(u8, u32), ()> = BTreeMap::new();
+        let mut handles: HashMap<u32, Handle> = HashMap::new();
+        let mut tree: CachedRbTree<u8, u32> = CachedRbTree::new();
+        let mut seq = 0;
+
+        for op in ops {
+            match op {
+                MultiOp::Insert(k) => {
+                    handles.insert(seq, tree.insert_multi(k, seq));
+                    oracle.insert((k, seq), ());
+                    seq += 1;
+                }
+                MultiOp::RemoveKey(k) => {
+                    let expected = oracle.range((k, 0)..=(k, u32::MAX)).next().map(|(e, _)| *e);
+                    if let Some(e) = expected {
+                        oracle.remove(&e);
+                    }
+                    prop_assert_eq!(tree.remove_entry(&k), expected);
+                }
+                MultiOp::RemoveHandle(pick) => {
+                    if seq == 0 {
+                        continue;
+                    }
+                    let victim = pick as u32 % seq;
+                    let handle = handles[&victim];
+                    let expected = oracle
+                        .keys()
+                        .find(|(_, s)| *s == victim)
+                        .copied();
+                    if let Some(e) = expected {
+                        oracle.remove(&e);
+                    }
+                    prop_assert_eq!(tree.remove_handle(handle), expected);
+                    prop_assert_eq!(tree.get_handle(handle), None);
+                }
+                MultiOp::PopFirst => {
+                    prop_assert_eq!(tree.pop_first(), oracle.pop_first().map(|(e, _)| e));
+                }
+            }
+
+            prop_assert!(tree.check_invariants().is_ok());
+            prop_assert_eq!(tree.len(), oracle.len());
+        }
+
+        let expected: Vec<(u8, u32)> = oracle.keys().copied().collect();
+        prop_assert_eq!(entries(tree.iter()), expected);
+        for (key, seq) in oracle.keys() {
+            prop_assert_eq!(tree.get_handle(handles[seq]), Some((key, seq)));
+        }
+    }
+
+    #[test]
+    fn cursor_edits_match_btreemap(
+        keys in prop::collection::btree_set(any::<u8>(), 0..64),
+        steps in prop::collection::vec((any::<bool>(), 0u8..4), 0..100),
+    ) {
+        let mut oracle: BTreeMap<u8, u32> = keys.iter().map(|&k| (k, k as u32)).collect();
+        let mut tree: CachedRbTree<u8, u32> = oracle.iter().map(|(k, v)| (*k, *v)).collect();
+        let mut cursor = tree.cursor_front_mut();
+
+        for (forward, action) in steps {
+            if forward {
+                cursor.move_next();
+            } else {
+                cursor.move_prev();
+            }
+            let current = cursor.peek().map(|(k, _)| *k);
+            match action {
+                0 => {
+                    let removed = cursor.remove_current();
+                    prop_assert_eq!(removed.map(|(k, _)| k), current);
+                    if let Some(k) = current {
+                        oracle.remove(&k);
+                    }
+                }
+                1 => {
+                    // Insert the key right after the current one when it is free.
+                    let candidate = current.map_or(0, |k| k.wrapping_add(1));
+                    let fits = !oracle.contains_key(&candidate)
+                        && current.is_none_or(|k| candidate > k)
+                        && oracle.range(..candidate).next_back().map(|(k, _)| *k) == current;
+                    if fits {
+                        prop_assert!(cursor.insert_after(candidate, 0).is_ok());
+                        oracle.insert(candidate, 0);
+                    }
+                }
+                _ => {
+                    let next = cursor.peek_next().map(|(k, _)| *k);
+                    let expected = match current {
+                        Some(k) => oracle.range((Bound::Excluded(k), Bound::Unbounded)).next(),
+                        None => oracle.iter().next(),
+                    };
+                    prop_assert_eq!(next, expected.map(|(k, _)| *k));
+                }
+            }
+        }
+
+        prop_assert!(tree.check_invariants().is_ok());
+        prop_assert_eq!(entries(tree.iter()), entries(oracle.iter()));
+    }
+
+    #[test]
+    fn snapshot_round_trips(ops in prop::collection::vec(map_op(), 1..200)) {
+        let mut tree = RbTree::new();
+        for op in ops {
+            match op {
+                MapOp::Insert(k, v) => {
+                    tree.insert(k, v);
+                }
+                MapOp::Remove(k) => {
+                    tree.remove(&k);
+                }
+                _ => {}
+            }
+        }
+        let restored: RbTree<u8, u32, MinValue> = RbTree::from_snapshot(tree.snapshot()).unwrap();
+        prop_assert_eq!(entries(restored.iter()), entries(tree.iter()));
+        prop_assert_eq!(restored.root_aggregate().copied(), tree.values().copied().min());
+    }
+}
@@ -1,10 +1,13 @@
+#![allow(clippy::let_and_return, clippy::len_zero)]
+
 use std::{collections::HashMap, fs, num::ParseIntError};
 pub use sysinfo::ProcessStatus;
-use sysinfo::System;
+use sysinfo::{CpuRefreshKind, System};
 
 const NICE_COL: usize = 18;
 const PRIO_COL: usize = 17;
 const RT_PRIO_COL: usize = 39;
+const POLICY_COL: usize = 40;
 
 #[derive(Clone, Debug)]
 pub struct Process {
@@ -12,10 +15,14 @@ pub struct Process {
     pub pid: u32,
     pub run_time: u64,
     pub ram: u64,
+    pub cpu_usage: f32,
     pub nice: Option<i16>,
     pub status: Option<ProcessStatus>,
     pub priority: Option<i16>,
     pub rt_priority: Option<u16>,
+    /// Raw scheduling policy, as in `SCHED_NORMAL` = 0, `SCHED_FIFO` = 1,
+    /// `SCHED_RR` = 2.
+    pub policy: Option<u32>,
     stat: Vec<String>,
     // TODO: add more
 }
@@ -26,8 +33,19 @@ impl Process {
     }
 
     fn read_stat(&mut self) {
-        if let Ok(stat) = fs::read_to_string(format!("/proc/{}/stat", self.pid)) {
-            self.stat = stat.split_whitespace().map(|s| s.to_string()).collect();
+        let Ok(stat) = fs::read_to_string(format!("/proc/{}/stat", self.pid)) else {
+            return;
+        };
+        // The command name is in parentheses and may contain spaces, so it is
+        // cut out before splitting the other fields.
+        if let Some((head, tail)) = stat.rsplit_once(')')
+            && let Some((pid, comm)) = head.split_once(" (")
+        {
+            self.stat = [pid, comm]
+                .into_iter()
+                .chain(tail.split_whitespace())
+                .map(|s| s.to_string())
+                .collect();
         }
     }
 
@@ -45,18 +63,27 @@ impl Process {
         priority
     }
 
+    fn get_policy(&mut self) -> Result<u32, ParseIntError> {
+        self.stat[POLICY_COL].parse::<u32>()
+    }
+
     pub fn refresh(&mut self) {
         self.read_stat();
+        // Older kernels have fewer columns, so each one is checked before it
+        // is read.
         if self.stat.len() > 15 {
-            if self.priority.is_none() {
+            if self.priority.is_none() && self.stat.get(PRIO_COL).is_some() {
                 self.priority = Some(self.get_priority().unwrap_or(0));
             }
-            if self.rt_priority.is_none() {
+            if self.rt_priority.is_none() && self.stat.get(RT_PRIO_COL).is_some() {
                 self.rt_priority = Some(self.get_rt_priority().unwrap_or(0));
             }
-            if self.nice.is_none() {
+            if self.nice.is_none() && self.stat.get(NICE_COL).is_some() {
                 self.nice = Some(self.get_nice().unwrap_or(0));
             }
+            if self.policy.is_none() && self.stat.get(POLICY_COL).is_some() {
+                self.policy = Some(self.get_policy().unwrap_or(0));
+            }
         }
     }
 }
@@ -68,6 +95,7 @@ pub struct ProcessBuilder {
     status: Option<ProcessStatus>,
     run_time: Option<u64>,
     ram: Option<u64>,
+    cpu_usage: f32,
 }
 
 impl ProcessBuilder {
@@ -78,6 +106,7 @@ impl ProcessBuilder {
             status: None,
             run_time: None,
             ram: None,
+            cpu_usage: 0.0,
         }
     }
 
@@ -101,6 +130,11 @@ impl ProcessBuilder {
         self
     }
 
+    fn cpu_usage(&mut self, cpu_usage: f32) -> &mut Self {
+        self.cpu_usage = cpu_usage;
+        self
+    }
+
     fn status(&mut self, status: ProcessStatus) -> &mut Self {
         self.status = Some(status);
         self
@@ -115,15 +149,20 @@ impl ProcessBuilder {
             status: self.status,
             priority: None,
             rt_priority: None,
+            policy: None,
             ram: self.ram.unwrap(),
+            cpu_usage: self.cpu_usage,
             stat: Vec::new(),
         }
     }
 }
 
+#[derive(Default)]
 pub struct SysProbe {
     sys: System,
     pub quantum: u32,
+    /// Logical CPUs, one simulated runqueue each.
+    pub cpus: usize,
     pub processes: HashMap<u32, Process>,
 }
 
@@ -133,11 +172,13 @@ impl SysProbe {
             sys: System::new(),
             processes: HashMap::new(),
             quantum: 0,
+            cpus: 0,
         }
     }
 
     pub fn init(&mut self) {
         self.set_quantum();
+        self.set_cpus();
         self.refresh_processes();
     }
 
@@ -146,6 +187,11 @@ impl SysProbe {
         self.quantum = timeslice.trim().parse::<u32>().unwrap();
     }
 
+    pub fn set_cpus(&mut self) {
+        self.sys.refresh_cpu_list(CpuRefreshKind::nothing());
+        self.cpus = self.sys.cpus().len();
+    }
+
     pub fn refresh_processes(&mut self) {
         self.sys.refresh_all();
 
@@ -175,6 +221,7 @@ impl SysProbe {
                 .run_time(process.run_time())
                 .status(process.status())
                 .ram(process.memory())
+                .cpu_usage(process.cpu_usage())
                 .build();
 
             process_entry.refresh();
@@ -196,6 +243,45 @@ mod tests {
         assert!(sysinfo.quantum == 100);
     }
 
+    #[test]
+    fn cpus() {
+        let mut sysinfo = SysProbe::new();
+        sysinfo.set_cpus();
+
+        assert!(sysinfo.cpus >= 1);
+    }
+
+    #[test]
+    fn stat_fields_line_up() {
+        let mut sysinfo = SysProbe::new();
+        sysinfo.refresh_processes();
+
+        // Names with spaces used to shift every column after the name.
+        for process in sysinfo.processes.values() {
+            assert!(process.nice.is_none_or(|nice| (-20..=19).contains(&nice)));
+            assert!(process.rt_priority.is_none_or(|prio| prio <= 99));
+            assert!(process.policy.is_none_or(|policy| policy <= 6));
+        }
+    }
+
+    #[test]
+    fn short_stat_lines_leave_columns_unset() {
+        // No such process, so refresh keeps the stat line set here.
+        let mut process = Process::builder()
+            .name("old".to_string())
+            .pid(u32::MAX)
+            .run_time(0)
+            .ram(0)
+            .build();
+        process.stat = (0..20).map(|col| col.to_string()).collect();
+        process.refresh();
+
+        assert_eq!(process.nice, Some(NICE_COL as i16));
+        assert_eq!(process.priority, Some(PRIO_COL as i16));
+        assert_eq!(process.rt_priority, None);
+        assert_eq!(process.policy, None);
+    }
+
     #[test]
     fn process_builder() {
         let process = Process::builder()